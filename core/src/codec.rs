@@ -1,9 +1,9 @@
 //! `Ipld` codecs.
-use alloc::{string::String, vec::Vec};
+use alloc::{boxed::Box, string::String, vec::Vec};
 use core::{convert::TryFrom, fmt::Write as _};
 
 use crate::cid::Cid;
-use crate::error::{Result, UnsupportedCodec};
+use crate::error::{with_offset, Result, UnsupportedCodec};
 use crate::io::{Cursor, Read, Seek, Write};
 use crate::ipld::Ipld;
 
@@ -19,8 +19,12 @@ pub trait Codec:
     }
 
     /// Decodes a decodable type.
+    ///
+    /// Errors carry a [`DecodeLocation`](crate::error::DecodeLocation) with the byte offset at
+    /// which decoding failed.
     fn decode<T: Decode<Self>>(&self, bytes: &[u8]) -> Result<T> {
-        T::decode(*self, &mut Cursor::new(bytes))
+        let mut r = Cursor::new(bytes);
+        T::decode(*self, &mut r).map_err(|err| with_offset(err, r.position()))
     }
 
     /// Scrapes the references.
@@ -29,7 +33,8 @@ pub trait Codec:
         bytes: &[u8],
         set: &mut E,
     ) -> Result<()> {
-        T::references(*self, &mut Cursor::new(bytes), set)
+        let mut r = Cursor::new(bytes);
+        T::references(*self, &mut r, set).map_err(|err| with_offset(err, r.position()))
     }
 }
 
@@ -51,6 +56,12 @@ impl<C: Codec, T: Encode<C>> Encode<C> for &T {
     }
 }
 
+impl<C: Codec, T: Encode<C>> Encode<C> for Box<T> {
+    fn encode<W: Write>(&self, c: C, w: &mut W) -> Result<()> {
+        T::encode(self, c, w)
+    }
+}
+
 /// Decode trait.
 ///
 /// This trait is generic over a codec, so that different codecs can be implemented for the same
@@ -63,6 +74,12 @@ pub trait Decode<C: Codec>: Sized {
     fn decode<R: Read + Seek>(c: C, r: &mut R) -> Result<Self>;
 }
 
+impl<C: Codec, T: Decode<C>> Decode<C> for Box<T> {
+    fn decode<R: Read + Seek>(c: C, r: &mut R) -> Result<Self> {
+        Ok(Box::new(T::decode(c, r)?))
+    }
+}
+
 /// References trait.
 ///
 /// This trait is generic over a codec, so that different codecs can be implemented for the same
@@ -4,13 +4,34 @@ use alloc::{
     boxed::Box,
     collections::BTreeMap,
     string::{String, ToString},
+    vec,
     vec::Vec,
 };
 
-use crate::{cid::Cid, ipld::Ipld};
+use crate::{cid::Cid, ipld::Ipld, link::Link};
 
 #[cfg(feature = "std")]
-use crate::error::{Error, TypeError, TypeErrorType};
+use crate::error::{
+    with_path_segment, Error, InvalidLength, PathSegment, TypeError, TypeErrorType,
+};
+
+/// Conversion into [`Ipld`] used by the derived `From<T> for Ipld` implementations.
+///
+/// Unlike `Into<Ipld>` it is implemented for lists, maps and options of convertible types.
+pub trait IntoIpld {
+    /// Converts into ipld.
+    fn into_ipld(self) -> Ipld;
+}
+
+/// Conversion from [`Ipld`] used by the derived `TryFrom<Ipld>` implementations.
+///
+/// Unlike `TryFrom<Ipld>` it is implemented for lists, maps and options of convertible types.
+/// Errors carry the data model path to the value that failed to convert.
+#[cfg(feature = "std")]
+pub trait TryFromIpld: Sized {
+    /// Converts from ipld.
+    fn try_from_ipld(ipld: Ipld) -> Result<Self, Error>;
+}
 
 #[cfg(feature = "std")]
 impl TryFrom<Ipld> for () {
@@ -197,6 +218,185 @@ derive_try_from_ipld_option!(Map, BTreeMap<String, Ipld>);
 #[cfg(feature = "std")]
 derive_try_from_ipld_option!(Link, Cid);
 
+macro_rules! impl_into_ipld {
+    ($($ty:ty),*) => {
+        $(
+            impl IntoIpld for $ty {
+                fn into_ipld(self) -> Ipld {
+                    self.into()
+                }
+            }
+        )*
+    };
+}
+
+impl_into_ipld!(
+    bool,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    u8,
+    u16,
+    u32,
+    u64,
+    usize,
+    f32,
+    f64,
+    String,
+    Box<[u8]>,
+    Cid,
+    Ipld
+);
+
+#[cfg(feature = "std")]
+macro_rules! impl_try_from_ipld {
+    ($($ty:ty),*) => {
+        $(
+            impl TryFromIpld for $ty {
+                fn try_from_ipld(ipld: Ipld) -> Result<Self, Error> {
+                    ipld.try_into()
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "std")]
+impl_try_from_ipld!(
+    bool, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f64, String, Cid
+);
+
+impl<T> IntoIpld for Link<T> {
+    fn into_ipld(self) -> Ipld {
+        Ipld::Link(*self.cid())
+    }
+}
+
+impl<T: IntoIpld> IntoIpld for Option<T> {
+    fn into_ipld(self) -> Ipld {
+        self.map(IntoIpld::into_ipld).unwrap_or(Ipld::Null)
+    }
+}
+
+impl<T: IntoIpld> IntoIpld for Vec<T> {
+    fn into_ipld(self) -> Ipld {
+        Ipld::List(self.into_iter().map(IntoIpld::into_ipld).collect())
+    }
+}
+
+impl<T: IntoIpld> IntoIpld for BTreeMap<String, T> {
+    fn into_ipld(self) -> Ipld {
+        Ipld::Map(
+            self.into_iter()
+                .map(|(key, value)| (key, value.into_ipld()))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFromIpld for Ipld {
+    fn try_from_ipld(ipld: Ipld) -> Result<Self, Error> {
+        Ok(ipld)
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFromIpld for Box<[u8]> {
+    fn try_from_ipld(ipld: Ipld) -> Result<Self, Error> {
+        Ok(Vec::<u8>::try_from(ipld)?.into_boxed_slice())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> TryFromIpld for Link<T> {
+    fn try_from_ipld(ipld: Ipld) -> Result<Self, Error> {
+        Ok(Self::new(ipld.try_into()?))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: TryFromIpld> TryFromIpld for Option<T> {
+    fn try_from_ipld(ipld: Ipld) -> Result<Self, Error> {
+        match ipld {
+            Ipld::Null => Ok(None),
+            ipld => Ok(Some(T::try_from_ipld(ipld)?)),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: TryFromIpld> TryFromIpld for Vec<T> {
+    fn try_from_ipld(ipld: Ipld) -> Result<Self, Error> {
+        match ipld {
+            Ipld::List(list) => list
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    T::try_from_ipld(item)
+                        .map_err(|err| with_path_segment(err, PathSegment::Index(i)))
+                })
+                .collect(),
+            ipld => Err(TypeError::new(TypeErrorType::List, ipld).into()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: TryFromIpld> TryFromIpld for BTreeMap<String, T> {
+    fn try_from_ipld(ipld: Ipld) -> Result<Self, Error> {
+        match ipld {
+            Ipld::Map(map) => map
+                .into_iter()
+                .map(|(key, value)| match T::try_from_ipld(value) {
+                    Ok(value) => Ok((key, value)),
+                    Err(err) => Err(with_path_segment(err, PathSegment::Key(key))),
+                })
+                .collect(),
+            ipld => Err(TypeError::new(TypeErrorType::Map, ipld).into()),
+        }
+    }
+}
+
+/// Converts a map into a list of `[key, value]` pairs, the listpairs representation.
+pub fn into_list_pairs<K: IntoIpld, T: IntoIpld>(map: BTreeMap<K, T>) -> Ipld {
+    Ipld::List(
+        map.into_iter()
+            .map(|(key, value)| Ipld::List(vec![key.into_ipld(), value.into_ipld()]))
+            .collect(),
+    )
+}
+
+/// Converts a list of `[key, value]` pairs, the listpairs representation, into a map.
+#[cfg(feature = "std")]
+pub fn try_from_list_pairs<K: TryFromIpld + Ord, T: TryFromIpld>(
+    ipld: Ipld,
+) -> Result<BTreeMap<K, T>, Error> {
+    let list = match ipld {
+        Ipld::List(list) => list,
+        ipld => return Err(TypeError::new(TypeErrorType::List, ipld).into()),
+    };
+    let mut map = BTreeMap::new();
+    for (i, pair) in list.into_iter().enumerate() {
+        let pair = || -> Result<(K, T), Error> {
+            let [key, value]: [Ipld; 2] = match pair {
+                Ipld::List(pair) => pair.try_into().map_err(|pair: Vec<Ipld>| InvalidLength {
+                    expected: 2,
+                    found: pair.len(),
+                })?,
+                ipld => return Err(TypeError::new(TypeErrorType::List, ipld).into()),
+            };
+            Ok((K::try_from_ipld(key)?, T::try_from_ipld(value)?))
+        };
+        let (key, value) = pair().map_err(|err| with_path_segment(err, PathSegment::Index(i)))?;
+        map.insert(key, value);
+    }
+    Ok(map)
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::collections::BTreeMap;
@@ -324,6 +524,23 @@ mod tests {
         assert_eq!(option, Option::None)
     }
 
+    #[test]
+    #[cfg(feature = "std")]
+    fn try_from_ipld_nested() {
+        use super::{IntoIpld, TryFromIpld};
+        use crate::error::DecodeLocation;
+
+        let map: BTreeMap<String, Vec<Option<u8>>> =
+            BTreeMap::from([("a".to_string(), vec![Some(1), None])]);
+        let ipld = map.clone().into_ipld();
+        assert_eq!(BTreeMap::try_from_ipld(ipld).unwrap(), map);
+
+        let ipld = BTreeMap::from([("a".to_string(), vec![Ipld::Null, Ipld::Bool(true)])]);
+        let err = BTreeMap::<String, Vec<Option<u8>>>::try_from_ipld(ipld.into_ipld()).unwrap_err();
+        let location = err.downcast_ref::<DecodeLocation>().unwrap();
+        assert_eq!(location.path(), ".a[1]");
+    }
+
     #[test]
     fn try_into_cid() {
         let cid = Cid::default();
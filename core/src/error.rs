@@ -1,10 +1,11 @@
 //! `Ipld` error definitions.
-#[cfg(feature = "serde-codec")]
-use alloc::string::ToString;
-use alloc::{string::String, vec::Vec};
+use alloc::string::{String, ToString};
+use alloc::{vec, vec::Vec};
+use core::fmt;
 
 use crate::cid::Cid;
 use crate::ipld::{Ipld, IpldIndex};
+use crate::ipld_ref::IpldRef;
 pub use anyhow::{Error, Result};
 #[cfg(feature = "std")]
 use thiserror::Error;
@@ -38,6 +39,107 @@ pub struct InvalidMultihash(pub Vec<u8>);
 #[cfg_attr(feature = "std", derive(Error), error("Failed to retrieve block {0}."))]
 pub struct BlockNotFound(pub Cid);
 
+/// Too many consecutive links were followed. The supplied CID is the last one followed.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(
+    feature = "std",
+    derive(Error),
+    error("Too many links followed to {0}.")
+)]
+pub struct TooManyLinks(pub Cid);
+
+/// Segment of the data model path to a value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathSegment {
+    /// Key of a map entry.
+    Key(String),
+    /// Index of a list item.
+    Index(usize),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(key) => write!(f, ".{}", key),
+            Self::Index(index) => write!(f, "[{}]", index),
+        }
+    }
+}
+
+/// Location where decoding failed.
+///
+/// It is attached to decode errors as context, so the underlying error can still be downcast to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DecodeLocation {
+    /// Byte offset into the input, if known.
+    pub offset: Option<u64>,
+    /// Data model path to the value that failed to decode.
+    pub segments: Vec<PathSegment>,
+    /// Message of the underlying error.
+    pub cause: String,
+}
+
+impl DecodeLocation {
+    /// Returns the data model path, for example `.foo[3].bar`. The root is `.`.
+    pub fn path(&self) -> String {
+        if self.segments.is_empty() {
+            return ".".into();
+        }
+        let mut path = String::new();
+        for segment in &self.segments {
+            fmt::Write::write_fmt(&mut path, format_args!("{}", segment)).ok();
+        }
+        path
+    }
+}
+
+impl fmt::Display for DecodeLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to decode `{}`", self.path())?;
+        if let Some(offset) = self.offset {
+            write!(f, " at byte {}", offset)?;
+        }
+        if self.cause.is_empty() {
+            write!(f, ".")
+        } else {
+            write!(f, ": {}", self.cause)
+        }
+    }
+}
+
+/// Attaches the location at which decoding failed to an error.
+///
+/// The message of the error is kept in the location, so that it is still shown when the error is
+/// displayed.
+pub fn with_location(err: Error, offset: Option<u64>, segments: Vec<PathSegment>) -> Error {
+    let cause = err.to_string();
+    err.context(DecodeLocation {
+        offset,
+        segments,
+        cause,
+    })
+}
+
+/// Adds the path segment of the value that failed to decode to an error.
+///
+/// Called by containers as the error propagates, so the innermost segment is added first.
+pub fn with_path_segment(mut err: Error, segment: PathSegment) -> Error {
+    if let Some(location) = err.downcast_mut::<DecodeLocation>() {
+        location.segments.insert(0, segment);
+        return err;
+    }
+    with_location(err, None, vec![segment])
+}
+
+/// Sets the byte offset at which decoding failed, unless it is already known.
+pub fn with_offset(mut err: Error, offset: u64) -> Error {
+    if let Some(location) = err.downcast_mut::<DecodeLocation>() {
+        location.offset.get_or_insert(offset);
+        return err;
+    }
+    with_location(err, Some(offset), vec![])
+}
+
 /// Error during Serde operations.
 #[cfg(feature = "serde-codec")]
 #[derive(Clone, Debug)]
@@ -147,6 +249,22 @@ impl From<&Ipld> for TypeErrorType {
     }
 }
 
+impl From<&IpldRef<'_>> for TypeErrorType {
+    fn from(ipld: &IpldRef<'_>) -> Self {
+        match ipld {
+            IpldRef::Null => Self::Null,
+            IpldRef::Bool(_) => Self::Bool,
+            IpldRef::Integer(_) => Self::Integer,
+            IpldRef::Float(_) => Self::Float,
+            IpldRef::String(_) => Self::String,
+            IpldRef::Bytes(_) => Self::Bytes,
+            IpldRef::List(_) => Self::List,
+            IpldRef::Map(_) => Self::Map,
+            IpldRef::Link(_) => Self::Link,
+        }
+    }
+}
+
 impl From<IpldIndex<'_>> for TypeErrorType {
     fn from(index: IpldIndex<'_>) -> Self {
         match index {
@@ -156,3 +274,92 @@ impl From<IpldIndex<'_>> for TypeErrorType {
         }
     }
 }
+
+/// A list doesn't have the expected length.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(
+    feature = "std",
+    derive(Error),
+    error("Expected a list of length {expected} but found length {found}.")
+)]
+pub struct InvalidLength {
+    /// The expected length.
+    pub expected: usize,
+    /// The actual length.
+    pub found: usize,
+}
+
+/// No variant of a union matches.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "std",
+    derive(Error),
+    error("Unknown variant `{variant}` of `{ty}`.")
+)]
+pub struct UnknownVariant {
+    /// The variant name, discriminant or kind.
+    pub variant: String,
+    /// The union type.
+    pub ty: &'static str,
+}
+
+impl UnknownVariant {
+    /// Creates a new `UnknownVariant` error.
+    pub fn new<T>(variant: String) -> Self {
+        Self {
+            variant,
+            ty: core::any::type_name::<T>(),
+        }
+    }
+}
+
+/// A map has a key that doesn't belong to the type.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "std",
+    derive(Error),
+    error("Unknown key `{key}` of `{ty}`.")
+)]
+pub struct UnknownKey {
+    /// The key.
+    pub key: String,
+    /// The type represented by the map.
+    pub ty: &'static str,
+}
+
+impl UnknownKey {
+    /// Creates a new `UnknownKey` error.
+    pub fn new<T>(key: String) -> Self {
+        Self {
+            key,
+            ty: core::any::type_name::<T>(),
+        }
+    }
+}
+
+/// A value represented as a string could not be parsed.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "std",
+    derive(Error),
+    error("Invalid string `{value}` for `{ty}`: {reason}.")
+)]
+pub struct InvalidString {
+    /// The string.
+    pub value: String,
+    /// The type represented by the string.
+    pub ty: &'static str,
+    /// Why it could not be parsed.
+    pub reason: String,
+}
+
+impl InvalidString {
+    /// Creates a new `InvalidString` error.
+    pub fn new<T>(value: String, reason: String) -> Self {
+        Self {
+            value,
+            ty: core::any::type_name::<T>(),
+            reason,
+        }
+    }
+}
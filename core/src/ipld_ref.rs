@@ -0,0 +1,148 @@
+//! Borrowed ipld representation.
+use alloc::{
+    borrow::Cow,
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::cid::Cid;
+use crate::error::TypeError;
+use crate::ipld::{Ipld, IpldIndex};
+
+/// Borrowed counterpart of [`Ipld`].
+///
+/// Strings and bytes borrow from the buffer the value was decoded from where the codec allows it,
+/// so that reading a few fields of a block doesn't require copying all of it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IpldRef<'a> {
+    /// Represents the absence of a value or the value undefined.
+    Null,
+    /// Represents a boolean value.
+    Bool(bool),
+    /// Represents an integer.
+    Integer(i128),
+    /// Represents a floating point value.
+    Float(f64),
+    /// Represents an UTF-8 string.
+    String(Cow<'a, str>),
+    /// Represents a sequence of bytes.
+    Bytes(Cow<'a, [u8]>),
+    /// Represents a list.
+    List(Vec<IpldRef<'a>>),
+    /// Represents a map of strings.
+    Map(BTreeMap<Cow<'a, str>, IpldRef<'a>>),
+    /// Represents a link.
+    Link(Cid),
+}
+
+impl<'a> IpldRef<'a> {
+    /// Indexes into an ipld list or map.
+    pub fn get<'b, T: Into<IpldIndex<'b>>>(&self, index: T) -> Result<&Self, TypeError> {
+        let index = index.into();
+        let ipld = match self {
+            Self::List(l) => match index {
+                IpldIndex::List(i) => Some(i),
+                IpldIndex::Map(ref key) => key.parse().ok(),
+                IpldIndex::MapRef(key) => key.parse().ok(),
+            }
+            .map(|i| l.get(i)),
+            Self::Map(m) => match index {
+                IpldIndex::Map(ref key) => Some(m.get(key.as_str())),
+                IpldIndex::MapRef(key) => Some(m.get(key)),
+                IpldIndex::List(i) => Some(m.get(i.to_string().as_str())),
+            },
+            _ => None,
+        };
+        ipld.unwrap_or_default()
+            .ok_or_else(|| TypeError::new(index, self))
+    }
+
+    /// Converts into an owned [`Ipld`], copying borrowed strings and bytes.
+    pub fn into_owned(self) -> Ipld {
+        match self {
+            Self::Null => Ipld::Null,
+            Self::Bool(b) => Ipld::Bool(b),
+            Self::Integer(i) => Ipld::Integer(i),
+            Self::Float(f) => Ipld::Float(f),
+            Self::String(s) => Ipld::String(s.into_owned()),
+            Self::Bytes(b) => Ipld::Bytes(b.into_owned()),
+            Self::List(l) => Ipld::List(l.into_iter().map(Self::into_owned).collect()),
+            Self::Map(m) => Ipld::Map(
+                m.into_iter()
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect(),
+            ),
+            Self::Link(cid) => Ipld::Link(cid),
+        }
+    }
+}
+
+impl<'a> From<IpldRef<'a>> for Ipld {
+    fn from(ipld: IpldRef<'a>) -> Self {
+        ipld.into_owned()
+    }
+}
+
+impl<'a> From<&'a Ipld> for IpldRef<'a> {
+    fn from(ipld: &'a Ipld) -> Self {
+        match ipld {
+            Ipld::Null => Self::Null,
+            Ipld::Bool(b) => Self::Bool(*b),
+            Ipld::Integer(i) => Self::Integer(*i),
+            Ipld::Float(f) => Self::Float(*f),
+            Ipld::String(s) => Self::String(Cow::Borrowed(s)),
+            Ipld::Bytes(b) => Self::Bytes(Cow::Borrowed(b)),
+            Ipld::List(l) => Self::List(l.iter().map(Self::from).collect()),
+            Ipld::Map(m) => Self::Map(
+                m.iter()
+                    .map(|(k, v)| (Cow::Borrowed(k.as_str()), Self::from(v)))
+                    .collect(),
+            ),
+            Ipld::Link(cid) => Self::Link(*cid),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for IpldRef<'a> {
+    fn from(s: &'a str) -> Self {
+        Self::String(Cow::Borrowed(s))
+    }
+}
+
+impl From<String> for IpldRef<'_> {
+    fn from(s: String) -> Self {
+        Self::String(Cow::Owned(s))
+    }
+}
+
+impl<'a> From<&'a [u8]> for IpldRef<'a> {
+    fn from(b: &'a [u8]) -> Self {
+        Self::Bytes(Cow::Borrowed(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut map = BTreeMap::new();
+        map.insert("bytes".to_string(), Ipld::Bytes(vec![0, 1, 2]));
+        map.insert("list".to_string(), Ipld::List(vec![Ipld::Null, 1.into()]));
+        let ipld = Ipld::Map(map);
+        let ipld_ref = IpldRef::from(&ipld);
+        assert_eq!(
+            ipld_ref.get("bytes").unwrap(),
+            &IpldRef::from(&[0u8, 1, 2][..])
+        );
+        assert_eq!(
+            ipld_ref.get("list").unwrap().get(1).unwrap(),
+            &IpldRef::Integer(1)
+        );
+        assert!(ipld_ref.get("missing").is_err());
+        assert_eq!(ipld_ref.into_owned(), ipld);
+    }
+}
@@ -9,6 +9,7 @@ pub mod codec;
 pub mod convert;
 pub mod error;
 pub mod ipld;
+pub mod ipld_ref;
 pub mod link;
 pub mod raw;
 pub mod raw_value;
@@ -30,8 +30,12 @@ pub struct Struct {
     pub generics: Option<syn::Generics>,
     pub rename: Option<String>,
     pub fields: Vec<StructField>,
+    pub flatten: Option<Box<StructField>>,
+    pub unknown: UnknownPolicy,
     pub repr: StructRepr,
+    pub kind: Option<Kind>,
     pub pat: TokenStreamEq,
+    pub pat_move: TokenStreamEq,
     pub construct: TokenStreamEq,
 }
 
@@ -40,6 +44,7 @@ pub struct StructField {
     pub name: syn::Member,
     pub rename: Option<String>,
     pub default: Option<Box<syn::Expr>>,
+    pub list_pairs: bool,
     pub binding: syn::Ident,
 }
 
@@ -49,6 +54,65 @@ pub enum StructRepr {
     Tuple,
     Value,
     Null,
+    StringJoin {
+        join: String,
+    },
+    StringPairs {
+        inner_delim: String,
+        entry_delim: String,
+    },
+    ListPairs,
+}
+
+/// What to do with unknown keys when decoding a map struct without a flattened field.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UnknownPolicy {
+    Reject,
+    Ignore,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    Null,
+    Bool,
+    Int,
+    Float,
+    String,
+    Bytes,
+    List,
+    Map,
+    Link,
+}
+
+impl Kind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "null" => Self::Null,
+            "bool" => Self::Bool,
+            "int" => Self::Int,
+            "float" => Self::Float,
+            "string" => Self::String,
+            "bytes" => Self::Bytes,
+            "list" => Self::List,
+            "map" => Self::Map,
+            "link" => Self::Link,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Bool => "bool",
+            Self::Int => "int",
+            Self::Float => "float",
+            Self::String => "string",
+            Self::Bytes => "bytes",
+            Self::List => "list",
+            Self::Map => "map",
+            Self::Link => "link",
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -66,4 +130,6 @@ pub enum UnionRepr {
     String,
     Int,
     IntTuple,
+    Inline { tag: String },
+    Envelope { tag: String, content: String },
 }
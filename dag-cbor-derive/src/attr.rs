@@ -5,9 +5,21 @@ mod kw {
     use syn::custom_keyword;
 
     custom_keyword!(repr);
+    custom_keyword!(kind);
+    custom_keyword!(tag);
+    custom_keyword!(content);
+    custom_keyword!(join);
+    custom_keyword!(inner_delim);
+    custom_keyword!(entry_delim);
+    custom_keyword!(rename_all);
+    custom_keyword!(transparent);
+    custom_keyword!(unknown);
 
     custom_keyword!(rename);
     custom_keyword!(default);
+    custom_keyword!(skip);
+    custom_keyword!(optional);
+    custom_keyword!(flatten);
 }
 
 #[derive(Debug)]
@@ -45,12 +57,40 @@ impl<K: Parse, V: Parse> Parse for Attr<K, V> {
 #[derive(Debug)]
 pub enum DeriveAttr {
     Repr(Attr<kw::repr, syn::LitStr>),
+    Kind(Attr<kw::kind, syn::LitStr>),
+    Tag(Attr<kw::tag, syn::LitStr>),
+    Content(Attr<kw::content, syn::LitStr>),
+    Join(Attr<kw::join, syn::LitStr>),
+    InnerDelim(Attr<kw::inner_delim, syn::LitStr>),
+    EntryDelim(Attr<kw::entry_delim, syn::LitStr>),
+    RenameAll(Attr<kw::rename_all, syn::LitStr>),
+    Transparent,
+    Unknown(Attr<kw::unknown, syn::LitStr>),
 }
 
 impl Parse for DeriveAttr {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         if input.peek(kw::repr) {
             Ok(DeriveAttr::Repr(input.parse()?))
+        } else if input.peek(kw::kind) {
+            Ok(DeriveAttr::Kind(input.parse()?))
+        } else if input.peek(kw::tag) {
+            Ok(DeriveAttr::Tag(input.parse()?))
+        } else if input.peek(kw::content) {
+            Ok(DeriveAttr::Content(input.parse()?))
+        } else if input.peek(kw::join) {
+            Ok(DeriveAttr::Join(input.parse()?))
+        } else if input.peek(kw::inner_delim) {
+            Ok(DeriveAttr::InnerDelim(input.parse()?))
+        } else if input.peek(kw::entry_delim) {
+            Ok(DeriveAttr::EntryDelim(input.parse()?))
+        } else if input.peek(kw::rename_all) {
+            Ok(DeriveAttr::RenameAll(input.parse()?))
+        } else if input.peek(kw::transparent) {
+            input.parse::<kw::transparent>()?;
+            Ok(DeriveAttr::Transparent)
+        } else if input.peek(kw::unknown) {
+            Ok(DeriveAttr::Unknown(input.parse()?))
         } else {
             Err(syn::Error::new(input.span(), "unknown attribute"))
         }
@@ -61,6 +101,11 @@ impl Parse for DeriveAttr {
 pub enum FieldAttr {
     Rename(Attr<kw::rename, syn::LitStr>),
     Default(Attr<kw::default, Box<syn::Expr>>),
+    Repr(Attr<kw::repr, syn::LitStr>),
+    Skip,
+    Optional,
+    Flatten,
+    Unknown,
 }
 
 impl Parse for FieldAttr {
@@ -69,6 +114,20 @@ impl Parse for FieldAttr {
             Ok(FieldAttr::Rename(input.parse()?))
         } else if input.peek(kw::default) {
             Ok(FieldAttr::Default(input.parse()?))
+        } else if input.peek(kw::repr) {
+            Ok(FieldAttr::Repr(input.parse()?))
+        } else if input.peek(kw::skip) {
+            input.parse::<kw::skip>()?;
+            Ok(FieldAttr::Skip)
+        } else if input.peek(kw::optional) {
+            input.parse::<kw::optional>()?;
+            Ok(FieldAttr::Optional)
+        } else if input.peek(kw::flatten) {
+            input.parse::<kw::flatten>()?;
+            Ok(FieldAttr::Flatten)
+        } else if input.peek(kw::unknown) {
+            input.parse::<kw::unknown>()?;
+            Ok(FieldAttr::Unknown)
         } else {
             Err(syn::Error::new(input.span(), "unknown attribute"))
         }
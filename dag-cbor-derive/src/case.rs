@@ -0,0 +1,114 @@
+/// A casing applied to field and variant names by `rename_all`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "lowercase" => Self::Lower,
+            "UPPERCASE" => Self::Upper,
+            "PascalCase" => Self::Pascal,
+            "camelCase" => Self::Camel,
+            "snake_case" => Self::Snake,
+            "SCREAMING_SNAKE_CASE" => Self::ScreamingSnake,
+            "kebab-case" => Self::Kebab,
+            "SCREAMING-KEBAB-CASE" => Self::ScreamingKebab,
+            _ => return None,
+        })
+    }
+
+    /// Renames a field, which is expected to be in snake case.
+    ///
+    /// Like serde, `lowercase` and `UPPERCASE` keep the underscores of a field.
+    pub fn apply_to_field(self, field: &str) -> String {
+        match self {
+            Self::Lower => field.to_string(),
+            Self::Upper => field.to_uppercase(),
+            _ => self.join(field.split('_').filter(|word| !word.is_empty()).collect()),
+        }
+    }
+
+    /// Renames a variant, which is expected to be in pascal case.
+    pub fn apply_to_variant(self, variant: &str) -> String {
+        let mut words = Vec::new();
+        let mut start = 0;
+        for (i, c) in variant.char_indices().skip(1) {
+            if c.is_uppercase() {
+                words.push(&variant[start..i]);
+                start = i;
+            }
+        }
+        words.push(&variant[start..]);
+        self.join(words)
+    }
+
+    fn join(self, words: Vec<&str>) -> String {
+        let lower: Vec<String> = words.iter().map(|word| word.to_lowercase()).collect();
+        let upper: Vec<String> = words.iter().map(|word| word.to_uppercase()).collect();
+        match self {
+            Self::Lower => lower.concat(),
+            Self::Upper => upper.concat(),
+            Self::Pascal => lower.iter().map(|word| capitalize(word)).collect(),
+            Self::Camel => lower
+                .iter()
+                .enumerate()
+                .map(|(i, word)| {
+                    if i == 0 {
+                        word.clone()
+                    } else {
+                        capitalize(word)
+                    }
+                })
+                .collect(),
+            Self::Snake => lower.join("_"),
+            Self::ScreamingSnake => upper.join("_"),
+            Self::Kebab => lower.join("-"),
+            Self::ScreamingKebab => upper.join("-"),
+        }
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_rule() {
+        let field = |rule, name| RenameRule::apply_to_field(rule, name);
+        assert_eq!(field(RenameRule::Camel, "created_at"), "createdAt");
+        assert_eq!(field(RenameRule::Pascal, "created_at"), "CreatedAt");
+        assert_eq!(field(RenameRule::Lower, "created_at"), "created_at");
+        assert_eq!(field(RenameRule::Upper, "created_at"), "CREATED_AT");
+        assert_eq!(
+            field(RenameRule::ScreamingSnake, "created_at"),
+            "CREATED_AT"
+        );
+        assert_eq!(field(RenameRule::Kebab, "created_at"), "created-at");
+        let variant = |rule, name| RenameRule::apply_to_variant(rule, name);
+        assert_eq!(variant(RenameRule::Camel, "CreatedAt"), "createdAt");
+        assert_eq!(variant(RenameRule::Snake, "CreatedAt"), "created_at");
+        assert_eq!(variant(RenameRule::Upper, "CreatedAt"), "CREATEDAT");
+        assert_eq!(
+            variant(RenameRule::ScreamingKebab, "CreatedAt"),
+            "CREATED-AT"
+        );
+        assert_eq!(variant(RenameRule::Pascal, "CreatedAt"), "CreatedAt");
+    }
+}
@@ -21,7 +21,10 @@ pub fn gen_encode(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
             ) -> #libipld::Result<()> {
                 use #libipld::codec::Encode;
                 use #libipld::cbor::cbor::MajorKind;
-                use #libipld::cbor::encode::{write_null, write_u8, write_u64};
+                use #libipld::cbor::decode::read_map_entries;
+                use #libipld::cbor::encode::{
+                    write_list_pairs, write_map_entries, write_null, write_u8, write_u64,
+                };
                 #body
             }
         }
@@ -31,7 +34,7 @@ pub fn gen_encode(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
 pub fn gen_decode(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
     let (ident, generics, body) = match ast {
         SchemaType::Struct(s) => (&s.name, s.generics.as_ref().unwrap(), gen_decode_struct(s)),
-        SchemaType::Union(u) => (&u.name, &u.generics, gen_decode_union(u)),
+        SchemaType::Union(u) => (&u.name, &u.generics, gen_decode_union(u, libipld)),
     };
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
     let trait_name = quote!(#libipld::codec::Decode<#libipld::cbor::DagCborCodec>);
@@ -43,10 +46,12 @@ pub fn gen_decode(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
                 r: &mut R,
             ) -> #libipld::Result<Self> {
                 use #libipld::cbor::cbor::{MajorKind, NULL};
-                use #libipld::cbor::decode::{read_uint, read_major};
+                use #libipld::cbor::decode::{read_list_pairs, read_uint, read_major};
+                use #libipld::cbor::encode::write_map_entries;
                 use #libipld::cbor::error::{LengthOutOfRange, MissingKey, UnexpectedCode, UnexpectedKey};
                 use #libipld::codec::Decode;
-                use #libipld::error::Result;
+                use #libipld::error::{with_path_segment, InvalidString, PathSegment, Result};
+                use #libipld::raw_value::{RawValue, SkipOne};
                 use std::io::SeekFrom;
                 #body
             }
@@ -54,7 +59,7 @@ pub fn gen_decode(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
     }
 }
 
-fn rename(name: &syn::Member, rename: Option<&String>) -> TokenStream {
+pub fn rename(name: &syn::Member, rename: Option<&String>) -> TokenStream {
     if let Some(rename) = rename {
         quote!(#rename)
     } else {
@@ -66,7 +71,11 @@ fn rename(name: &syn::Member, rename: Option<&String>) -> TokenStream {
     }
 }
 
-fn default(binding: &syn::Ident, default: Option<&syn::Expr>, tokens: TokenStream) -> TokenStream {
+pub fn default(
+    binding: &syn::Ident,
+    default: Option<&syn::Expr>,
+    tokens: TokenStream,
+) -> TokenStream {
     if let Some(default) = default {
         quote! {
             if #binding != &#default {
@@ -78,7 +87,7 @@ fn default(binding: &syn::Ident, default: Option<&syn::Expr>, tokens: TokenStrea
     }
 }
 
-fn gen_encode_match(arms: impl Iterator<Item = TokenStream>) -> TokenStream {
+pub fn gen_encode_match(arms: impl Iterator<Item = TokenStream>) -> TokenStream {
     quote! {
         match *self {
             #(#arms,)*
@@ -87,71 +96,159 @@ fn gen_encode_match(arms: impl Iterator<Item = TokenStream>) -> TokenStream {
     }
 }
 
-fn gen_encode_struct(s: &Struct) -> TokenStream {
-    let pat = &*s.pat;
-    let body = gen_encode_struct_body(s);
-    gen_encode_match(std::iter::once(quote!(#pat => { #body })))
+/// Returns the key of a field in a map, stringpairs or listpairs representation.
+pub fn field_key(field: &StructField) -> TokenStream {
+    rename(&field.name, field.rename.as_ref())
 }
 
-fn gen_encode_struct_body(s: &Struct) -> TokenStream {
-    match s.repr {
-        StructRepr::Map => {
-            let len = s.fields.len() as u64;
-            let dfields = s.fields.iter().filter_map(|field| {
-                if let Some(default) = field.default.as_ref() {
-                    let binding = &field.binding;
-                    Some(quote! {
-                        if #binding == &#default {
-                            len -= 1;
-                        }
-                    })
-                } else {
-                    None
-                }
+/// Joins the fields of a stringjoin or stringpairs struct into a `String`.
+///
+/// The fields are formatted with `Display` and bound by reference. Values must not contain the
+/// delimiters.
+pub fn gen_to_string(s: &Struct) -> TokenStream {
+    match &s.repr {
+        StructRepr::StringJoin { join } => {
+            let binding = s.fields.iter().map(|field| &field.binding);
+            quote!(<[String]>::join(&[#(#binding.to_string()),*], #join))
+        }
+        StructRepr::StringPairs {
+            inner_delim,
+            entry_delim,
+        } => {
+            let entries = s.fields.iter().map(|field| {
+                let key = field_key(field);
+                let binding = &field.binding;
+                default(
+                    binding,
+                    field.default.as_deref(),
+                    quote!(entries.push(format!("{}{}{}", #key, #inner_delim, #binding));),
+                )
             });
-            let mut cbor_order = s
-                .fields
-                .iter()
-                .map(|field| {
-                    let key = rename(&field.name, field.rename.as_ref());
-                    let binding = &field.binding;
-                    let field = default(
-                        binding,
-                        field.default.as_deref(),
-                        quote! {
-                            Encode::encode(#key, c, w)?;
-                            Encode::encode(#binding, c, w)?;
-                        },
-                    );
-                    (key.to_string(), field)
-                })
-                .collect::<Vec<(String, _)>>();
-            // CBOR RFC-7049 specifies a canonical sort order, where keys are sorted by length
-            // first. This was later revised with RFC-8949, but we need to stick to the original
-            // order to stay compatible with existing data.
-            cbor_order.sort_unstable_by(|(key_a, _), (key_b, _)| {
-                match key_a.len().cmp(&key_b.len()) {
-                    Ordering::Greater => Ordering::Greater,
-                    Ordering::Less => Ordering::Less,
-                    Ordering::Equal => key_a.cmp(key_b),
+            quote! {{
+                #[allow(unused_mut)]
+                let mut entries: Vec<String> = Vec::new();
+                #(#entries)*
+                entries.join(#entry_delim)
+            }}
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Parses the fields of a stringjoin or stringpairs struct from the `String` named `value`.
+///
+/// The fields are parsed with `FromStr`. Unknown keys of a stringpairs struct are ignored.
+pub fn gen_from_string(s: &Struct) -> TokenStream {
+    let construct = &*s.construct;
+    match &s.repr {
+        StructRepr::StringJoin { join } => {
+            let len = s.fields.len();
+            let fields = s.fields.iter().map(|field| {
+                let binding = &field.binding;
+                let key = field_key(field);
+                quote! {
+                    let #binding = match parts.next() {
+                        Some(part) => part.parse().map_err(|_| {
+                            InvalidString::new::<Self>(value.clone(), format!("invalid `{}`", #key))
+                        })?,
+                        None => return Err(InvalidString::new::<Self>(
+                            value.clone(),
+                            format!("expected {} fields", #len),
+                        )
+                        .into()),
+                    };
                 }
             });
-            let fields = cbor_order.iter().map(|(_, field)| field);
             quote! {
-                let mut len = #len;
-                #(#dfields)*
-                write_u64(w, MajorKind::Map, len)?;
+                let mut parts = value.split(#join);
                 #(#fields)*
+                if parts.next().is_some() {
+                    return Err(
+                        InvalidString::new::<Self>(value.clone(), format!("expected {} fields", #len))
+                            .into(),
+                    );
+                }
+                return Ok(#construct);
             }
         }
-        StructRepr::Tuple => {
-            let len = s.fields.len() as u64;
+        StructRepr::StringPairs {
+            inner_delim,
+            entry_delim,
+        } => {
+            let binding: Vec<_> = s.fields.iter().map(|field| &field.binding).collect();
+            let key: Vec<_> = s.fields.iter().map(field_key).collect();
             let fields = s.fields.iter().map(|field| {
                 let binding = &field.binding;
-                quote! {
-                    Encode::encode(#binding, c, w)?;
+                let key = field_key(field);
+                if let Some(default) = field.default.as_ref() {
+                    quote!(let #binding = #binding.unwrap_or(#default);)
+                } else {
+                    quote! {
+                        let #binding = #binding.ok_or_else(|| {
+                            InvalidString::new::<Self>(value.clone(), format!("missing `{}`", #key))
+                        })?;
+                    }
                 }
             });
+            quote! {
+                #(let mut #binding = None;)*
+                if !value.is_empty() {
+                    for entry in value.split(#entry_delim) {
+                        let (key, part) = entry.split_once(#inner_delim).ok_or_else(|| {
+                            InvalidString::new::<Self>(
+                                value.clone(),
+                                format!("`{}` is missing `{}`", entry, #inner_delim),
+                            )
+                        })?;
+                        match key {
+                            #(#key => {
+                                #binding = Some(part.parse().map_err(|_| {
+                                    InvalidString::new::<Self>(value.clone(), format!("invalid `{}`", #key))
+                                })?);
+                            })*
+                            _ => {
+                                let _ = part;
+                            }
+                        }
+                    }
+                }
+                #(#fields)*
+                return Ok(#construct);
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn gen_encode_field(field: &StructField) -> TokenStream {
+    let binding = &field.binding;
+    if field.list_pairs {
+        quote!(write_list_pairs(w, #binding)?;)
+    } else {
+        quote!(Encode::encode(#binding, c, w)?;)
+    }
+}
+
+fn gen_decode_field(field: &StructField) -> TokenStream {
+    if field.list_pairs {
+        quote!(read_list_pairs(r))
+    } else {
+        quote!(Decode::decode(c, r))
+    }
+}
+
+fn gen_encode_struct(s: &Struct) -> TokenStream {
+    let pat = &*s.pat;
+    let body = gen_encode_struct_body(s);
+    gen_encode_match(std::iter::once(quote!(#pat => { #body })))
+}
+
+fn gen_encode_struct_body(s: &Struct) -> TokenStream {
+    match &s.repr {
+        StructRepr::Map => gen_encode_map(s, None),
+        StructRepr::Tuple => {
+            let len = s.fields.len() as u64;
+            let fields = s.fields.iter().map(gen_encode_field);
             quote! {
                 write_u64(w, MajorKind::Array, #len)?;
                 #(#fields)*
@@ -161,32 +258,174 @@ fn gen_encode_struct_body(s: &Struct) -> TokenStream {
             assert_eq!(s.fields.len(), 1);
             let field = &s.fields[0];
             let binding = &field.binding;
-            default(
-                binding,
-                field.default.as_deref(),
-                quote! {
-                    Encode::encode(#binding, c, w)?;
-                },
-            )
+            default(binding, field.default.as_deref(), gen_encode_field(field))
         }
         StructRepr::Null => {
             assert_eq!(s.fields.len(), 0);
             quote!(write_null(w)?;)
         }
+        StructRepr::StringJoin { .. } | StructRepr::StringPairs { .. } => {
+            let value = gen_to_string(s);
+            quote! {
+                let value: String = #value;
+                Encode::encode(&value, c, w)?;
+            }
+        }
+        StructRepr::ListPairs => {
+            let len = s.fields.len() as u64;
+            let dfields = s.fields.iter().filter_map(|field| {
+                let binding = &field.binding;
+                let default = field.default.as_ref()?;
+                Some(quote! {
+                    if #binding == &#default {
+                        len -= 1;
+                    }
+                })
+            });
+            let fields = s.fields.iter().map(|field| {
+                let key = field_key(field);
+                let value = gen_encode_field(field);
+                default(
+                    &field.binding,
+                    field.default.as_deref(),
+                    quote! {
+                        write_u8(w, MajorKind::Array, 2)?;
+                        Encode::encode(#key, c, w)?;
+                        #value
+                    },
+                )
+            });
+            quote! {
+                let mut len = #len;
+                #(#dfields)*
+                write_u64(w, MajorKind::Array, len)?;
+                #(#fields)*
+            }
+        }
+    }
+}
+
+/// Compares map keys in the same order as `libipld_cbor::cbor::cmp_keys`.
+fn cbor_order(key_a: &str, key_b: &str) -> Ordering {
+    match key_a.len().cmp(&key_b.len()) {
+        Ordering::Greater => Ordering::Greater,
+        Ordering::Less => Ordering::Less,
+        Ordering::Equal => key_a.cmp(key_b),
+    }
+}
+
+/// Encodes the fields of a map struct, optionally followed by the tag of an inline union.
+fn gen_encode_map(s: &Struct, tag: Option<(&str, &TokenStream)>) -> TokenStream {
+    if let Some(flatten) = &s.flatten {
+        return gen_encode_flattened_map(s, flatten, tag);
+    }
+    let len = s.fields.len() as u64 + tag.is_some() as u64;
+    let dfields = s.fields.iter().filter_map(|field| {
+        if let Some(default) = field.default.as_ref() {
+            let binding = &field.binding;
+            Some(quote! {
+                if #binding == &#default {
+                    len -= 1;
+                }
+            })
+        } else {
+            None
+        }
+    });
+    let mut cbor_order = s
+        .fields
+        .iter()
+        .map(|field| {
+            let key = field_key(field);
+            let value = gen_encode_field(field);
+            let field = default(
+                &field.binding,
+                field.default.as_deref(),
+                quote! {
+                    Encode::encode(#key, c, w)?;
+                    #value
+                },
+            );
+            (key.to_string(), field)
+        })
+        .collect::<Vec<(String, _)>>();
+    if let Some((tag, key)) = tag {
+        let field = quote! {
+            Encode::encode(#tag, c, w)?;
+            Encode::encode(#key, c, w)?;
+        };
+        cbor_order.push((quote!(#tag).to_string(), field));
+    }
+    cbor_order.sort_unstable_by(|(key_a, _), (key_b, _)| self::cbor_order(key_a, key_b));
+    let fields = cbor_order.iter().map(|(_, field)| field);
+    quote! {
+        let mut len = #len;
+        #(#dfields)*
+        write_u64(w, MajorKind::Map, len)?;
+        #(#fields)*
+    }
+}
+
+/// Encodes a map struct with a flattened field.
+///
+/// The keys of the flattened field are only known at runtime, so all entries are encoded
+/// separately and sorted before they are written.
+fn gen_encode_flattened_map(
+    s: &Struct,
+    flatten: &StructField,
+    tag: Option<(&str, &TokenStream)>,
+) -> TokenStream {
+    let fields = s.fields.iter().map(|field| {
+        let key = field_key(field);
+        let value = gen_encode_field(field);
+        default(
+            &field.binding,
+            field.default.as_deref(),
+            quote! {
+                let mut value = Vec::new();
+                {
+                    let w = &mut value;
+                    #value
+                }
+                entries.push((#key.into(), value));
+            },
+        )
+    });
+    let tag = tag.map(|(tag, key)| {
+        quote! {
+            let mut value = Vec::new();
+            Encode::encode(#key, c, &mut value)?;
+            entries.push((#tag.into(), value));
+        }
+    });
+    let binding = &flatten.binding;
+    quote! {
+        let mut value = Vec::new();
+        Encode::encode(#binding, c, &mut value)?;
+        let mut entries = read_map_entries(&mut std::io::Cursor::new(value))?;
+        #(#fields)*
+        #tag
+        write_map_entries(w, entries)?;
     }
 }
 
 #[allow(clippy::needless_collect)]
 fn gen_encode_union(u: &Union) -> TokenStream {
-    let arms = u
-        .variants
+    let variants = match &u.repr {
+        UnionRepr::Inline { tag } => match inline_variants(u, tag) {
+            Ok(variants) => variants,
+            Err(err) => return err,
+        },
+        _ => u.variants.clone(),
+    };
+    let arms = variants
         .iter()
         .enumerate()
         .map(|(i, s)| {
             let pat = &*s.pat;
             let key = rename(&syn::Member::Named(s.name.clone()), s.rename.as_ref());
             let value = gen_encode_struct_body(s);
-            match u.repr {
+            match &u.repr {
                 UnionRepr::Keyed => {
                     quote! {
                         #pat => {
@@ -216,6 +455,29 @@ fn gen_encode_union(u: &Union) -> TokenStream {
                         }
                     }
                 }
+                UnionRepr::Inline { tag } => {
+                    let value = gen_encode_map(s, Some((tag, &key)));
+                    quote!(#pat => { #value })
+                }
+                UnionRepr::Envelope { tag, content } => {
+                    let mut entries = [
+                        (tag, quote!(Encode::encode(#key, c, w)?;)),
+                        (content, value),
+                    ];
+                    entries.sort_unstable_by(|(key_a, _), (key_b, _)| cbor_order(key_a, key_b));
+                    let entries = entries.iter().map(|(key, value)| {
+                        quote! {
+                            Encode::encode(#key, c, w)?;
+                            #value
+                        }
+                    });
+                    quote! {
+                        #pat => {
+                            write_u8(w, MajorKind::Map, 2)?;
+                            #(#entries)*
+                        }
+                    }
+                }
             }
         })
         .collect::<Vec<_>>();
@@ -226,64 +488,88 @@ fn gen_encode_union(u: &Union) -> TokenStream {
     }
 }
 
-fn gen_decode_struct(s: &Struct) -> TokenStream {
-    let len = s.fields.len() as u64;
-    let construct = &*s.construct;
-    match s.repr {
-        StructRepr::Map => {
-            let binding: Vec<_> = s.fields.iter().map(|field| &field.binding).collect();
-            let key: Vec<_> = s
-                .fields
-                .iter()
-                .map(|field| rename(&field.name, field.rename.as_ref()))
-                .collect();
-            let fields: Vec<_> = s
-                .fields
-                .iter()
-                .map(|field| {
-                    let binding = &field.binding;
-                    let key = rename(&field.name, field.rename.as_ref());
-                    if let Some(default) = field.default.as_ref() {
-                        quote!(let #binding = #binding.unwrap_or(#default);)
-                    } else {
-                        quote!(let #binding = #binding.ok_or(MissingKey::new::<Self>(#key))?;)
-                    }
-                })
-                .collect();
-            quote! {
-                let major = read_major(r)?;
-                match major.kind() {
-                    MajorKind::Map => {
-                        let len = read_uint(r, major)?;
-                        if len > #len {
-                            return Err(LengthOutOfRange::new::<Self>().into());
-                        }
-                        #(let mut #binding = None;)*
-                        for _ in 0..len {
-                            let mut key: String = Decode::decode(c, r)?;
-                            match key.as_str() {
-                                #(#key => { #binding = Some(Decode::decode(c, r)?); })*
-                                _ => {
-                                    Decode::decode(c, r)?;
-                                }
-                            }
-                        }
+/// Returns the variants of an inline union, with unit variants represented as empty maps.
+///
+/// Fails with a compile error if a variant is not represented as a map or if one of its fields
+/// uses the tag as key.
+pub fn inline_variants(u: &Union, tag: &str) -> Result<Vec<Struct>, TokenStream> {
+    let mut variants = Vec::with_capacity(u.variants.len());
+    for s in &u.variants {
+        let mut s = s.clone();
+        match s.repr {
+            StructRepr::Map => {}
+            StructRepr::Null => s.repr = StructRepr::Map,
+            _ => {
+                let msg = format!(
+                    "`{}` must be represented as a map to be part of an inline union",
+                    s.name
+                );
+                return Err(syn::Error::new_spanned(&s.name, msg).to_compile_error());
+            }
+        }
+        for field in &s.fields {
+            if rename(&field.name, field.rename.as_ref()).to_string() == quote!(#tag).to_string() {
+                let msg = format!("the field `{}` of `{}` clashes with the tag", tag, s.name);
+                return Err(syn::Error::new_spanned(&s.name, msg).to_compile_error());
+            }
+        }
+        variants.push(s);
+    }
+    Ok(variants)
+}
 
-                        #(#fields)*
+/// Kinded union variants with a known kind, and the variant without one.
+pub type KindedVariants<'a> = (Vec<(Kind, &'a Struct)>, Option<&'a Struct>);
 
-                        return Ok(#construct);
-                    }
-                    _ => {
-                        return Err(UnexpectedCode::new::<Self>(major.into()).into());
-                    }
-                }
+/// Returns the variants of a kinded union with their kinds.
+///
+/// The variant whose kind can't be inferred is returned separately, it decodes every kind that no
+/// other variant has. Fails with a compile error if a kind is shared by two variants or if the kind
+/// of more than one variant is unknown.
+pub fn kinded_variants(u: &Union) -> Result<KindedVariants<'_>, TokenStream> {
+    let mut variants: Vec<(Kind, &Struct)> = Vec::with_capacity(u.variants.len());
+    let mut fallback: Option<&Struct> = None;
+    for s in &u.variants {
+        let kind = match (s.kind, fallback) {
+            (Some(kind), _) => kind,
+            (None, None) => {
+                fallback = Some(s);
+                continue;
             }
+            (None, Some(other)) => {
+                let msg = format!(
+                    "cannot infer the kind of `{}` or `{}`, add `#[ipld(kind = \"...\")]`",
+                    other.name, s.name
+                );
+                return Err(syn::Error::new_spanned(&s.name, msg).to_compile_error());
+            }
+        };
+        if let Some((_, other)) = variants.iter().find(|(other, _)| *other == kind) {
+            let msg = format!(
+                "`{}` and `{}` both have kind {}",
+                other.name,
+                s.name,
+                kind.as_str()
+            );
+            return Err(syn::Error::new_spanned(&s.name, msg).to_compile_error());
         }
+        variants.push((kind, s));
+    }
+    Ok((variants, fallback))
+}
+
+fn gen_decode_struct(s: &Struct) -> TokenStream {
+    let len = s.fields.len() as u64;
+    let construct = &*s.construct;
+    match &s.repr {
+        StructRepr::Map => gen_decode_map(s, None),
         StructRepr::Tuple => {
-            let fields = s.fields.iter().map(|field| {
+            let fields = s.fields.iter().enumerate().map(|(i, field)| {
                 let binding = &field.binding;
+                let decode = gen_decode_field(field);
                 quote! {
-                    let #binding = Decode::decode(c, r)?;
+                    let #binding = #decode
+                        .map_err(|err| with_path_segment(err, PathSegment::Index(#i)))?;
                 }
             });
             quote! {
@@ -306,8 +592,9 @@ fn gen_decode_struct(s: &Struct) -> TokenStream {
         StructRepr::Value => {
             assert_eq!(s.fields.len(), 1);
             let binding = &s.fields[0].binding;
+            let decode = gen_decode_field(&s.fields[0]);
             quote! {
-                let #binding = Decode::decode(c, r)?;
+                let #binding = #decode?;
                 return Ok(#construct);
             }
         }
@@ -325,18 +612,162 @@ fn gen_decode_struct(s: &Struct) -> TokenStream {
                 }
             }
         }
+        StructRepr::StringJoin { .. } | StructRepr::StringPairs { .. } => {
+            let parse = gen_from_string(s);
+            quote! {
+                let value: String = Decode::decode(c, r)?;
+                #parse
+            }
+        }
+        StructRepr::ListPairs => {
+            let binding: Vec<_> = s.fields.iter().map(|field| &field.binding).collect();
+            let key: Vec<_> = s.fields.iter().map(field_key).collect();
+            let decode: Vec<_> = s.fields.iter().map(gen_decode_field).collect();
+            let fields = s.fields.iter().map(|field| {
+                let binding = &field.binding;
+                let key = field_key(field);
+                if let Some(default) = field.default.as_ref() {
+                    quote!(let #binding = #binding.unwrap_or(#default);)
+                } else {
+                    quote!(let #binding = #binding.ok_or(MissingKey::new::<Self>(#key))?;)
+                }
+            });
+            quote! {
+                let major = read_major(r)?;
+                if major.kind() != MajorKind::Array {
+                    return Err(UnexpectedCode::new::<Self>(major.into()).into());
+                }
+                let len = read_uint(r, major)?;
+                #(let mut #binding = None;)*
+                for _ in 0..len {
+                    let major = read_major(r)?;
+                    if major.kind() != MajorKind::Array {
+                        return Err(UnexpectedCode::new::<Self>(major.into()).into());
+                    } else if read_uint(r, major)? != 2 {
+                        return Err(LengthOutOfRange::new::<Self>().into());
+                    }
+                    let key: String = Decode::decode(c, r)?;
+                    match key.as_str() {
+                        #(#key => {
+                            #binding = Some(#decode.map_err(|err| {
+                                with_path_segment(err, PathSegment::Key(#key.into()))
+                            })?);
+                        })*
+                        _ => c.skip(r)?,
+                    }
+                }
+                #(#fields)*
+                return Ok(#construct);
+            }
+        }
     }
 }
 
-fn gen_decode_union(u: &Union) -> TokenStream {
-    match u.repr {
+/// Decodes the fields of a map struct, skipping the tag of an inline union.
+///
+/// Unknown entries are passed on to the flattened field, if there is one. Otherwise they are
+/// rejected or skipped, depending on the unknown policy of the struct.
+fn gen_decode_map(s: &Struct, tag: Option<&str>) -> TokenStream {
+    let construct = &*s.construct;
+    let binding: Vec<_> = s.fields.iter().map(|field| &field.binding).collect();
+    let key: Vec<_> = s
+        .fields
+        .iter()
+        .map(|field| rename(&field.name, field.rename.as_ref()))
+        .collect();
+    let fields: Vec<_> = s
+        .fields
+        .iter()
+        .map(|field| {
+            let binding = &field.binding;
+            let key = rename(&field.name, field.rename.as_ref());
+            if let Some(default) = field.default.as_ref() {
+                quote!(let #binding = #binding.unwrap_or(#default);)
+            } else {
+                quote!(let #binding = #binding.ok_or(MissingKey::new::<Self>(#key))?;)
+            }
+        })
+        .collect();
+    let decode: Vec<_> = s.fields.iter().map(gen_decode_field).collect();
+    let tag = tag.map(|tag| {
+        quote! {
+            #tag => {
+                let _: String = Decode::decode(c, r)?;
+            }
+        }
+    });
+    let (rest, unknown, flatten) = match &s.flatten {
+        Some(flatten) => {
+            let binding = &flatten.binding;
+            (
+                quote!(let mut rest: Vec<(String, Vec<u8>)> = Vec::new();),
+                quote! {
+                    let value: RawValue<_> = Decode::decode(c, r)?;
+                    rest.push((key, value.into()));
+                },
+                quote! {
+                    let mut value = Vec::new();
+                    write_map_entries(&mut value, rest)?;
+                    let #binding = Decode::decode(c, &mut std::io::Cursor::new(value))?;
+                },
+            )
+        }
+        None => {
+            let unknown = match s.unknown {
+                UnknownPolicy::Reject => {
+                    quote!(return Err(UnexpectedKey::new::<Self>(key).into());)
+                }
+                UnknownPolicy::Ignore => quote!(c.skip(r)?;),
+            };
+            (quote!(), unknown, quote!())
+        }
+    };
+    quote! {
+        let major = read_major(r)?;
+        match major.kind() {
+            MajorKind::Map => {
+                let len = read_uint(r, major)?;
+                #(let mut #binding = None;)*
+                #rest
+                for _ in 0..len {
+                    let key: String = Decode::decode(c, r)?;
+                    match key.as_str() {
+                        #(#key => {
+                            #binding = Some(#decode.map_err(|err| {
+                                with_path_segment(err, PathSegment::Key(#key.into()))
+                            })?);
+                        })*
+                        #tag
+                        _ => {
+                            #unknown
+                        }
+                    }
+                }
+
+                #(#fields)*
+                #flatten
+
+                return Ok(#construct);
+            }
+            _ => {
+                return Err(UnexpectedCode::new::<Self>(major.into()).into());
+            }
+        }
+    }
+}
+
+fn gen_decode_union(u: &Union, libipld: &syn::Ident) -> TokenStream {
+    match &u.repr {
         UnionRepr::Keyed => {
             let variants = u.variants.iter().map(|s| {
                 let key = rename(&syn::Member::Named(s.name.clone()), s.rename.as_ref());
                 let parse = gen_decode_struct(s);
                 quote! {
                     if key.as_str() == #key {
-                        #parse
+                        let result: Result<Self> = (|| {
+                            #parse
+                        })();
+                        return result.map_err(|err| with_path_segment(err, PathSegment::Key(#key.into())));
                     }
                 }
             });
@@ -353,25 +784,57 @@ fn gen_decode_union(u: &Union) -> TokenStream {
             }
         }
         UnionRepr::Kinded => {
-            // TODO: this is wrong. Kinded should be based on the kind, not "if it decodes".
-            let variants = u.variants.iter().map(|s| {
+            let (variants, fallback) = match kinded_variants(u) {
+                Ok(variants) => variants,
+                Err(err) => return err,
+            };
+            let fallback = match fallback {
+                Some(s) => {
+                    let parse = gen_decode_struct(s);
+                    quote! {
+                        let result: Result<Self> = (|| {
+                            #parse
+                        })();
+                        result
+                    }
+                }
+                None => quote!(Err(UnexpectedCode::new::<Self>(major.into()).into())),
+            };
+            let arms = variants.into_iter().map(|(kind, s)| {
+                let pattern = match kind {
+                    Kind::Null => quote!(major if major == NULL),
+                    Kind::Bool => quote!(major if major == TRUE || major == FALSE),
+                    Kind::Float => quote!(major if major == F16 || major == F32 || major == F64),
+                    Kind::Int => quote! {
+                        major if major.kind() == MajorKind::UnsignedInt
+                            || major.kind() == MajorKind::NegativeInt
+                    },
+                    Kind::String => quote!(major if major.kind() == MajorKind::TextString),
+                    Kind::Bytes => quote!(major if major.kind() == MajorKind::ByteString),
+                    Kind::List => quote!(major if major.kind() == MajorKind::Array),
+                    Kind::Map => quote!(major if major.kind() == MajorKind::Map),
+                    Kind::Link => quote!(major if major.kind() == MajorKind::Tag),
+                };
                 let parse = gen_decode_struct(s);
                 quote! {
-                    let pos = r.seek(SeekFrom::Current(0))?;
-                    let result: Result<Self> = (|| {
-                        #parse
-                    })();
-                    match result {
-                        Ok(res) => return Ok(res),
-                        Err(err) => {
-                            r.seek(SeekFrom::Start(pos))?;
-                        }
-                    };
+                    #pattern => {
+                        let result: Result<Self> = (|| {
+                            #parse
+                        })();
+                        result
+                    }
                 }
             });
             quote! {
-                #(#variants;)*
-                Err(UnexpectedCode::new::<Self>(read_major(r)?.into()).into())
+                use #libipld::cbor::cbor::{FALSE, F16, F32, F64, TRUE};
+                let major = read_major(r)?;
+                r.seek(SeekFrom::Current(-1))?;
+                match major {
+                    #(#arms,)*
+                    _ => {
+                        #fallback
+                    }
+                }
             }
         }
         UnionRepr::String => {
@@ -403,11 +866,109 @@ fn gen_decode_union(u: &Union) -> TokenStream {
                 Ok(res)
             }
         }
+        UnionRepr::Inline { tag } => {
+            let variants = match inline_variants(u, tag) {
+                Ok(variants) => variants,
+                Err(err) => return err,
+            };
+            let variants = variants.iter().map(|s| {
+                let key = rename(&syn::Member::Named(s.name.clone()), s.rename.as_ref());
+                let parse = gen_decode_map(s, Some(tag));
+                quote! {
+                    if discriminant.as_str() == #key {
+                        let result: Result<Self> = (|| {
+                            #parse
+                        })();
+                        return result;
+                    }
+                }
+            });
+            quote! {
+                let pos = r.seek(SeekFrom::Current(0))?;
+                let major = read_major(r)?;
+                if major.kind() != MajorKind::Map {
+                    return Err(UnexpectedCode::new::<Self>(major.into()).into());
+                }
+                let len = read_uint(r, major)?;
+                let mut discriminant = None;
+                for _ in 0..len {
+                    let key: String = Decode::decode(c, r)?;
+                    if key == #tag {
+                        let value: String = Decode::decode(c, r).map_err(|err| {
+                            with_path_segment(err, PathSegment::Key(#tag.into()))
+                        })?;
+                        discriminant = Some(value);
+                        break;
+                    }
+                    c.skip(r)?;
+                }
+                let discriminant = discriminant.ok_or(MissingKey::new::<Self>(#tag))?;
+                r.seek(SeekFrom::Start(pos))?;
+                #(#variants)*
+                Err(UnexpectedKey::new::<Self>(discriminant).into())
+            }
+        }
+        UnionRepr::Envelope { tag, content } => {
+            let variants = u.variants.iter().map(|s| {
+                let key = rename(&syn::Member::Named(s.name.clone()), s.rename.as_ref());
+                let parse = gen_decode_struct(s);
+                quote! {
+                    #key => (|| {
+                        #parse
+                    })()
+                }
+            });
+            quote! {
+                let major = read_major(r)?;
+                if major.kind() != MajorKind::Map {
+                    return Err(UnexpectedCode::new::<Self>(major.into()).into());
+                } else if read_uint(r, major)? != 2 {
+                    return Err(LengthOutOfRange::new::<Self>().into());
+                }
+                let mut discriminant = None;
+                let mut value = None;
+                for _ in 0..2 {
+                    let key: String = Decode::decode(c, r)?;
+                    match key.as_str() {
+                        #tag => {
+                            let key: String = Decode::decode(c, r).map_err(|err| {
+                                with_path_segment(err, PathSegment::Key(#tag.into()))
+                            })?;
+                            discriminant = Some(key);
+                        }
+                        #content => {
+                            value = Some(r.seek(SeekFrom::Current(0))?);
+                            c.skip(r)?;
+                        }
+                        _ => return Err(UnexpectedKey::new::<Self>(key).into()),
+                    }
+                }
+                let discriminant = discriminant.ok_or(MissingKey::new::<Self>(#tag))?;
+                let value = value.ok_or(MissingKey::new::<Self>(#content))?;
+                let end = r.seek(SeekFrom::Current(0))?;
+                r.seek(SeekFrom::Start(value))?;
+                let result: Result<Self> = match discriminant.as_str() {
+                    #(#variants,)*
+                    _ => return Err(UnexpectedKey::new::<Self>(discriminant).into()),
+                };
+                let value =
+                    result.map_err(|err| with_path_segment(err, PathSegment::Key(#content.into())))?;
+                r.seek(SeekFrom::Start(end))?;
+                Ok(value)
+            }
+        }
         UnionRepr::IntTuple => {
             let variants = u.variants.iter().enumerate().map(|(i, s)| {
                 let i = i as u64;
                 let parse = gen_decode_struct(s);
-                quote!(#i => { #parse })
+                quote! {
+                    #i => {
+                        let result: Result<Self> = (|| {
+                            #parse
+                        })();
+                        result.map_err(|err| with_path_segment(err, PathSegment::Index(1)))
+                    }
+                }
             });
             quote! {
                 let major = read_major(r)?;
@@ -0,0 +1,638 @@
+use crate::ast::*;
+use crate::gen::{
+    field_key, gen_from_string, gen_to_string, inline_variants, kinded_variants, rename,
+};
+use proc_macro2::TokenStream;
+use quote::quote;
+
+pub fn gen_into_ipld(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
+    let (ident, generics, body) = match ast {
+        SchemaType::Struct(s) => (&s.name, s.generics.as_ref().unwrap(), gen_into_struct(s)),
+        SchemaType::Union(u) => (&u.name, &u.generics, gen_into_union(u)),
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics #libipld::convert::IntoIpld for #ident #ty_generics #where_clause {
+            fn into_ipld(self) -> #libipld::Ipld {
+                use #libipld::convert::{into_list_pairs, IntoIpld};
+                use #libipld::Ipld;
+                use std::collections::BTreeMap;
+                #body
+            }
+        }
+
+        impl #impl_generics From<#ident #ty_generics> for #libipld::Ipld #where_clause {
+            fn from(value: #ident #ty_generics) -> Self {
+                #libipld::convert::IntoIpld::into_ipld(value)
+            }
+        }
+    }
+}
+
+pub fn gen_try_from_ipld(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
+    let (ident, generics, body) = match ast {
+        SchemaType::Struct(s) => (
+            &s.name,
+            s.generics.as_ref().unwrap(),
+            gen_try_from_struct(s),
+        ),
+        SchemaType::Union(u) => (&u.name, &u.generics, gen_try_from_union(u)),
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics #libipld::convert::TryFromIpld for #ident #ty_generics #where_clause {
+            fn try_from_ipld(ipld: #libipld::Ipld) -> #libipld::Result<Self> {
+                use #libipld::convert::{try_from_list_pairs, TryFromIpld};
+                use #libipld::error::{
+                    with_path_segment, InvalidLength, InvalidString, PathSegment, Result,
+                    TypeError, TypeErrorType, UnknownKey, UnknownVariant,
+                };
+                use #libipld::Ipld;
+                #body
+            }
+        }
+
+        impl #impl_generics TryFrom<#libipld::Ipld> for #ident #ty_generics #where_clause {
+            type Error = #libipld::error::Error;
+
+            fn try_from(ipld: #libipld::Ipld) -> #libipld::Result<Self> {
+                #libipld::convert::TryFromIpld::try_from_ipld(ipld)
+            }
+        }
+    }
+}
+
+fn gen_into_struct(s: &Struct) -> TokenStream {
+    let pat = &*s.pat_move;
+    let body = gen_into_struct_body(s);
+    quote! {
+        match self {
+            #pat => { #body }
+        }
+    }
+}
+
+fn gen_into_field(field: &StructField) -> TokenStream {
+    let binding = &field.binding;
+    if field.list_pairs {
+        quote!(into_list_pairs(#binding))
+    } else {
+        quote!(IntoIpld::into_ipld(#binding))
+    }
+}
+
+fn gen_try_from_field(field: &StructField, value: TokenStream) -> TokenStream {
+    if field.list_pairs {
+        quote!(try_from_list_pairs(#value))
+    } else {
+        quote!(TryFromIpld::try_from_ipld(#value))
+    }
+}
+
+fn gen_into_struct_body(s: &Struct) -> TokenStream {
+    match &s.repr {
+        StructRepr::Map => gen_into_map(s, None),
+        StructRepr::Tuple => {
+            let fields = s.fields.iter().map(gen_into_field);
+            quote!(Ipld::List(vec![#(#fields),*]))
+        }
+        StructRepr::Value => {
+            assert_eq!(s.fields.len(), 1);
+            gen_into_field(&s.fields[0])
+        }
+        StructRepr::Null => {
+            assert_eq!(s.fields.len(), 0);
+            quote!(Ipld::Null)
+        }
+        StructRepr::StringJoin { .. } | StructRepr::StringPairs { .. } => {
+            let binding = s.fields.iter().map(|field| &field.binding);
+            let value = gen_to_string(s);
+            quote! {
+                #(let #binding = &#binding;)*
+                Ipld::String(#value)
+            }
+        }
+        StructRepr::ListPairs => {
+            let fields = s.fields.iter().map(|field| {
+                let key = field_key(field);
+                let value = gen_into_field(field);
+                let push = quote!(list.push(Ipld::List(vec![Ipld::String(#key.into()), #value])););
+                if let Some(default) = field.default.as_ref() {
+                    let binding = &field.binding;
+                    quote! {
+                        if #binding != #default {
+                            #push
+                        }
+                    }
+                } else {
+                    push
+                }
+            });
+            quote! {
+                #[allow(unused_mut)]
+                let mut list = Vec::new();
+                #(#fields)*
+                Ipld::List(list)
+            }
+        }
+    }
+}
+
+/// Converts the fields of a map struct, optionally followed by the tag of an inline union.
+///
+/// The entries of a flattened field are merged into the map. As the conversion can't fail, a
+/// flattened field that doesn't convert to a map is kept under its own key.
+fn gen_into_map(s: &Struct, tag: Option<(&str, &TokenStream)>) -> TokenStream {
+    let fields = s.fields.iter().map(|field| {
+        let key = field_key(field);
+        let binding = &field.binding;
+        let value = gen_into_field(field);
+        let insert = quote!(map.insert(#key.into(), #value););
+        if let Some(default) = field.default.as_ref() {
+            quote! {
+                if #binding != #default {
+                    #insert
+                }
+            }
+        } else {
+            insert
+        }
+    });
+    let tag = tag.map(|(tag, key)| quote!(map.insert(#tag.into(), Ipld::String(#key.into()));));
+    let map = match &s.flatten {
+        Some(flatten) => {
+            let key = field_key(flatten);
+            let value = gen_into_field(flatten);
+            quote! {
+                match #value {
+                    Ipld::Map(map) => map,
+                    ipld => BTreeMap::from([(#key.into(), ipld)]),
+                }
+            }
+        }
+        None => quote!(BTreeMap::new()),
+    };
+    quote! {
+        #[allow(unused_mut)]
+        let mut map = #map;
+        #(#fields)*
+        #tag
+        Ipld::Map(map)
+    }
+}
+
+fn gen_into_union(u: &Union) -> TokenStream {
+    if u.repr == UnionRepr::Int {
+        return quote!(Ipld::Integer(self as i128));
+    }
+    let variants = match &u.repr {
+        UnionRepr::Inline { tag } => match inline_variants(u, tag) {
+            Ok(variants) => variants,
+            Err(err) => return err,
+        },
+        _ => u.variants.clone(),
+    };
+    let arms = variants.iter().enumerate().map(|(i, s)| {
+        let pat = &*s.pat_move;
+        let key = rename(&syn::Member::Named(s.name.clone()), s.rename.as_ref());
+        let value = gen_into_struct_body(s);
+        match &u.repr {
+            UnionRepr::Keyed => {
+                quote!(#pat => Ipld::Map(BTreeMap::from([(#key.into(), { #value })])))
+            }
+            UnionRepr::Kinded => quote!(#pat => { #value }),
+            UnionRepr::String => {
+                assert_eq!(s.repr, StructRepr::Null);
+                quote!(#pat => Ipld::String(#key.into()))
+            }
+            UnionRepr::Int => unreachable!(),
+            UnionRepr::IntTuple => {
+                let i = i as i128;
+                quote!(#pat => Ipld::List(vec![Ipld::Integer(#i), { #value }]))
+            }
+            UnionRepr::Inline { tag } => {
+                let value = gen_into_map(s, Some((tag, &key)));
+                quote!(#pat => { #value })
+            }
+            UnionRepr::Envelope { tag, content } => quote! {
+                #pat => Ipld::Map(BTreeMap::from([
+                    (#tag.into(), Ipld::String(#key.into())),
+                    (#content.into(), { #value }),
+                ]))
+            },
+        }
+    });
+    quote! {
+        match self {
+            #(#arms,)*
+        }
+    }
+}
+
+fn gen_try_from_struct(s: &Struct) -> TokenStream {
+    let construct = &*s.construct;
+    match &s.repr {
+        StructRepr::Map => {
+            let fields = s.fields.iter().map(|field| {
+                let binding = &field.binding;
+                let key = rename(&field.name, field.rename.as_ref());
+                let missing = if let Some(default) = field.default.as_ref() {
+                    quote!(#default)
+                } else {
+                    quote! {
+                        return Err(
+                            TypeError::new(TypeErrorType::Key(#key.into()), TypeErrorType::Map)
+                                .into(),
+                        )
+                    }
+                };
+                let convert = gen_try_from_field(field, quote!(value));
+                quote! {
+                    let #binding = match map.remove(#key) {
+                        Some(value) => #convert.map_err(|err| {
+                            with_path_segment(err, PathSegment::Key(#key.into()))
+                        })?,
+                        None => #missing,
+                    };
+                }
+            });
+            let flatten = match (&s.flatten, s.unknown) {
+                (Some(flatten), _) => {
+                    let binding = &flatten.binding;
+                    let convert = gen_try_from_field(flatten, quote!(Ipld::Map(map)));
+                    quote!(let #binding = #convert?;)
+                }
+                (None, UnknownPolicy::Reject) => quote! {
+                    if let Some(key) = map.into_keys().next() {
+                        return Err(UnknownKey::new::<Self>(key).into());
+                    }
+                },
+                (None, UnknownPolicy::Ignore) => quote!(),
+            };
+            quote! {
+                match ipld {
+                    #[allow(unused_mut, unused_variables)]
+                    Ipld::Map(mut map) => {
+                        #(#fields)*
+                        #flatten
+                        Ok(#construct)
+                    }
+                    ipld => Err(TypeError::new(TypeErrorType::Map, ipld).into()),
+                }
+            }
+        }
+        StructRepr::Tuple => {
+            let len = s.fields.len();
+            let binding: Vec<_> = s.fields.iter().map(|field| &field.binding).collect();
+            let fields = s.fields.iter().enumerate().map(|(i, field)| {
+                let binding = &field.binding;
+                let convert = gen_try_from_field(field, quote!(#binding));
+                quote! {
+                    let #binding = #convert
+                        .map_err(|err| with_path_segment(err, PathSegment::Index(#i)))?;
+                }
+            });
+            quote! {
+                match ipld {
+                    Ipld::List(list) => {
+                        let [#(#binding),*]: [Ipld; #len] = list.try_into().map_err(|list: Vec<Ipld>| {
+                            InvalidLength {
+                                expected: #len,
+                                found: list.len(),
+                            }
+                        })?;
+                        #(#fields)*
+                        Ok(#construct)
+                    }
+                    ipld => Err(TypeError::new(TypeErrorType::List, ipld).into()),
+                }
+            }
+        }
+        StructRepr::Value => {
+            assert_eq!(s.fields.len(), 1);
+            let binding = &s.fields[0].binding;
+            let convert = gen_try_from_field(&s.fields[0], quote!(ipld));
+            quote! {
+                let #binding = #convert?;
+                Ok(#construct)
+            }
+        }
+        StructRepr::Null => {
+            assert_eq!(s.fields.len(), 0);
+            quote! {
+                match ipld {
+                    Ipld::Null => Ok(#construct),
+                    ipld => Err(TypeError::new(TypeErrorType::Null, ipld).into()),
+                }
+            }
+        }
+        StructRepr::StringJoin { .. } | StructRepr::StringPairs { .. } => {
+            let parse = gen_from_string(s);
+            quote! {
+                let value: String = TryFromIpld::try_from_ipld(ipld)?;
+                #parse
+            }
+        }
+        StructRepr::ListPairs => {
+            let binding: Vec<_> = s.fields.iter().map(|field| &field.binding).collect();
+            let key: Vec<_> = s.fields.iter().map(field_key).collect();
+            let convert: Vec<_> = s
+                .fields
+                .iter()
+                .map(|field| gen_try_from_field(field, quote!(value)))
+                .collect();
+            let fields = s.fields.iter().map(|field| {
+                let binding = &field.binding;
+                let key = field_key(field);
+                let missing = if let Some(default) = field.default.as_ref() {
+                    quote!(#default)
+                } else {
+                    quote! {
+                        return Err(
+                            TypeError::new(TypeErrorType::Key(#key.into()), TypeErrorType::List)
+                                .into(),
+                        )
+                    }
+                };
+                quote! {
+                    let #binding = match #binding {
+                        Some(value) => value,
+                        None => #missing,
+                    };
+                }
+            });
+            quote! {
+                let list = match ipld {
+                    Ipld::List(list) => list,
+                    ipld => return Err(TypeError::new(TypeErrorType::List, ipld).into()),
+                };
+                #(let mut #binding = None;)*
+                for (i, pair) in list.into_iter().enumerate() {
+                    let pair = match pair {
+                        Ipld::List(pair) => pair,
+                        ipld => {
+                            let err = TypeError::new(TypeErrorType::List, ipld).into();
+                            return Err(with_path_segment(err, PathSegment::Index(i)));
+                        }
+                    };
+                    let [key, value]: [Ipld; 2] = pair.try_into().map_err(|pair: Vec<Ipld>| {
+                        let err = InvalidLength {
+                            expected: 2,
+                            found: pair.len(),
+                        };
+                        with_path_segment(err.into(), PathSegment::Index(i))
+                    })?;
+                    let key: String = TryFromIpld::try_from_ipld(key)
+                        .map_err(|err| with_path_segment(err, PathSegment::Index(i)))?;
+                    match key.as_str() {
+                        #(#key => {
+                            #binding = Some(#convert.map_err(|err| {
+                                with_path_segment(err, PathSegment::Key(#key.into()))
+                            })?);
+                        })*
+                        _ => {
+                            let _ = value;
+                        }
+                    }
+                }
+                #(#fields)*
+                Ok(#construct)
+            }
+        }
+    }
+}
+
+fn gen_try_from_union(u: &Union) -> TokenStream {
+    match &u.repr {
+        UnionRepr::Keyed => {
+            let variants = u.variants.iter().map(|s| {
+                let key = rename(&syn::Member::Named(s.name.clone()), s.rename.as_ref());
+                let parse = gen_try_from_struct(s);
+                quote! {
+                    #key => {
+                        let result: Result<Self> = (move || {
+                            #parse
+                        })();
+                        result.map_err(|err| with_path_segment(err, PathSegment::Key(#key.into())))
+                    }
+                }
+            });
+            quote! {
+                let map = match ipld {
+                    Ipld::Map(map) => map,
+                    ipld => return Err(TypeError::new(TypeErrorType::Map, ipld).into()),
+                };
+                if map.len() != 1 {
+                    return Err(InvalidLength {
+                        expected: 1,
+                        found: map.len(),
+                    }
+                    .into());
+                }
+                let (key, ipld) = map.into_iter().next().unwrap();
+                match key.as_str() {
+                    #(#variants,)*
+                    _ => Err(UnknownVariant::new::<Self>(key).into()),
+                }
+            }
+        }
+        UnionRepr::Kinded => {
+            let (variants, fallback) = match kinded_variants(u) {
+                Ok(variants) => variants,
+                Err(err) => return err,
+            };
+            let fallback = match fallback {
+                Some(s) => {
+                    let parse = gen_try_from_struct(s);
+                    quote! {
+                        let result: Result<Self> = (move || {
+                            #parse
+                        })();
+                        result
+                    }
+                }
+                None => quote! {
+                    Err(UnknownVariant::new::<Self>(format!("{:?}", TypeErrorType::from(ipld))).into())
+                },
+            };
+            let variants = variants.into_iter().map(|(kind, s)| {
+                let pattern = match kind {
+                    Kind::Null => quote!(Ipld::Null),
+                    Kind::Bool => quote!(Ipld::Bool(_)),
+                    Kind::Int => quote!(Ipld::Integer(_)),
+                    Kind::Float => quote!(Ipld::Float(_)),
+                    Kind::String => quote!(Ipld::String(_)),
+                    Kind::Bytes => quote!(Ipld::Bytes(_)),
+                    Kind::List => quote!(Ipld::List(_)),
+                    Kind::Map => quote!(Ipld::Map(_)),
+                    Kind::Link => quote!(Ipld::Link(_)),
+                };
+                let parse = gen_try_from_struct(s);
+                quote! {
+                    if matches!(ipld, #pattern) {
+                        let result: Result<Self> = (move || {
+                            #parse
+                        })();
+                        return result;
+                    }
+                }
+            });
+            quote! {
+                #(#variants)*
+                #fallback
+            }
+        }
+        UnionRepr::String => {
+            let arms = u.variants.iter().map(|v| {
+                let pat = &*v.pat;
+                let value = rename(&syn::Member::Named(v.name.clone()), v.rename.as_ref());
+                quote!(#value => Ok(#pat))
+            });
+            quote! {
+                match ipld {
+                    Ipld::String(key) => match key.as_str() {
+                        #(#arms,)*
+                        _ => Err(UnknownVariant::new::<Self>(key).into()),
+                    },
+                    ipld => Err(TypeError::new(TypeErrorType::String, ipld).into()),
+                }
+            }
+        }
+        UnionRepr::Int => {
+            let arms = u.variants.iter().map(|v| {
+                let pat = &*v.pat;
+                quote!(x if x == #pat as i128 => Ok(#pat))
+            });
+            quote! {
+                match ipld {
+                    Ipld::Integer(key) => match key {
+                        #(#arms,)*
+                        _ => Err(UnknownVariant::new::<Self>(key.to_string()).into()),
+                    },
+                    ipld => Err(TypeError::new(TypeErrorType::Integer, ipld).into()),
+                }
+            }
+        }
+        UnionRepr::Inline { tag } => {
+            let variants = match inline_variants(u, tag) {
+                Ok(variants) => variants,
+                Err(err) => return err,
+            };
+            let variants = variants.iter().map(|s| {
+                let key = rename(&syn::Member::Named(s.name.clone()), s.rename.as_ref());
+                let parse = gen_try_from_struct(s);
+                quote! {
+                    #key => {
+                        let result: Result<Self> = (move || {
+                            #parse
+                        })();
+                        result
+                    }
+                }
+            });
+            quote! {
+                let mut map = match ipld {
+                    Ipld::Map(map) => map,
+                    ipld => return Err(TypeError::new(TypeErrorType::Map, ipld).into()),
+                };
+                let discriminant: String = match map.remove(#tag) {
+                    Some(ipld) => TryFromIpld::try_from_ipld(ipld)
+                        .map_err(|err| with_path_segment(err, PathSegment::Key(#tag.into())))?,
+                    None => {
+                        return Err(
+                            TypeError::new(TypeErrorType::Key(#tag.into()), TypeErrorType::Map)
+                                .into(),
+                        )
+                    }
+                };
+                let ipld = Ipld::Map(map);
+                match discriminant.as_str() {
+                    #(#variants,)*
+                    _ => Err(UnknownVariant::new::<Self>(discriminant).into()),
+                }
+            }
+        }
+        UnionRepr::Envelope { tag, content } => {
+            let variants = u.variants.iter().map(|s| {
+                let key = rename(&syn::Member::Named(s.name.clone()), s.rename.as_ref());
+                let parse = gen_try_from_struct(s);
+                quote! {
+                    #key => {
+                        let result: Result<Self> = (move || {
+                            #parse
+                        })();
+                        result.map_err(|err| with_path_segment(err, PathSegment::Key(#content.into())))
+                    }
+                }
+            });
+            quote! {
+                let mut map = match ipld {
+                    Ipld::Map(map) => map,
+                    ipld => return Err(TypeError::new(TypeErrorType::Map, ipld).into()),
+                };
+                if map.len() != 2 {
+                    return Err(InvalidLength {
+                        expected: 2,
+                        found: map.len(),
+                    }
+                    .into());
+                }
+                let (discriminant, ipld) = match (map.remove(#tag), map.remove(#content)) {
+                    (Some(discriminant), Some(ipld)) => (discriminant, ipld),
+                    (None, _) => {
+                        return Err(
+                            TypeError::new(TypeErrorType::Key(#tag.into()), TypeErrorType::Map)
+                                .into(),
+                        )
+                    }
+                    (_, None) => {
+                        return Err(
+                            TypeError::new(TypeErrorType::Key(#content.into()), TypeErrorType::Map)
+                                .into(),
+                        )
+                    }
+                };
+                let discriminant: String = TryFromIpld::try_from_ipld(discriminant)
+                    .map_err(|err| with_path_segment(err, PathSegment::Key(#tag.into())))?;
+                match discriminant.as_str() {
+                    #(#variants,)*
+                    _ => Err(UnknownVariant::new::<Self>(discriminant).into()),
+                }
+            }
+        }
+        UnionRepr::IntTuple => {
+            let variants = u.variants.iter().enumerate().map(|(i, s)| {
+                let i = i as i128;
+                let parse = gen_try_from_struct(s);
+                quote! {
+                    #i => {
+                        let result: Result<Self> = (move || {
+                            #parse
+                        })();
+                        result.map_err(|err| with_path_segment(err, PathSegment::Index(1)))
+                    }
+                }
+            });
+            quote! {
+                let list = match ipld {
+                    Ipld::List(list) => list,
+                    ipld => return Err(TypeError::new(TypeErrorType::List, ipld).into()),
+                };
+                let [ty, ipld]: [Ipld; 2] = list.try_into().map_err(|list: Vec<Ipld>| {
+                    InvalidLength {
+                        expected: 2,
+                        found: list.len(),
+                    }
+                })?;
+                let ty: i128 = TryFromIpld::try_from_ipld(ty)
+                    .map_err(|err| with_path_segment(err, PathSegment::Index(0)))?;
+                match ty {
+                    #(#variants,)*
+                    _ => Err(UnknownVariant::new::<Self>(ty.to_string()).into()),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,741 @@
+use crate::ast::*;
+use crate::gen::{
+    default, field_key, gen_encode_match, gen_from_string, gen_to_string, inline_variants,
+    kinded_variants, rename,
+};
+use proc_macro2::TokenStream;
+use quote::quote;
+
+pub fn gen_encode(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
+    let (ident, generics, body) = match ast {
+        SchemaType::Struct(s) => (&s.name, s.generics.as_ref().unwrap(), gen_encode_struct(s)),
+        SchemaType::Union(u) => (&u.name, &u.generics, gen_encode_union(u)),
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let trait_name = quote!(#libipld::codec::Encode<#libipld::json::DagJsonCodec>);
+
+    quote! {
+        impl #impl_generics #trait_name for #ident #ty_generics #where_clause {
+            fn encode<W: std::io::Write>(
+                &self,
+                c: #libipld::json::DagJsonCodec,
+                w: &mut W,
+            ) -> #libipld::Result<()> {
+                use #libipld::codec::Encode;
+                use #libipld::json::decode::read_map_entries;
+                use #libipld::json::encode::{
+                    write_key, write_list_pairs, write_map_entries, write_null,
+                };
+                #body
+            }
+        }
+    }
+}
+
+pub fn gen_decode(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
+    let (ident, generics, body) = match ast {
+        SchemaType::Struct(s) => (
+            &s.name,
+            s.generics.as_ref().unwrap(),
+            gen_decode_struct(s, libipld),
+        ),
+        SchemaType::Union(u) => (&u.name, &u.generics, gen_decode_union(u, libipld)),
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let trait_name = quote!(#libipld::codec::Decode<#libipld::json::DagJsonCodec>);
+
+    quote! {
+        impl #impl_generics #trait_name for #ident #ty_generics #where_clause {
+            fn decode<R: std::io::Read + std::io::Seek>(
+                c: #libipld::json::DagJsonCodec,
+                r: &mut R,
+            ) -> #libipld::Result<Self> {
+                use #libipld::codec::Decode;
+                use #libipld::error::{with_path_segment, InvalidString, PathSegment, Result};
+                use #libipld::json::decode::{
+                    expect_byte, peek_byte, peek_kind, read_key, read_list_pairs, read_raw_value,
+                    read_separator, read_value,
+                };
+                use #libipld::json::encode::write_map_entries;
+                use #libipld::json::error::{
+                    LengthOutOfRange, MissingKey, UnexpectedChar, UnexpectedKey,
+                };
+                use std::io::SeekFrom;
+                #body
+            }
+        }
+    }
+}
+
+fn gen_encode_field(field: &StructField) -> TokenStream {
+    let binding = &field.binding;
+    if field.list_pairs {
+        quote!(write_list_pairs(w, #binding)?;)
+    } else {
+        quote!(Encode::encode(#binding, c, w)?;)
+    }
+}
+
+fn gen_decode_field(field: &StructField) -> TokenStream {
+    if field.list_pairs {
+        quote!(read_list_pairs(r))
+    } else {
+        quote!(Decode::decode(c, r))
+    }
+}
+
+fn gen_encode_struct(s: &Struct) -> TokenStream {
+    let pat = &*s.pat;
+    let body = gen_encode_struct_body(s);
+    gen_encode_match(std::iter::once(quote!(#pat => { #body })))
+}
+
+fn gen_encode_struct_body(s: &Struct) -> TokenStream {
+    match &s.repr {
+        StructRepr::Map => gen_encode_map(s, None),
+        StructRepr::Tuple => {
+            let fields = s.fields.iter().enumerate().map(|(i, field)| {
+                let separator = if i > 0 {
+                    quote!(w.write_all(b",")?;)
+                } else {
+                    quote!()
+                };
+                let value = gen_encode_field(field);
+                quote! {
+                    #separator
+                    #value
+                }
+            });
+            quote! {
+                w.write_all(b"[")?;
+                #(#fields)*
+                w.write_all(b"]")?;
+            }
+        }
+        StructRepr::Value => {
+            assert_eq!(s.fields.len(), 1);
+            let field = &s.fields[0];
+            let binding = &field.binding;
+            default(binding, field.default.as_deref(), gen_encode_field(field))
+        }
+        StructRepr::Null => {
+            assert_eq!(s.fields.len(), 0);
+            quote!(write_null(w)?;)
+        }
+        StructRepr::StringJoin { .. } | StructRepr::StringPairs { .. } => {
+            let value = gen_to_string(s);
+            quote! {
+                let value: String = #value;
+                Encode::encode(&value, c, w)?;
+            }
+        }
+        StructRepr::ListPairs => {
+            let fields = s.fields.iter().map(|field| {
+                let key = field_key(field);
+                let value = gen_encode_field(field);
+                default(
+                    &field.binding,
+                    field.default.as_deref(),
+                    quote! {
+                        if !std::mem::replace(&mut first, false) {
+                            w.write_all(b",")?;
+                        }
+                        w.write_all(b"[")?;
+                        Encode::encode(#key, c, w)?;
+                        w.write_all(b",")?;
+                        #value
+                        w.write_all(b"]")?;
+                    },
+                )
+            });
+            let first = if s.fields.is_empty() {
+                quote!()
+            } else {
+                quote!(let mut first = true;)
+            };
+            quote! {
+                w.write_all(b"[")?;
+                #first
+                #(#fields)*
+                w.write_all(b"]")?;
+            }
+        }
+    }
+}
+
+/// Encodes the fields of a map struct, optionally followed by the tag of an inline union.
+fn gen_encode_map(s: &Struct, tag: Option<(&str, &TokenStream)>) -> TokenStream {
+    if let Some(flatten) = &s.flatten {
+        return gen_encode_flattened_map(s, flatten, tag);
+    }
+    let mut json_order = s
+        .fields
+        .iter()
+        .map(|field| {
+            let key = field_key(field);
+            let value = gen_encode_field(field);
+            let field = default(
+                &field.binding,
+                field.default.as_deref(),
+                quote! {
+                    write_key(w, #key, &mut first)?;
+                    #value
+                },
+            );
+            (key.to_string(), field)
+        })
+        .collect::<Vec<(String, _)>>();
+    if let Some((tag, key)) = tag {
+        let field = quote! {
+            write_key(w, #tag, &mut first)?;
+            Encode::encode(#key, c, w)?;
+        };
+        json_order.push((quote!(#tag).to_string(), field));
+    }
+    // Unlike dag-cbor, dag-json sorts keys bytewise.
+    json_order.sort_unstable_by(|(key_a, _), (key_b, _)| key_a.cmp(key_b));
+    let fields = json_order.iter().map(|(_, field)| field);
+    let first = if json_order.is_empty() {
+        quote!()
+    } else {
+        quote!(let mut first = true;)
+    };
+    quote! {
+        w.write_all(b"{")?;
+        #first
+        #(#fields)*
+        w.write_all(b"}")?;
+    }
+}
+
+/// Encodes a map struct with a flattened field.
+///
+/// The keys of the flattened field are only known at runtime, so all entries are encoded
+/// separately and sorted before they are written.
+fn gen_encode_flattened_map(
+    s: &Struct,
+    flatten: &StructField,
+    tag: Option<(&str, &TokenStream)>,
+) -> TokenStream {
+    let fields = s.fields.iter().map(|field| {
+        let key = field_key(field);
+        let value = gen_encode_field(field);
+        default(
+            &field.binding,
+            field.default.as_deref(),
+            quote! {
+                let mut value = Vec::new();
+                {
+                    let w = &mut value;
+                    #value
+                }
+                entries.push((#key.into(), value));
+            },
+        )
+    });
+    let tag = tag.map(|(tag, key)| {
+        quote! {
+            let mut value = Vec::new();
+            Encode::encode(#key, c, &mut value)?;
+            entries.push((#tag.into(), value));
+        }
+    });
+    let binding = &flatten.binding;
+    quote! {
+        let mut value = Vec::new();
+        Encode::encode(#binding, c, &mut value)?;
+        let mut entries = read_map_entries(&mut std::io::Cursor::new(value))?;
+        #(#fields)*
+        #tag
+        write_map_entries(w, entries)?;
+    }
+}
+
+#[allow(clippy::needless_collect)]
+fn gen_encode_union(u: &Union) -> TokenStream {
+    let variants = match &u.repr {
+        UnionRepr::Inline { tag } => match inline_variants(u, tag) {
+            Ok(variants) => variants,
+            Err(err) => return err,
+        },
+        _ => u.variants.clone(),
+    };
+    let arms = variants
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let pat = &*s.pat;
+            let key = rename(&syn::Member::Named(s.name.clone()), s.rename.as_ref());
+            let value = gen_encode_struct_body(s);
+            match &u.repr {
+                UnionRepr::Keyed => {
+                    quote! {
+                        #pat => {
+                            w.write_all(b"{")?;
+                            Encode::encode(#key, c, w)?;
+                            w.write_all(b":")?;
+                            #value
+                            w.write_all(b"}")?;
+                        }
+                    }
+                }
+                UnionRepr::Kinded => {
+                    quote!(#pat => { #value })
+                }
+                UnionRepr::String => {
+                    assert_eq!(s.repr, StructRepr::Null);
+                    quote!(#pat => Encode::encode(#key, c, w)?)
+                }
+                UnionRepr::Int => {
+                    assert_eq!(s.repr, StructRepr::Null);
+                    quote!()
+                }
+                UnionRepr::IntTuple => {
+                    let i = i as u64;
+                    quote! {
+                        #pat => {
+                            w.write_all(b"[")?;
+                            Encode::encode(&#i, c, w)?;
+                            w.write_all(b",")?;
+                            #value
+                            w.write_all(b"]")?;
+                        }
+                    }
+                }
+                UnionRepr::Inline { tag } => {
+                    let value = gen_encode_map(s, Some((tag, &key)));
+                    quote!(#pat => { #value })
+                }
+                UnionRepr::Envelope { tag, content } => {
+                    let mut entries = [
+                        (tag, quote!(Encode::encode(#key, c, w)?;)),
+                        (content, quote!({ #value })),
+                    ];
+                    entries.sort_unstable_by_key(|(key, _)| *key);
+                    let entries = entries.iter().map(|(key, value)| {
+                        quote! {
+                            write_key(w, #key, &mut first)?;
+                            #value
+                        }
+                    });
+                    quote! {
+                        #pat => {
+                            w.write_all(b"{")?;
+                            let mut first = true;
+                            #(#entries)*
+                            w.write_all(b"}")?;
+                        }
+                    }
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    if u.repr == UnionRepr::Int {
+        quote!(Encode::encode(&(*self as u64), c, w))
+    } else {
+        gen_encode_match(arms.into_iter())
+    }
+}
+
+fn gen_decode_struct(s: &Struct, libipld: &syn::Ident) -> TokenStream {
+    let len = s.fields.len();
+    let construct = &*s.construct;
+    match &s.repr {
+        StructRepr::Map => gen_decode_map(s, None, libipld),
+        StructRepr::Tuple => {
+            let fields = s.fields.iter().enumerate().map(|(i, field)| {
+                let binding = &field.binding;
+                let decode = gen_decode_field(field);
+                quote! {
+                    if !read_separator::<Self, _>(r, b']', #i)? {
+                        return Err(LengthOutOfRange::new::<Self>().into());
+                    }
+                    let #binding = #decode
+                        .map_err(|err| with_path_segment(err, PathSegment::Index(#i)))?;
+                }
+            });
+            quote! {
+                expect_byte::<Self, _>(r, b'[')?;
+                #(#fields)*
+                if read_separator::<Self, _>(r, b']', #len)? {
+                    return Err(LengthOutOfRange::new::<Self>().into());
+                }
+                return Ok(#construct);
+            }
+        }
+        StructRepr::Value => {
+            assert_eq!(s.fields.len(), 1);
+            let binding = &s.fields[0].binding;
+            let decode = gen_decode_field(&s.fields[0]);
+            quote! {
+                let #binding = #decode?;
+                return Ok(#construct);
+            }
+        }
+        StructRepr::Null => {
+            assert_eq!(s.fields.len(), 0);
+            quote! {
+                read_value::<(), _>(r)?;
+                return Ok(#construct);
+            }
+        }
+        StructRepr::StringJoin { .. } | StructRepr::StringPairs { .. } => {
+            let parse = gen_from_string(s);
+            quote! {
+                let value: String = Decode::decode(c, r)?;
+                #parse
+            }
+        }
+        StructRepr::ListPairs => {
+            let binding: Vec<_> = s.fields.iter().map(|field| &field.binding).collect();
+            let key: Vec<_> = s.fields.iter().map(field_key).collect();
+            let decode: Vec<_> = s.fields.iter().map(gen_decode_field).collect();
+            let fields = s.fields.iter().map(|field| {
+                let binding = &field.binding;
+                let key = field_key(field);
+                if let Some(default) = field.default.as_ref() {
+                    quote!(let #binding = #binding.unwrap_or(#default);)
+                } else {
+                    quote!(let #binding = #binding.ok_or(MissingKey::new::<Self>(#key))?;)
+                }
+            });
+            quote! {
+                expect_byte::<Self, _>(r, b'[')?;
+                #(let mut #binding = None;)*
+                let mut i = 0;
+                while read_separator::<Self, _>(r, b']', i)? {
+                    i += 1;
+                    expect_byte::<Self, _>(r, b'[')?;
+                    let key: String = Decode::decode(c, r)?;
+                    expect_byte::<Self, _>(r, b',')?;
+                    match key.as_str() {
+                        #(#key => {
+                            #binding = Some(#decode.map_err(|err| {
+                                with_path_segment(err, PathSegment::Key(#key.into()))
+                            })?);
+                        })*
+                        _ => {
+                            let _: #libipld::Ipld = Decode::decode(c, r)?;
+                        }
+                    }
+                    expect_byte::<Self, _>(r, b']')?;
+                }
+                #(#fields)*
+                return Ok(#construct);
+            }
+        }
+    }
+}
+
+/// Decodes the fields of a map struct, skipping the tag of an inline union.
+///
+/// Unknown entries are passed on to the flattened field, if there is one. Otherwise they are
+/// rejected or skipped, depending on the unknown policy of the struct.
+fn gen_decode_map(s: &Struct, tag: Option<&str>, libipld: &syn::Ident) -> TokenStream {
+    let construct = &*s.construct;
+    let binding: Vec<_> = s.fields.iter().map(|field| &field.binding).collect();
+    let key: Vec<_> = s
+        .fields
+        .iter()
+        .map(|field| rename(&field.name, field.rename.as_ref()))
+        .collect();
+    let fields: Vec<_> = s
+        .fields
+        .iter()
+        .map(|field| {
+            let binding = &field.binding;
+            let key = rename(&field.name, field.rename.as_ref());
+            if let Some(default) = field.default.as_ref() {
+                quote!(let #binding = #binding.unwrap_or(#default);)
+            } else {
+                quote!(let #binding = #binding.ok_or(MissingKey::new::<Self>(#key))?;)
+            }
+        })
+        .collect();
+    let decode: Vec<_> = s.fields.iter().map(gen_decode_field).collect();
+    let tag = tag.map(|tag| {
+        quote! {
+            #tag => {
+                let _: String = Decode::decode(c, r)?;
+            }
+        }
+    });
+    let (rest, unknown, flatten) = match &s.flatten {
+        Some(flatten) => {
+            let binding = &flatten.binding;
+            (
+                quote!(let mut rest: Vec<(String, Vec<u8>)> = Vec::new();),
+                quote!(rest.push((key, read_raw_value(r)?));),
+                quote! {
+                    let mut value = Vec::new();
+                    write_map_entries(&mut value, rest)?;
+                    let #binding = Decode::decode(c, &mut std::io::Cursor::new(value))?;
+                },
+            )
+        }
+        None => {
+            let unknown = match s.unknown {
+                UnknownPolicy::Reject => {
+                    quote!(return Err(UnexpectedKey::new::<Self>(key).into());)
+                }
+                UnknownPolicy::Ignore => quote!(let _: #libipld::Ipld = Decode::decode(c, r)?;),
+            };
+            (quote!(), unknown, quote!())
+        }
+    };
+    quote! {
+        expect_byte::<Self, _>(r, b'{')?;
+        #(let mut #binding = None;)*
+        #rest
+        let mut i = 0;
+        while read_separator::<Self, _>(r, b'}', i)? {
+            i += 1;
+            let key = read_key::<Self, _>(r)?;
+            match key.as_str() {
+                #(#key => {
+                    #binding = Some(#decode.map_err(|err| {
+                        with_path_segment(err, PathSegment::Key(#key.into()))
+                    })?);
+                })*
+                #tag
+                _ => {
+                    #unknown
+                }
+            }
+        }
+
+        #(#fields)*
+        #flatten
+
+        return Ok(#construct);
+    }
+}
+
+fn gen_decode_union(u: &Union, libipld: &syn::Ident) -> TokenStream {
+    match &u.repr {
+        UnionRepr::Keyed => {
+            let variants = u.variants.iter().map(|s| {
+                let key = rename(&syn::Member::Named(s.name.clone()), s.rename.as_ref());
+                let parse = gen_decode_struct(s, libipld);
+                quote! {
+                    #key => {
+                        let result: Result<Self> = (|| {
+                            #parse
+                        })();
+                        result.map_err(|err| with_path_segment(err, PathSegment::Key(#key.into())))?
+                    }
+                }
+            });
+            quote! {
+                expect_byte::<Self, _>(r, b'{')?;
+                if !read_separator::<Self, _>(r, b'}', 0)? {
+                    return Err(LengthOutOfRange::new::<Self>().into());
+                }
+                let key = read_key::<Self, _>(r)?;
+                let value = match key.as_str() {
+                    #(#variants,)*
+                    _ => return Err(UnexpectedKey::new::<Self>(key).into()),
+                };
+                if read_separator::<Self, _>(r, b'}', 1)? {
+                    return Err(LengthOutOfRange::new::<Self>().into());
+                }
+                Ok(value)
+            }
+        }
+        UnionRepr::Kinded => {
+            let (variants, fallback) = match kinded_variants(u) {
+                Ok(variants) => variants,
+                Err(err) => return err,
+            };
+            let fallback = match fallback {
+                Some(s) => {
+                    let parse = gen_decode_struct(s, libipld);
+                    quote! {
+                        let result: Result<Self> = (|| {
+                            #parse
+                        })();
+                        result
+                    }
+                }
+                None => quote!(Err(UnexpectedChar::new::<Self>(peek_byte(r)?).into())),
+            };
+            let arms = variants.into_iter().map(|(kind, s)| {
+                let kind = match kind {
+                    Kind::Null => quote!(Null),
+                    Kind::Bool => quote!(Bool),
+                    Kind::Int => quote!(Integer),
+                    Kind::Float => quote!(Float),
+                    Kind::String => quote!(String),
+                    Kind::Bytes => quote!(Bytes),
+                    Kind::List => quote!(List),
+                    Kind::Map => quote!(Map),
+                    Kind::Link => quote!(Link),
+                };
+                let parse = gen_decode_struct(s, libipld);
+                quote! {
+                    #libipld::json::decode::Kind::#kind => {
+                        let result: Result<Self> = (|| {
+                            #parse
+                        })();
+                        result
+                    }
+                }
+            });
+            quote! {
+                match peek_kind::<Self, _>(r)? {
+                    #(#arms,)*
+                    _ => {
+                        #fallback
+                    }
+                }
+            }
+        }
+        UnionRepr::String => {
+            let arms = u.variants.iter().map(|v| {
+                let pat = &*v.pat;
+                let value = rename(&syn::Member::Named(v.name.clone()), v.rename.as_ref());
+                quote!(#value => #pat)
+            });
+            quote! {
+                let key: String = Decode::decode(c, r)?;
+                let res = match key.as_str() {
+                    #(#arms,)*
+                    _ => return Err(UnexpectedKey::new::<Self>(key).into()),
+                };
+                Ok(res)
+            }
+        }
+        UnionRepr::Int => {
+            let arms = u.variants.iter().map(|v| {
+                let pat = &*v.pat;
+                quote!(x if x == #pat as u64 => #pat)
+            });
+            quote! {
+                let key: u64 = Decode::decode(c, r)?;
+                let res = match key {
+                    #(#arms,)*
+                    _ => return Err(UnexpectedKey::new::<Self>(key.to_string()).into()),
+                };
+                Ok(res)
+            }
+        }
+        UnionRepr::Inline { tag } => {
+            let variants = match inline_variants(u, tag) {
+                Ok(variants) => variants,
+                Err(err) => return err,
+            };
+            let variants = variants.iter().map(|s| {
+                let key = rename(&syn::Member::Named(s.name.clone()), s.rename.as_ref());
+                let parse = gen_decode_map(s, Some(tag), libipld);
+                quote! {
+                    if discriminant.as_str() == #key {
+                        let result: Result<Self> = (|| {
+                            #parse
+                        })();
+                        return result;
+                    }
+                }
+            });
+            quote! {
+                let pos = r.seek(SeekFrom::Current(0))?;
+                expect_byte::<Self, _>(r, b'{')?;
+                let mut discriminant = None;
+                let mut i = 0;
+                while read_separator::<Self, _>(r, b'}', i)? {
+                    i += 1;
+                    let key = read_key::<Self, _>(r)?;
+                    if key == #tag {
+                        let value: String = Decode::decode(c, r).map_err(|err| {
+                            with_path_segment(err, PathSegment::Key(#tag.into()))
+                        })?;
+                        discriminant = Some(value);
+                        break;
+                    }
+                    let _: #libipld::Ipld = Decode::decode(c, r)?;
+                }
+                let discriminant = discriminant.ok_or(MissingKey::new::<Self>(#tag))?;
+                r.seek(SeekFrom::Start(pos))?;
+                #(#variants)*
+                Err(UnexpectedKey::new::<Self>(discriminant).into())
+            }
+        }
+        UnionRepr::Envelope { tag, content } => {
+            let variants = u.variants.iter().map(|s| {
+                let key = rename(&syn::Member::Named(s.name.clone()), s.rename.as_ref());
+                let parse = gen_decode_struct(s, libipld);
+                quote! {
+                    #key => (|| {
+                        #parse
+                    })()
+                }
+            });
+            quote! {
+                expect_byte::<Self, _>(r, b'{')?;
+                let mut discriminant = None;
+                let mut value = None;
+                let mut i = 0;
+                while read_separator::<Self, _>(r, b'}', i)? {
+                    i += 1;
+                    let key = read_key::<Self, _>(r)?;
+                    match key.as_str() {
+                        #tag if discriminant.is_none() => {
+                            let key: String = Decode::decode(c, r).map_err(|err| {
+                                with_path_segment(err, PathSegment::Key(#tag.into()))
+                            })?;
+                            discriminant = Some(key);
+                        }
+                        #content if value.is_none() => {
+                            value = Some(r.seek(SeekFrom::Current(0))?);
+                            let _: #libipld::Ipld = Decode::decode(c, r)?;
+                        }
+                        _ => return Err(UnexpectedKey::new::<Self>(key).into()),
+                    }
+                }
+                let discriminant = discriminant.ok_or(MissingKey::new::<Self>(#tag))?;
+                let value = value.ok_or(MissingKey::new::<Self>(#content))?;
+                let end = r.seek(SeekFrom::Current(0))?;
+                r.seek(SeekFrom::Start(value))?;
+                let result: Result<Self> = match discriminant.as_str() {
+                    #(#variants,)*
+                    _ => return Err(UnexpectedKey::new::<Self>(discriminant).into()),
+                };
+                let value =
+                    result.map_err(|err| with_path_segment(err, PathSegment::Key(#content.into())))?;
+                r.seek(SeekFrom::Start(end))?;
+                Ok(value)
+            }
+        }
+        UnionRepr::IntTuple => {
+            let variants = u.variants.iter().enumerate().map(|(i, s)| {
+                let i = i as u64;
+                let parse = gen_decode_struct(s, libipld);
+                quote! {
+                    #i => {
+                        let result: Result<Self> = (|| {
+                            #parse
+                        })();
+                        result.map_err(|err| with_path_segment(err, PathSegment::Index(1)))?
+                    }
+                }
+            });
+            quote! {
+                expect_byte::<Self, _>(r, b'[')?;
+                if !read_separator::<Self, _>(r, b']', 0)? {
+                    return Err(LengthOutOfRange::new::<Self>().into());
+                }
+                let ty: u64 = Decode::decode(c, r)?;
+                if !read_separator::<Self, _>(r, b']', 1)? {
+                    return Err(LengthOutOfRange::new::<Self>().into());
+                }
+                let value = match ty {
+                    #(#variants,)*
+                    _ => return Err(UnexpectedKey::new::<Self>(ty.to_string()).into()),
+                };
+                if read_separator::<Self, _>(r, b']', 2)? {
+                    return Err(LengthOutOfRange::new::<Self>().into());
+                }
+                Ok(value)
+            }
+        }
+    }
+}
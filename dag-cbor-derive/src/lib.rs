@@ -4,10 +4,16 @@ use quote::quote;
 use synstructure::{decl_derive, Structure};
 
 decl_derive!([DagCbor, attributes(ipld)] => dag_cbor_derive);
+decl_derive!([DagJson, attributes(ipld)] => dag_json_derive);
+decl_derive!([IntoIpld, attributes(ipld)] => into_ipld_derive);
+decl_derive!([TryFromIpld, attributes(ipld)] => try_from_ipld_derive);
 
 mod ast;
 mod attr;
+mod case;
 mod gen;
+mod gen_ipld;
+mod gen_json;
 mod parse;
 
 fn dag_cbor_derive(s: Structure) -> TokenStream {
@@ -24,6 +30,38 @@ fn dag_cbor_derive(s: Structure) -> TokenStream {
     }
 }
 
+fn dag_json_derive(s: Structure) -> TokenStream {
+    let libipld = match use_crate("libipld") {
+        Ok(ident) => ident,
+        Err(error) => return error,
+    };
+    let ast = parse::parse(&s);
+    let encode = gen_json::gen_encode(&ast, &libipld);
+    let decode = gen_json::gen_decode(&ast, &libipld);
+    quote! {
+        #encode
+        #decode
+    }
+}
+
+fn into_ipld_derive(s: Structure) -> TokenStream {
+    let libipld = match use_crate("libipld") {
+        Ok(ident) => ident,
+        Err(error) => return error,
+    };
+    let ast = parse::parse(&s);
+    gen_ipld::gen_into_ipld(&ast, &libipld)
+}
+
+fn try_from_ipld_derive(s: Structure) -> TokenStream {
+    let libipld = match use_crate("libipld") {
+        Ok(ident) => ident,
+        Err(error) => return error,
+    };
+    let ast = parse::parse(&s);
+    gen_ipld::gen_try_from_ipld(&ast, &libipld)
+}
+
 /// Get the name of a crate based on its original name.
 ///
 /// This works even if the crate was renamed in the `Cargo.toml` file. If the crate is not a
@@ -1,9 +1,11 @@
 use crate::ast::*;
 use crate::attr::{Attrs, DeriveAttr, FieldAttr};
+use crate::case::RenameRule;
 use quote::quote;
+use syn::ext::IdentExt;
 use syn::parse::Parse;
 use syn::spanned::Spanned;
-use synstructure::{BindingInfo, Structure, VariantInfo};
+use synstructure::{BindStyle, BindingInfo, Structure, VariantInfo};
 
 pub fn parse(s: &Structure) -> SchemaType {
     match &s.ast().data {
@@ -32,47 +34,244 @@ fn parse_attrs<T: Parse>(ast: &[syn::Attribute]) -> Vec<T> {
 fn parse_struct_repr(ast: &[syn::Attribute]) -> Option<StructRepr> {
     let attrs = parse_attrs::<DeriveAttr>(ast);
     let mut repr = None;
-    for DeriveAttr::Repr(attr) in attrs {
-        repr = Some(match attr.value.value().as_str() {
-            "map" => StructRepr::Map,
-            "tuple" => StructRepr::Tuple,
-            "value" => StructRepr::Value,
-            "null" => StructRepr::Null,
-            repr => panic!("unknown struct representation {}", repr),
-        })
+    let mut join = None;
+    let mut inner_delim = None;
+    let mut entry_delim = None;
+    let mut transparent = false;
+    for attr in attrs {
+        match attr {
+            DeriveAttr::Repr(attr) => repr = Some(attr.value.value()),
+            DeriveAttr::Transparent => transparent = true,
+            DeriveAttr::Join(attr) => join = Some(attr.value.value()),
+            DeriveAttr::InnerDelim(attr) => inner_delim = Some(attr.value.value()),
+            DeriveAttr::EntryDelim(attr) => entry_delim = Some(attr.value.value()),
+            _ => {}
+        }
     }
-    repr
+    if transparent {
+        if repr.is_some() {
+            panic!("`transparent` cannot be combined with `repr`");
+        }
+        repr = Some("value".into());
+    }
+    let repr = match repr.as_deref()? {
+        "map" => StructRepr::Map,
+        "tuple" => StructRepr::Tuple,
+        "value" => StructRepr::Value,
+        "null" => StructRepr::Null,
+        "stringjoin" => {
+            let join = join
+                .take()
+                .expect("the stringjoin representation requires `join`");
+            StructRepr::StringJoin { join }
+        }
+        "stringpairs" => match (inner_delim.take(), entry_delim.take()) {
+            (Some(inner_delim), Some(entry_delim)) => StructRepr::StringPairs {
+                inner_delim,
+                entry_delim,
+            },
+            _ => panic!("the stringpairs representation requires `inner_delim` and `entry_delim`"),
+        },
+        "listpairs" => StructRepr::ListPairs,
+        repr => panic!("unknown struct representation {}", repr),
+    };
+    if join.is_some() {
+        panic!("`join` is only supported by the stringjoin representation");
+    }
+    if inner_delim.is_some() || entry_delim.is_some() {
+        panic!(
+            "`inner_delim` and `entry_delim` are only supported by the stringpairs representation"
+        );
+    }
+    Some(repr)
 }
 
 fn parse_union_repr(ast: &[syn::Attribute]) -> UnionRepr {
     let attrs = parse_attrs::<DeriveAttr>(ast);
     let mut repr = None;
-    for DeriveAttr::Repr(attr) in attrs {
-        repr = Some(match attr.value.value().as_str() {
-            "keyed" => UnionRepr::Keyed,
-            "kinded" => UnionRepr::Kinded,
-            "string" => UnionRepr::String,
-            "int" => UnionRepr::Int,
-            "int-tuple" => UnionRepr::IntTuple,
-            repr => panic!("unknown enum representation {}", repr),
-        })
-    }
-    repr.unwrap_or(UnionRepr::Keyed)
+    let mut tag = None;
+    let mut content = None;
+    for attr in attrs {
+        match attr {
+            DeriveAttr::Repr(attr) => repr = Some(attr.value.value()),
+            DeriveAttr::Tag(attr) => tag = Some(attr.value.value()),
+            DeriveAttr::Content(attr) => content = Some(attr.value.value()),
+            DeriveAttr::Transparent => panic!("`transparent` is only supported by structs"),
+            _ => {}
+        }
+    }
+    let repr = match repr.as_deref().unwrap_or("keyed") {
+        "keyed" => UnionRepr::Keyed,
+        "kinded" => UnionRepr::Kinded,
+        "string" => UnionRepr::String,
+        "int" => UnionRepr::Int,
+        "int-tuple" => UnionRepr::IntTuple,
+        "inline" => {
+            if content.is_some() {
+                panic!("`content` is only supported by the envelope representation");
+            }
+            return UnionRepr::Inline {
+                tag: tag.unwrap_or_else(|| "type".into()),
+            };
+        }
+        "envelope" => {
+            let tag = tag.unwrap_or_else(|| "type".into());
+            let content = content.unwrap_or_else(|| "content".into());
+            if tag == content {
+                panic!("`tag` and `content` must be different keys");
+            }
+            return UnionRepr::Envelope { tag, content };
+        }
+        repr => panic!("unknown enum representation {}", repr),
+    };
+    if tag.is_some() || content.is_some() {
+        panic!("`tag` and `content` are only supported by the inline and envelope representations");
+    }
+    repr
+}
+
+fn parse_kind(ast: &[syn::Attribute]) -> Option<Kind> {
+    let attrs = parse_attrs::<DeriveAttr>(ast);
+    let mut kind = None;
+    for attr in attrs {
+        if let DeriveAttr::Kind(attr) = attr {
+            let name = attr.value.value();
+            kind = Some(Kind::from_name(&name).unwrap_or_else(|| panic!("unknown kind {}", name)));
+        }
+    }
+    kind
+}
+
+fn parse_rename_all(ast: &[syn::Attribute]) -> Option<RenameRule> {
+    let attrs = parse_attrs::<DeriveAttr>(ast);
+    let mut rule = None;
+    for attr in attrs {
+        if let DeriveAttr::RenameAll(attr) = attr {
+            let name = attr.value.value();
+            rule = Some(
+                RenameRule::from_name(&name).unwrap_or_else(|| panic!("unknown casing {}", name)),
+            );
+        }
+    }
+    rule
+}
+
+fn parse_unknown(ast: &[syn::Attribute]) -> Option<String> {
+    let attrs = parse_attrs::<DeriveAttr>(ast);
+    let mut unknown = None;
+    for attr in attrs {
+        if let DeriveAttr::Unknown(attr) = attr {
+            unknown = Some(attr.value.value());
+        }
+    }
+    unknown
+}
+
+/// Infers the kind a field type encodes to, if it is a well known type.
+fn infer_kind(ty: &syn::Type) -> Option<Kind> {
+    let path = match ty {
+        syn::Type::Reference(reference) => return infer_kind(&reference.elem),
+        syn::Type::Tuple(tuple) if tuple.elems.is_empty() => return Some(Kind::Null),
+        syn::Type::Path(path) => path,
+        _ => return None,
+    };
+    let segment = path.path.segments.last()?;
+    let arg = match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+            Some(syn::GenericArgument::Type(ty)) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    };
+    Some(match segment.ident.to_string().as_str() {
+        "bool" => Kind::Bool,
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64" | "i128"
+        | "isize" => Kind::Int,
+        "f32" | "f64" => Kind::Float,
+        "String" | "str" => Kind::String,
+        "Vec" | "VecDeque" => Kind::List,
+        "BTreeMap" | "HashMap" => Kind::Map,
+        "Cid" | "Link" => Kind::Link,
+        "Box" | "Arc" | "Rc" => match arg? {
+            syn::Type::Slice(slice) => match infer_kind(&slice.elem)? {
+                Kind::Int => Kind::Bytes,
+                _ => return None,
+            },
+            ty => return infer_kind(ty),
+        },
+        _ => return None,
+    })
 }
 
 fn parse_struct(v: &VariantInfo, generics: Option<syn::Generics>) -> Struct {
     let repr = parse_struct_repr(v.ast().attrs);
-    let mut fields: Vec<_> = v
+    let rename_all = parse_rename_all(v.ast().attrs);
+    let parsed: Vec<_> = v
         .bindings()
         .iter()
         .enumerate()
-        .map(|(i, binding)| parse_field(i, binding))
+        .map(|(i, binding)| parse_field(i, binding, rename_all))
         .collect();
     let repr = repr.unwrap_or_else(|| match &v.ast().fields {
         syn::Fields::Named(_) => StructRepr::Map,
         syn::Fields::Unnamed(_) => StructRepr::Tuple,
         syn::Fields::Unit => StructRepr::Null,
     });
+    let unknown = parse_unknown(v.ast().attrs);
+    if unknown.is_some() && repr != StructRepr::Map {
+        panic!("`unknown` is only supported by the map representation");
+    }
+    let mut fields = Vec::with_capacity(parsed.len());
+    let mut flatten = None;
+    for (field, mode) in &parsed {
+        match mode {
+            FieldMode::Normal => fields.push(field.clone()),
+            FieldMode::Skip => {}
+            FieldMode::Flatten | FieldMode::Unknown => {
+                if repr != StructRepr::Map {
+                    panic!("`flatten` is only supported by the map representation");
+                }
+                if *mode == FieldMode::Unknown && unknown.as_deref() != Some("collect") {
+                    panic!("a field marked `unknown` requires `unknown = \"collect\"`");
+                }
+                if flatten.replace(field.clone()).is_some() {
+                    panic!("only one field can be flattened or collect unknown keys");
+                }
+            }
+        }
+    }
+    // A field collecting unknown keys is encoded and decoded like a flattened field, which is
+    // passed all unknown keys. The policy only applies to structs without such a field.
+    let collects = parsed.iter().any(|(_, mode)| *mode == FieldMode::Unknown);
+    let unknown = match unknown.as_deref() {
+        None => UnknownPolicy::Reject,
+        Some("collect") if collects => UnknownPolicy::Reject,
+        Some("collect") => panic!("`unknown = \"collect\"` requires a field marked `unknown`"),
+        Some(_) if flatten.is_some() => {
+            panic!("`unknown` cannot be combined with a flattened field")
+        }
+        Some("reject") => UnknownPolicy::Reject,
+        Some("ignore") => UnknownPolicy::Ignore,
+        Some(policy) => panic!("unknown policy {}", policy),
+    };
+    if repr == StructRepr::Value && fields.len() != 1 {
+        panic!("the value representation requires exactly one field");
+    }
+    let mut bound = v.clone();
+    bound.filter(|binding| {
+        parsed
+            .iter()
+            .any(|(field, mode)| field.binding == binding.binding && *mode != FieldMode::Skip)
+    });
+    let kind = parse_kind(v.ast().attrs).or_else(|| match repr {
+        StructRepr::Map => Some(Kind::Map),
+        StructRepr::Tuple => Some(Kind::List),
+        StructRepr::Null => Some(Kind::Null),
+        StructRepr::Value if fields.first()?.list_pairs => Some(Kind::List),
+        StructRepr::Value => infer_kind(&bound.bindings().first()?.ast().ty),
+        StructRepr::StringJoin { .. } | StructRepr::StringPairs { .. } => Some(Kind::String),
+        StructRepr::ListPairs => Some(Kind::List),
+    });
     if repr == StructRepr::Map {
         fields.sort_by(|f1, f2| match (&f1.name, &f2.name) {
             (syn::Member::Named(ident1), syn::Member::Named(ident2)) => {
@@ -84,22 +283,36 @@ fn parse_struct(v: &VariantInfo, generics: Option<syn::Generics>) -> Struct {
             _ => unreachable!(),
         });
     }
+    let construct = v.construct(|_, i| match &parsed[i] {
+        (field, FieldMode::Skip) => match field.default.as_ref() {
+            Some(default) => quote!(#default),
+            None => quote!(Default::default()),
+        },
+        (field, _) => {
+            let binding = &field.binding;
+            quote!(#binding)
+        }
+    });
+    let mut moved = bound.clone();
+    moved.bind_with(|_| BindStyle::Move);
     Struct {
         name: v.ast().ident.clone(),
         generics,
         rename: None,
         fields,
+        flatten: flatten.map(Box::new),
+        unknown,
         repr,
-        pat: TokenStreamEq(v.pat()),
-        construct: TokenStreamEq(v.construct(|_, i| {
-            let binding = &v.bindings()[i];
-            quote!(#binding)
-        })),
+        kind,
+        pat: TokenStreamEq(bound.pat()),
+        pat_move: TokenStreamEq(moved.pat()),
+        construct: TokenStreamEq(construct),
     }
 }
 
 fn parse_union(s: &Structure) -> Union {
     let repr = parse_union_repr(&s.ast().attrs);
+    let rename_all = parse_rename_all(&s.ast().attrs);
     Union {
         name: s.ast().ident.clone(),
         generics: s.ast().generics.clone(),
@@ -113,6 +326,9 @@ fn parse_union(s: &Structure) -> Union {
                         s.rename = Some(attr.value.value());
                     }
                 }
+                if let (None, Some(rule)) = (&s.rename, rename_all) {
+                    s.rename = Some(rule.apply_to_variant(&s.name.unraw().to_string()));
+                }
                 s
             })
             .collect(),
@@ -120,7 +336,20 @@ fn parse_union(s: &Structure) -> Union {
     }
 }
 
-fn parse_field(i: usize, b: &BindingInfo) -> StructField {
+/// How a field takes part in the representation of its struct.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum FieldMode {
+    Normal,
+    Skip,
+    Flatten,
+    Unknown,
+}
+
+fn parse_field(
+    i: usize,
+    b: &BindingInfo,
+    rename_all: Option<RenameRule>,
+) -> (StructField, FieldMode) {
     let mut field = StructField {
         name: match b.ast().ident.as_ref() {
             Some(ident) => syn::Member::Named(ident.clone()),
@@ -131,15 +360,42 @@ fn parse_field(i: usize, b: &BindingInfo) -> StructField {
         },
         rename: None,
         default: None,
+        list_pairs: false,
         binding: b.binding.clone(),
     };
+    let mut mode = FieldMode::Normal;
+    let mut optional = false;
     for attr in parse_attrs::<FieldAttr>(&b.ast().attrs) {
         match attr {
             FieldAttr::Rename(attr) => field.rename = Some(attr.value.value()),
             FieldAttr::Default(attr) => field.default = Some(attr.value),
+            FieldAttr::Repr(attr) => match attr.value.value().as_str() {
+                "map" => field.list_pairs = false,
+                "listpairs" => field.list_pairs = true,
+                repr => panic!("unknown map representation {}", repr),
+            },
+            FieldAttr::Skip => mode = FieldMode::Skip,
+            FieldAttr::Optional => optional = true,
+            FieldAttr::Flatten => mode = FieldMode::Flatten,
+            FieldAttr::Unknown => mode = FieldMode::Unknown,
+        }
+    }
+    if optional {
+        if field.default.is_some() {
+            panic!("`optional` cannot be combined with `default`");
         }
+        field.default = Some(Box::new(syn::parse_quote!(None)));
     }
-    field
+    if mode != FieldMode::Normal
+        && mode != FieldMode::Skip
+        && (field.rename.is_some() || field.default.is_some() || field.list_pairs)
+    {
+        panic!("`flatten` and `unknown` cannot be combined with other field attributes");
+    }
+    if let (None, Some(rule), Some(ident)) = (&field.rename, rename_all, b.ast().ident.as_ref()) {
+        field.rename = Some(rule.apply_to_field(&ident.unraw().to_string()));
+    }
+    (field, mode)
 }
 
 #[cfg(test)]
@@ -184,10 +440,15 @@ pub mod tests {
                     name: syn::Member::Named(format_ident!("field")),
                     rename: Some("other".to_string()),
                     default: Some(syn::parse2(quote!(false)).unwrap()),
+                    list_pairs: false,
                     binding: format_ident!("__binding_0"),
                 }],
+                flatten: None,
+                unknown: UnknownPolicy::Reject,
                 repr: StructRepr::Map,
+                kind: Some(Kind::Map),
                 pat: TokenStreamEq(quote! { Map { field: ref __binding_0, }}),
+                pat_move: TokenStreamEq(quote! { Map { field: __binding_0, }}),
                 construct: TokenStreamEq(quote! { Map { field: __binding_0, }}),
             })
         );
@@ -211,10 +472,15 @@ pub mod tests {
                     name: syn::Member::Unnamed(format_index!(0)),
                     rename: None,
                     default: None,
+                    list_pairs: false,
                     binding: format_ident!("__binding_0"),
                 }],
+                flatten: None,
+                unknown: UnknownPolicy::Reject,
                 repr: StructRepr::Tuple,
+                kind: Some(Kind::List),
                 pat: TokenStreamEq(quote! { Tuple(ref __binding_0,) }),
+                pat_move: TokenStreamEq(quote! { Tuple(__binding_0,) }),
                 construct: TokenStreamEq(quote! { Tuple(__binding_0,) }),
             })
         );
@@ -234,8 +500,12 @@ pub mod tests {
                 generics: Some(Default::default()),
                 rename: None,
                 fields: Default::default(),
+                flatten: None,
+                unknown: UnknownPolicy::Reject,
                 repr: StructRepr::Null,
+                kind: Some(Kind::Null),
                 pat: TokenStreamEq(quote!(Map)),
+                pat_move: TokenStreamEq(quote!(Map)),
                 construct: TokenStreamEq(quote!(Map)),
             })
         );
@@ -264,8 +534,12 @@ pub mod tests {
                         generics: None,
                         rename: Some("unit".into()),
                         fields: vec![],
+                        flatten: None,
+                        unknown: UnknownPolicy::Reject,
                         repr: StructRepr::Null,
+                        kind: Some(Kind::Null),
                         pat: TokenStreamEq(quote!(Union::Unit)),
+                        pat_move: TokenStreamEq(quote!(Union::Unit)),
                         construct: TokenStreamEq(quote!(Union::Unit)),
                     },
                     Struct {
@@ -276,10 +550,15 @@ pub mod tests {
                             name: syn::Member::Unnamed(format_index!(0)),
                             rename: None,
                             default: None,
+                            list_pairs: false,
                             binding: format_ident!("__binding_0"),
                         }],
+                        flatten: None,
+                        unknown: UnknownPolicy::Reject,
                         repr: StructRepr::Tuple,
+                        kind: Some(Kind::List),
                         pat: TokenStreamEq(quote! { Union::Tuple(ref __binding_0,) }),
+                        pat_move: TokenStreamEq(quote! { Union::Tuple(__binding_0,) }),
                         construct: TokenStreamEq(quote! { Union::Tuple(__binding_0,) }),
                     },
                     Struct {
@@ -290,10 +569,15 @@ pub mod tests {
                             name: syn::Member::Named(format_ident!("value")),
                             rename: None,
                             default: None,
+                            list_pairs: false,
                             binding: format_ident!("__binding_0"),
                         }],
+                        flatten: None,
+                        unknown: UnknownPolicy::Reject,
                         repr: StructRepr::Map,
+                        kind: Some(Kind::Map),
                         pat: TokenStreamEq(quote! { Union::Struct { value: ref __binding_0, } }),
+                        pat_move: TokenStreamEq(quote! { Union::Struct { value: __binding_0, } }),
                         construct: TokenStreamEq(quote! { Union::Struct { value: __binding_0, } }),
                     }
                 ],
@@ -323,12 +607,105 @@ pub mod tests {
                     generics: None,
                     rename: Some("test".into()),
                     fields: vec![],
+                    flatten: None,
+                    unknown: UnknownPolicy::Reject,
                     repr: StructRepr::Null,
+                    kind: Some(Kind::Null),
                     pat: TokenStreamEq(quote!(Enum::Variant)),
+                    pat_move: TokenStreamEq(quote!(Enum::Variant)),
                     construct: TokenStreamEq(quote!(Enum::Variant)),
                 }],
                 repr: UnionRepr::String,
             })
         );
     }
+
+    #[test]
+    fn test_struct_repr_pairs() {
+        let ast = ast(quote! {
+            #[derive(DagCbor)]
+            #[ipld(repr = "stringpairs", inner_delim = "=")]
+            #[ipld(entry_delim = "&")]
+            struct Pairs {
+                #[ipld(repr = "listpairs")]
+                field: BTreeMap<u32, bool>,
+            }
+        });
+        let s = match ast {
+            SchemaType::Struct(s) => s,
+            SchemaType::Union(_) => unreachable!(),
+        };
+        assert_eq!(
+            s.repr,
+            StructRepr::StringPairs {
+                inner_delim: "=".into(),
+                entry_delim: "&".into()
+            }
+        );
+        assert_eq!(s.kind, Some(Kind::String));
+        assert!(s.fields[0].list_pairs);
+    }
+
+    #[test]
+    fn test_struct_skip_transparent() {
+        let ast = ast(quote! {
+            #[derive(DagCbor)]
+            #[ipld(transparent)]
+            struct Wrapper(#[ipld(skip, default = 1)] u8, String);
+        });
+        let s = match ast {
+            SchemaType::Struct(s) => s,
+            SchemaType::Union(_) => unreachable!(),
+        };
+        assert_eq!(s.repr, StructRepr::Value);
+        assert_eq!(s.kind, Some(Kind::String));
+        assert_eq!(s.fields.len(), 1);
+        assert_eq!(s.fields[0].binding, format_ident!("__binding_1"));
+        assert_eq!(
+            s.pat,
+            TokenStreamEq(quote! { Wrapper(_, ref __binding_1,) })
+        );
+        assert_eq!(
+            s.construct,
+            TokenStreamEq(quote! { Wrapper(1, __binding_1,) })
+        );
+    }
+
+    #[test]
+    fn test_union_repr_keys() {
+        let repr = |ts: TokenStream| match ast(ts) {
+            SchemaType::Union(u) => u.repr,
+            SchemaType::Struct(_) => unreachable!(),
+        };
+        assert_eq!(
+            repr(quote! {
+                #[ipld(repr = "inline")]
+                enum Enum { Variant }
+            }),
+            UnionRepr::Inline { tag: "type".into() }
+        );
+        assert_eq!(
+            repr(quote! {
+                #[ipld(repr = "envelope", tag = "kind")]
+                #[ipld(content = "data")]
+                enum Enum { Variant }
+            }),
+            UnionRepr::Envelope {
+                tag: "kind".into(),
+                content: "data".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_infer_kind() {
+        let kind = |ty: TokenStream| infer_kind(&syn::parse2(ty).unwrap());
+        assert_eq!(kind(quote!(u32)), Some(Kind::Int));
+        assert_eq!(kind(quote!(&'a str)), Some(Kind::String));
+        assert_eq!(kind(quote!(Box<[u8]>)), Some(Kind::Bytes));
+        assert_eq!(kind(quote!(std::sync::Arc<Vec<u8>>)), Some(Kind::List));
+        assert_eq!(kind(quote!(Link<Foo>)), Some(Kind::Link));
+        assert_eq!(kind(quote!(Option<bool>)), None);
+        assert_eq!(kind(quote!(Foo)), None);
+    }
 }
@@ -0,0 +1,261 @@
+use libipld::error::{DecodeLocation, InvalidLength, TypeError, UnknownKey, UnknownVariant};
+use libipld::{ipld, Cid, IntoIpld, Ipld, TryFromIpld};
+use std::collections::BTreeMap;
+
+fn assert_convert<T>(data: T, ipld: Ipld)
+where
+    T: Clone + std::fmt::Debug + PartialEq + TryFrom<Ipld, Error = libipld::error::Error>,
+    Ipld: From<T>,
+{
+    assert_eq!(Ipld::from(data.clone()), ipld);
+    assert_eq!(T::try_from(ipld).unwrap(), data);
+}
+
+#[derive(Clone, Debug, IntoIpld, PartialEq, TryFromIpld)]
+pub struct Map {
+    #[ipld(rename = "b")]
+    boolean: bool,
+    #[ipld(default = 0)]
+    int: u32,
+    list: Vec<Option<String>>,
+    map: BTreeMap<String, Tuple>,
+    link: Option<Cid>,
+}
+
+#[derive(Clone, Debug, IntoIpld, PartialEq, TryFromIpld)]
+#[ipld(repr = "tuple")]
+pub struct Tuple(bool, Ipld);
+
+#[derive(Clone, Debug, IntoIpld, PartialEq, TryFromIpld)]
+#[ipld(repr = "value")]
+pub struct Value(u8);
+
+#[derive(Clone, Debug, IntoIpld, PartialEq, TryFromIpld)]
+pub struct Null;
+
+#[derive(Clone, Debug, IntoIpld, PartialEq, TryFromIpld)]
+#[ipld(repr = "stringpairs", inner_delim = ":", entry_delim = ";")]
+pub struct StringPairs {
+    a: bool,
+    #[ipld(default = 1)]
+    b: i8,
+}
+
+#[derive(Clone, Debug, IntoIpld, PartialEq, TryFromIpld)]
+#[ipld(repr = "listpairs")]
+pub struct ListPairs {
+    #[ipld(repr = "listpairs")]
+    map: BTreeMap<u8, String>,
+    #[ipld(default = 0)]
+    int: u32,
+}
+
+#[test]
+fn ipld_struct() {
+    let map = Map {
+        boolean: true,
+        int: 0,
+        list: vec![Some("a".into()), None],
+        map: BTreeMap::from([("t".into(), Tuple(false, ipld!([1])))]),
+        link: None,
+    };
+    assert_convert(
+        map.clone(),
+        ipld!({"b": true, "list": ["a", null], "map": {"t": [false, [1]]}, "link": null}),
+    );
+    assert_convert(
+        Map { int: 1, ..map },
+        ipld!({"b": true, "int": 1, "list": ["a", null], "map": {"t": [false, [1]]}, "link": null}),
+    );
+    assert_convert(Value(1), ipld!(1));
+    assert_convert(Null, ipld!(null));
+
+    let err = Map::try_from(ipld!({"b": true, "list": [], "map": {"t": [false]}, "link": null}))
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<DecodeLocation>().unwrap().path(),
+        ".map.t"
+    );
+    err.downcast::<InvalidLength>().unwrap();
+    let err = Map::try_from(ipld!({"b": true, "list": [], "map": {}})).unwrap_err();
+    err.downcast::<TypeError>().unwrap();
+    let err =
+        Map::try_from(ipld!({"b": true, "list": [], "map": {}, "link": null, "x": 1})).unwrap_err();
+    assert_eq!(err.downcast::<UnknownKey>().unwrap().key, "x");
+}
+
+#[test]
+fn ipld_struct_pairs() {
+    assert_convert(StringPairs { a: true, b: -1 }, ipld!("a:true;b:-1"));
+    assert_convert(StringPairs { a: false, b: 1 }, ipld!("a:false"));
+    let err = StringPairs::try_from(ipld!("a:yes")).unwrap_err();
+    err.downcast::<libipld::error::InvalidString>().unwrap();
+
+    let value = ListPairs {
+        map: BTreeMap::from([(1, "x".into())]),
+        int: 2,
+    };
+    assert_convert(value, ipld!([["map", [[1, "x"]]], ["int", 2]]));
+    assert_convert(
+        ListPairs {
+            map: BTreeMap::new(),
+            int: 0,
+        },
+        ipld!([["map", []]]),
+    );
+    let err = ListPairs::try_from(ipld!([["map", [[1, "x", 2]]]])).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<DecodeLocation>().unwrap().path(),
+        ".map[0]"
+    );
+    err.downcast::<InvalidLength>().unwrap();
+}
+
+#[derive(Clone, Debug, IntoIpld, PartialEq, TryFromIpld)]
+#[ipld(rename_all = "camelCase")]
+pub struct Flatten {
+    link_count: u32,
+    #[ipld(skip)]
+    cached: bool,
+    #[ipld(flatten)]
+    inner: Map,
+}
+
+#[test]
+fn ipld_struct_flatten() {
+    let value = Flatten {
+        link_count: 1,
+        cached: false,
+        inner: Map {
+            boolean: true,
+            int: 0,
+            list: vec![],
+            map: BTreeMap::new(),
+            link: None,
+        },
+    };
+    assert_convert(
+        value,
+        ipld!({"b": true, "linkCount": 1, "link": null, "list": [], "map": {}}),
+    );
+    let err = Flatten::try_from(ipld!({"linkCount": 1})).unwrap_err();
+    err.downcast::<TypeError>().unwrap();
+}
+
+#[derive(Clone, Debug, IntoIpld, PartialEq, TryFromIpld)]
+#[ipld(repr = "keyed")]
+pub enum Keyed {
+    A,
+    #[ipld(rename = "b")]
+    #[ipld(repr = "value")]
+    B(bool),
+    D(bool),
+    E {
+        boolean: bool,
+    },
+}
+
+#[derive(Clone, Debug, IntoIpld, PartialEq, TryFromIpld)]
+#[ipld(repr = "kinded")]
+pub enum Kinded {
+    A,
+    #[ipld(repr = "value")]
+    B(bool),
+    D(bool),
+    #[ipld(repr = "value")]
+    E(StringPairs),
+}
+
+#[derive(Clone, Debug, IntoIpld, PartialEq, TryFromIpld)]
+#[ipld(repr = "int-tuple")]
+pub enum IntTuple {
+    A,
+    #[ipld(repr = "value")]
+    B(bool),
+}
+
+#[derive(Clone, Copy, Debug, IntoIpld, PartialEq, TryFromIpld)]
+#[ipld(repr = "int")]
+pub enum Int {
+    Variant = 1,
+    Other = 0,
+}
+
+#[derive(Clone, Debug, IntoIpld, PartialEq, TryFromIpld)]
+#[ipld(repr = "string")]
+pub enum Str {
+    #[ipld(rename = "test")]
+    Variant,
+    Other,
+}
+
+#[derive(Clone, Debug, IntoIpld, PartialEq, TryFromIpld)]
+#[ipld(repr = "inline", tag = "kind")]
+pub enum Inline {
+    #[ipld(rename = "circle")]
+    Circle {
+        radius: u32,
+    },
+    Empty,
+}
+
+#[derive(Clone, Debug, IntoIpld, PartialEq, TryFromIpld)]
+#[ipld(repr = "envelope")]
+pub enum Envelope {
+    #[ipld(repr = "value")]
+    Name(String),
+    Empty,
+}
+
+#[test]
+fn ipld_union() {
+    assert_convert(Keyed::A, ipld!({ "A": null }));
+    assert_convert(Keyed::B(true), ipld!({ "b": true }));
+    assert_convert(Keyed::D(true), ipld!({ "D": [true] }));
+    assert_convert(
+        Keyed::E { boolean: true },
+        ipld!({ "E": { "boolean": true } }),
+    );
+    let err = Keyed::try_from(ipld!({ "C": null })).unwrap_err();
+    err.downcast::<UnknownVariant>().unwrap();
+
+    assert_convert(Kinded::A, ipld!(null));
+    assert_convert(Kinded::B(true), ipld!(true));
+    assert_convert(Kinded::D(true), ipld!([true]));
+    assert_convert(
+        Kinded::E(StringPairs { a: true, b: -1 }),
+        ipld!("a:true;b:-1"),
+    );
+    assert!(Kinded::try_from(ipld!("x")).is_err());
+
+    assert_convert(IntTuple::A, ipld!([0, null]));
+    assert_convert(IntTuple::B(false), ipld!([1, false]));
+    let err = IntTuple::try_from(ipld!([1, 2])).unwrap_err();
+    assert_eq!(err.downcast_ref::<DecodeLocation>().unwrap().path(), "[1]");
+
+    assert_convert(Int::Variant, ipld!(1));
+    assert_convert(Int::Other, ipld!(0));
+    assert_convert(Str::Variant, ipld!("test"));
+    assert_convert(Str::Other, ipld!("Other"));
+
+    assert_convert(
+        Inline::Circle { radius: 1 },
+        ipld!({ "kind": "circle", "radius": 1 }),
+    );
+    assert_convert(Inline::Empty, ipld!({ "kind": "Empty" }));
+    let err = Inline::try_from(ipld!({ "radius": 1 })).unwrap_err();
+    err.downcast::<TypeError>().unwrap();
+
+    assert_convert(
+        Envelope::Name("x".into()),
+        ipld!({ "type": "Name", "content": "x" }),
+    );
+    let err = Envelope::try_from(ipld!({ "type": "Name", "content": 1 })).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<DecodeLocation>().unwrap().path(),
+        ".content"
+    );
+}
+
+#[derive(IntoIpld, TryFromIpld)]
+pub struct Generic<T: libipld::convert::IntoIpld + libipld::convert::TryFromIpld>(T);
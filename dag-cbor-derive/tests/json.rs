@@ -0,0 +1,385 @@
+use libipld::codec::{assert_roundtrip, Codec};
+use libipld::json::{DagJson, DagJsonCodec};
+use libipld::{ipld, DagCbor, DagJson, Ipld};
+use std::collections::BTreeMap;
+
+#[derive(Clone, DagCbor, DagJson, Debug, PartialEq)]
+#[ipld(unknown = "ignore")]
+pub struct Map {
+    #[ipld(rename = "b")]
+    boolean: bool,
+    #[ipld(default = 0)]
+    int: u32,
+    list: Vec<Option<String>>,
+    ipld: Ipld,
+}
+
+#[test]
+fn json_struct_map() {
+    let map = Map {
+        boolean: true,
+        int: 1,
+        list: vec![Some("a".into()), None],
+        ipld: ipld!([1.5, 2]),
+    };
+    assert_roundtrip(
+        DagJsonCodec,
+        &map,
+        &ipld!({"b": true, "int": 1, "list": ["a", null], "ipld": [1.5, 2]}),
+    );
+    let map = Map { int: 0, ..map };
+    assert_roundtrip(
+        DagJsonCodec,
+        &map,
+        &ipld!({"b": true, "list": ["a", null], "ipld": [1.5, 2]}),
+    );
+    let json = br#"{"ipld": 3, "unknown": {"x": [1]}, "b": true, "list": []}"#;
+    let map: Map = DagJsonCodec.decode(json).unwrap();
+    assert_eq!(map.ipld, Ipld::Integer(3));
+}
+
+#[derive(Clone, Copy, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "tuple")]
+pub struct Tuple(bool, u8);
+
+#[derive(Clone, Copy, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "value")]
+pub struct Value(bool);
+
+#[derive(Clone, Copy, DagJson, Debug, Eq, PartialEq)]
+pub struct Null;
+
+#[test]
+fn json_struct_repr() {
+    assert_roundtrip(DagJsonCodec, &Tuple(true, 1), &ipld!([true, 1]));
+    assert!(DagJsonCodec.decode::<Tuple>(b"[true]").is_err());
+    assert!(DagJsonCodec.decode::<Tuple>(b"[true, 1, 2]").is_err());
+    assert_roundtrip(DagJsonCodec, &Value(true), &ipld!(true));
+    assert_roundtrip(DagJsonCodec, &Null, &ipld!(null));
+}
+
+#[derive(Clone, Copy, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "stringjoin", join = ":")]
+pub struct Range(u32, u32);
+
+#[derive(Clone, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "listpairs")]
+pub struct ListPairs {
+    name: String,
+    #[ipld(repr = "listpairs")]
+    scores: std::collections::BTreeMap<u32, bool>,
+}
+
+#[test]
+fn json_struct_pairs() {
+    assert_roundtrip(DagJsonCodec, &Range(1, 2), &ipld!("1:2"));
+    assert!(DagJsonCodec.decode::<Range>(br#""1""#).is_err());
+
+    let value = ListPairs {
+        name: "a".into(),
+        scores: [(2, true), (1, false)].into(),
+    };
+    assert_roundtrip(
+        DagJsonCodec,
+        &value,
+        &ipld!([["name", "a"], ["scores", [[1, false], [2, true]]]]),
+    );
+    let json = br#"[ ["scores", [ [1, false], [2, true] ]], ["x", {"y": 1}], ["name", "a"] ]"#;
+    assert_eq!(DagJsonCodec.decode::<ListPairs>(json).unwrap(), value);
+    assert!(DagJsonCodec
+        .decode::<ListPairs>(br#"[["name", "a"], ["scores", [[1, true], [1, false]]]]"#)
+        .is_err());
+}
+
+#[derive(Clone, DagJson, Debug, PartialEq)]
+pub struct Reject {
+    a: u32,
+}
+
+#[derive(Clone, DagJson, Debug, PartialEq)]
+#[ipld(unknown = "collect")]
+pub struct Collect {
+    a: u32,
+    #[ipld(unknown)]
+    rest: BTreeMap<String, Ipld>,
+}
+
+#[test]
+fn json_struct_unknown() {
+    let json = br#"{"a": 1, "b": {"c": [1, "x"]}}"#;
+    let err = DagJsonCodec.decode::<Reject>(json).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<libipld::json::error::UnexpectedKey>()
+            .unwrap()
+            .key,
+        "b"
+    );
+    let value = Collect {
+        a: 1,
+        rest: BTreeMap::from([("b".into(), ipld!({"c": [1, "x"]}))]),
+    };
+    assert_eq!(DagJsonCodec.decode::<Collect>(json).unwrap(), value);
+    assert_roundtrip(DagJsonCodec, &value, &ipld!({"a": 1, "b": {"c": [1, "x"]}}));
+}
+
+#[derive(Clone, DagJson, Debug, PartialEq)]
+#[ipld(rename_all = "kebab-case")]
+pub struct Flatten {
+    content_type: String,
+    #[ipld(skip)]
+    size: usize,
+    #[ipld(flatten)]
+    extra: BTreeMap<String, Ipld>,
+}
+
+#[derive(Clone, DagJson, Debug, PartialEq)]
+#[ipld(repr = "inline")]
+pub enum FlattenInline {
+    Extra {
+        #[ipld(flatten)]
+        fields: BTreeMap<String, Ipld>,
+    },
+}
+
+#[test]
+fn json_struct_flatten() {
+    let value = Flatten {
+        content_type: "text".into(),
+        size: 0,
+        extra: BTreeMap::from([("a".into(), ipld!(1)), ("z".into(), ipld!([true]))]),
+    };
+    assert_roundtrip(
+        DagJsonCodec,
+        &value,
+        &ipld!({"a": 1, "content-type": "text", "z": [true]}),
+    );
+    let json = br#"{"content-type": "text", "x": 1, "x": 2}"#;
+    assert!(DagJsonCodec.decode::<Flatten>(json).is_err());
+
+    let value = FlattenInline::Extra {
+        fields: BTreeMap::from([("a".into(), ipld!(1))]),
+    };
+    assert_roundtrip(DagJsonCodec, &value, &ipld!({"a": 1, "type": "Extra"}));
+}
+
+#[derive(Clone, Copy, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "keyed")]
+pub enum Keyed {
+    A,
+    #[ipld(rename = "b")]
+    #[ipld(repr = "value")]
+    B(bool),
+    D(bool),
+    E {
+        boolean: bool,
+    },
+}
+
+#[derive(Clone, Copy, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "kinded")]
+pub enum Kinded {
+    A,
+    #[ipld(repr = "value")]
+    B(bool),
+    #[ipld(repr = "value")]
+    C {
+        n: u32,
+    },
+    D(bool),
+    E {
+        boolean: bool,
+    },
+}
+
+#[derive(Clone, Copy, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "int-tuple")]
+pub enum IntTuple {
+    A,
+    #[ipld(repr = "value")]
+    B(bool),
+    E {
+        boolean: bool,
+    },
+}
+
+#[derive(Clone, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "inline")]
+pub enum Inline {
+    #[ipld(rename = "circle")]
+    Circle {
+        radius: u32,
+    },
+    Empty,
+}
+
+#[derive(Clone, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "envelope", tag = "kind", content = "data")]
+pub enum Envelope {
+    #[ipld(rename = "circle")]
+    Circle {
+        radius: u32,
+    },
+    Empty,
+}
+
+#[test]
+fn json_union() {
+    assert_roundtrip(DagJsonCodec, &Keyed::A, &ipld!({ "A": null }));
+    assert_roundtrip(DagJsonCodec, &Keyed::B(true), &ipld!({"b": true}));
+    assert_roundtrip(DagJsonCodec, &Keyed::D(true), &ipld!({"D": [true]}));
+    assert_roundtrip(
+        DagJsonCodec,
+        &Keyed::E { boolean: true },
+        &ipld!({"E": { "boolean": true }}),
+    );
+    assert!(DagJsonCodec
+        .decode::<Keyed>(br#"{"A": null, "D": [true]}"#)
+        .is_err());
+
+    assert_roundtrip(DagJsonCodec, &Kinded::A, &ipld!(null));
+    assert_roundtrip(DagJsonCodec, &Kinded::B(false), &ipld!(false));
+    assert_roundtrip(DagJsonCodec, &Kinded::C { n: 1 }, &ipld!(1));
+    assert_roundtrip(DagJsonCodec, &Kinded::D(true), &ipld!([true]));
+    assert_roundtrip(
+        DagJsonCodec,
+        &Kinded::E { boolean: true },
+        &ipld!({ "boolean": true }),
+    );
+
+    assert_roundtrip(DagJsonCodec, &IntTuple::A, &ipld!([0, null]));
+    assert_roundtrip(DagJsonCodec, &IntTuple::B(true), &ipld!([1, true]));
+    assert_roundtrip(
+        DagJsonCodec,
+        &IntTuple::E { boolean: true },
+        &ipld!([2, { "boolean": true }]),
+    );
+
+    assert_roundtrip(
+        DagJsonCodec,
+        &Inline::Circle { radius: 1 },
+        &ipld!({ "radius": 1, "type": "circle" }),
+    );
+    assert_roundtrip(DagJsonCodec, &Inline::Empty, &ipld!({ "type": "Empty" }));
+    assert_eq!(
+        DagJsonCodec
+            .decode::<Inline>(br#"{"type": "circle", "radius": 2}"#)
+            .unwrap(),
+        Inline::Circle { radius: 2 }
+    );
+
+    assert_roundtrip(
+        DagJsonCodec,
+        &Envelope::Circle { radius: 1 },
+        &ipld!({ "data": { "radius": 1 }, "kind": "circle" }),
+    );
+    assert_roundtrip(
+        DagJsonCodec,
+        &Envelope::Empty,
+        &ipld!({ "data": null, "kind": "Empty" }),
+    );
+    assert_eq!(
+        DagJsonCodec
+            .decode::<Vec<Envelope>>(
+                br#"[{"kind": "circle", "data": {"radius": 2}}, {"data": null, "kind": "Empty"}]"#
+            )
+            .unwrap(),
+        vec![Envelope::Circle { radius: 2 }, Envelope::Empty]
+    );
+    assert!(DagJsonCodec
+        .decode::<Envelope>(br#"{"kind": "circle"}"#)
+        .is_err());
+}
+
+#[derive(Clone, DagCbor, DagJson, Debug, PartialEq)]
+#[ipld(repr = "kinded")]
+pub enum KindedByKind {
+    #[ipld(repr = "value")]
+    Int(u32),
+    #[ipld(repr = "value")]
+    Float(f64),
+    #[ipld(repr = "value")]
+    Bytes(Box<[u8]>),
+    #[ipld(repr = "value")]
+    Link(libipld::Cid),
+    #[ipld(repr = "value")]
+    Map(Map),
+}
+
+#[test]
+fn json_union_kinded_dispatches_on_kind() {
+    use libipld::cbor::DagCborCodec;
+
+    let cid = libipld::Cid::default();
+    let map = Map {
+        boolean: true,
+        int: 1,
+        list: vec![],
+        ipld: Ipld::Null,
+    };
+    let values = [
+        (KindedByKind::Int(1), ipld!(1)),
+        (KindedByKind::Float(1.5), ipld!(1.5)),
+        (
+            KindedByKind::Bytes(vec![1, 2].into_boxed_slice()),
+            Ipld::Bytes(vec![1, 2]),
+        ),
+        (KindedByKind::Link(cid), Ipld::Link(cid)),
+        (
+            KindedByKind::Map(map),
+            ipld!({ "b": true, "int": 1, "list": [], "ipld": null }),
+        ),
+    ];
+    for (value, ipld) in &values {
+        assert_roundtrip(DagJsonCodec, value, ipld);
+        // Both codecs pick the same variant.
+        let bytes = DagCborCodec.encode(ipld).unwrap();
+        assert_eq!(&DagCborCodec.decode::<KindedByKind>(&bytes).unwrap(), value);
+    }
+
+    // A float never decodes as the int variant, a string has no variant.
+    assert!(DagJsonCodec.decode::<KindedByKind>(b"2.0").is_ok());
+    assert!(DagJsonCodec.decode::<KindedByKind>(br#""a""#).is_err());
+    assert!(DagJsonCodec.decode::<KindedByKind>(b"[1]").is_err());
+}
+
+#[derive(Clone, Copy, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "int")]
+pub enum EnumInt {
+    Variant = 1,
+    Other = 0,
+}
+
+#[derive(Clone, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "string")]
+pub enum EnumString {
+    #[ipld(rename = "test")]
+    Variant,
+    Other,
+}
+
+#[test]
+fn json_enum() {
+    assert_roundtrip(DagJsonCodec, &EnumInt::Variant, &ipld!(1));
+    assert_roundtrip(DagJsonCodec, &EnumInt::Other, &ipld!(0));
+    assert_roundtrip(DagJsonCodec, &EnumString::Variant, &ipld!("test"));
+    assert_roundtrip(DagJsonCodec, &EnumString::Other, &ipld!("Other"));
+}
+
+#[derive(DagJson)]
+pub struct Generic<T: DagJson>(T);
+
+#[test]
+fn json_error_location() {
+    use libipld::error::DecodeLocation;
+
+    #[derive(Clone, DagJson, Debug, Eq, PartialEq)]
+    struct Outer {
+        inner: Vec<Tuple>,
+    }
+
+    let err = DagJsonCodec
+        .decode::<Outer>(br#"{"inner": [[true, 1], [false, "x"]]}"#)
+        .unwrap_err();
+    let location = err.downcast_ref::<DecodeLocation>().unwrap();
+    assert_eq!(location.path(), ".inner[1][1]");
+}
@@ -155,3 +155,294 @@ pub struct IlMap {
 
 #[derive(DagCbor)]
 pub struct Generic<T: DagCbor>(T);
+
+#[test]
+fn struct_error_location() {
+    use libipld::codec::Codec;
+    use libipld::error::DecodeLocation;
+
+    #[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+    struct Outer {
+        inner: Vec<Tuple>,
+    }
+
+    let bytes = DagCborCodec
+        .encode(&ipld!({"inner": [[true], [1]]}))
+        .unwrap();
+    let err = DagCborCodec.decode::<Outer>(&bytes).unwrap_err();
+    let location = err.downcast_ref::<DecodeLocation>().unwrap();
+    assert_eq!(location.path(), ".inner[1][0]");
+    assert_eq!(location.offset, Some(bytes.len() as u64));
+
+    #[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+    struct Entries {
+        entries: std::collections::BTreeMap<String, Tuple>,
+    }
+
+    let bytes = DagCborCodec
+        .encode(&ipld!({"entries": {"a": [true], "b": [1]}}))
+        .unwrap();
+    let err = DagCborCodec.decode::<Entries>(&bytes).unwrap_err();
+    let location = err.downcast_ref::<DecodeLocation>().unwrap();
+    assert_eq!(location.path(), ".entries.b[0]");
+    assert_eq!(location.offset, Some(bytes.len() as u64));
+}
+
+#[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq)]
+#[ipld(repr = "stringjoin", join = ".")]
+pub struct Version {
+    major: u32,
+    minor: u32,
+}
+
+#[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq)]
+#[ipld(repr = "stringpairs", inner_delim = "=", entry_delim = ",")]
+pub struct Params {
+    width: u32,
+    #[ipld(rename = "h")]
+    #[ipld(default = 0)]
+    height: u32,
+}
+
+#[test]
+fn struct_string_reprs() {
+    use libipld::codec::Codec;
+    use libipld::error::InvalidString;
+
+    assert_roundtrip(DagCborCodec, &Version { major: 1, minor: 2 }, &ipld!("1.2"));
+    for invalid in ["1", "1.2.3", "1.x"] {
+        let bytes = DagCborCodec.encode(&invalid.to_string()).unwrap();
+        let err = DagCborCodec.decode::<Version>(&bytes).unwrap_err();
+        assert_eq!(err.downcast::<InvalidString>().unwrap().value, invalid);
+    }
+
+    assert_roundtrip(
+        DagCborCodec,
+        &Params {
+            width: 1,
+            height: 2,
+        },
+        &ipld!("width=1,h=2"),
+    );
+    assert_roundtrip(
+        DagCborCodec,
+        &Params {
+            width: 1,
+            height: 0,
+        },
+        &ipld!("width=1"),
+    );
+    let bytes = DagCborCodec.encode(&"h=2,width=1,d=3".to_string()).unwrap();
+    assert_eq!(
+        DagCborCodec.decode::<Params>(&bytes).unwrap(),
+        Params {
+            width: 1,
+            height: 2
+        }
+    );
+    for invalid in ["", "h=2", "width"] {
+        let bytes = DagCborCodec.encode(&invalid.to_string()).unwrap();
+        let err = DagCborCodec.decode::<Params>(&bytes).unwrap_err();
+        err.downcast::<InvalidString>().unwrap();
+    }
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+#[ipld(repr = "listpairs")]
+pub struct ListPairs {
+    name: String,
+    #[ipld(default = None)]
+    age: Option<u32>,
+    #[ipld(repr = "listpairs")]
+    scores: std::collections::BTreeMap<u32, bool>,
+}
+
+#[test]
+fn struct_list_pairs() {
+    use libipld::codec::Codec;
+    use libipld::error::DecodeLocation;
+
+    let value = ListPairs {
+        name: "a".into(),
+        age: Some(3),
+        scores: [(2, true), (1, false)].into(),
+    };
+    assert_roundtrip(
+        DagCborCodec,
+        &value,
+        &ipld!([
+            ["name", "a"],
+            ["age", 3],
+            ["scores", [[1, false], [2, true]]]
+        ]),
+    );
+    let value = ListPairs {
+        age: None,
+        scores: Default::default(),
+        ..value
+    };
+    assert_roundtrip(
+        DagCborCodec,
+        &value,
+        &ipld!([["name", "a"], ["scores", []]]),
+    );
+
+    let bytes = DagCborCodec
+        .encode(&ipld!([
+            ["scores", [[1, true]]],
+            ["extra", {}],
+            ["name", "b"]
+        ]))
+        .unwrap();
+    assert_eq!(
+        DagCborCodec.decode::<ListPairs>(&bytes).unwrap(),
+        ListPairs {
+            name: "b".into(),
+            age: None,
+            scores: [(1, true)].into(),
+        }
+    );
+    let bytes = DagCborCodec
+        .encode(&ipld!([["name", "a"], ["scores", [[1, true], [2]]]]))
+        .unwrap();
+    let err = DagCborCodec.decode::<ListPairs>(&bytes).unwrap_err();
+    let location = err.downcast_ref::<DecodeLocation>().unwrap();
+    assert_eq!(location.path(), ".scores[1]");
+}
+
+#[derive(Clone, DagCbor, Debug, PartialEq)]
+#[ipld(rename_all = "camelCase")]
+pub struct Attrs {
+    created_at: u64,
+    #[ipld(optional)]
+    updated_at: Option<u64>,
+    #[ipld(rename = "by")]
+    created_by: String,
+    #[ipld(skip)]
+    cached: Option<String>,
+    #[ipld(skip, default = 7)]
+    version: u32,
+}
+
+#[test]
+fn struct_field_attrs() {
+    use libipld::codec::Codec;
+
+    let value = Attrs {
+        created_at: 1,
+        updated_at: Some(2),
+        created_by: "a".into(),
+        cached: None,
+        version: 7,
+    };
+    assert_roundtrip(
+        DagCborCodec,
+        &value,
+        &ipld!({"by": "a", "createdAt": 1, "updatedAt": 2}),
+    );
+    let value = Attrs {
+        updated_at: None,
+        ..value
+    };
+    assert_roundtrip(DagCborCodec, &value, &ipld!({"by": "a", "createdAt": 1}));
+    let value = Attrs {
+        cached: Some("x".into()),
+        version: 1,
+        ..value
+    };
+    let bytes = DagCborCodec.encode(&value).unwrap();
+    assert_eq!(
+        DagCborCodec.decode::<Attrs>(&bytes).unwrap(),
+        Attrs {
+            cached: None,
+            version: 7,
+            ..value
+        }
+    );
+}
+
+#[derive(Clone, DagCbor, Debug, PartialEq)]
+#[ipld(transparent)]
+pub struct Transparent(String, #[ipld(skip)] std::marker::PhantomData<u8>);
+
+#[derive(Clone, DagCbor, Debug, PartialEq)]
+pub struct Flatten {
+    id: u32,
+    #[ipld(flatten)]
+    inner: Rename,
+    #[ipld(default = None)]
+    note: Option<String>,
+}
+
+#[test]
+fn struct_transparent_flatten() {
+    use libipld::codec::Codec;
+
+    assert_roundtrip(
+        DagCborCodec,
+        &Transparent("a".into(), Default::default()),
+        &ipld!("a"),
+    );
+
+    let value = Flatten {
+        id: 1,
+        inner: Rename { boolean: true },
+        note: None,
+    };
+    assert_roundtrip(DagCborCodec, &value, &ipld!({"id": 1, "bool": true}));
+    let value = Flatten {
+        note: Some("n".into()),
+        ..value
+    };
+    assert_roundtrip(
+        DagCborCodec,
+        &value,
+        &ipld!({"id": 1, "bool": true, "note": "n"}),
+    );
+
+    let bytes = DagCborCodec.encode(&ipld!({"id": 1})).unwrap();
+    assert!(DagCborCodec.decode::<Flatten>(&bytes).is_err());
+}
+
+#[derive(Clone, DagCbor, Debug, PartialEq)]
+#[ipld(unknown = "ignore")]
+pub struct IgnoreUnknown {
+    boolean: bool,
+}
+
+#[derive(Clone, DagCbor, Debug, PartialEq)]
+#[ipld(unknown = "collect")]
+pub struct CollectUnknown {
+    boolean: bool,
+    #[ipld(unknown)]
+    rest: std::collections::BTreeMap<String, libipld::Ipld>,
+}
+
+#[test]
+fn struct_unknown() {
+    use libipld::cbor::error::UnexpectedKey;
+    use libipld::codec::Codec;
+
+    let bytes = DagCborCodec
+        .encode(&ipld!({"boolean": true, "extra": {"list": [1, "x"]}}))
+        .unwrap();
+    let err = DagCborCodec.decode::<Map>(&bytes).unwrap_err();
+    assert_eq!(err.downcast_ref::<UnexpectedKey>().unwrap().key, "extra");
+    assert_eq!(
+        DagCborCodec.decode::<IgnoreUnknown>(&bytes).unwrap(),
+        IgnoreUnknown { boolean: true }
+    );
+    let value = CollectUnknown {
+        boolean: true,
+        rest: [("extra".to_string(), ipld!({"list": [1, "x"]}))].into(),
+    };
+    assert_eq!(
+        DagCborCodec.decode::<CollectUnknown>(&bytes).unwrap(),
+        value
+    );
+    assert_roundtrip(
+        DagCborCodec,
+        &value,
+        &ipld!({"boolean": true, "extra": {"list": [1, "x"]}}),
+    );
+}
@@ -1,6 +1,6 @@
 use libipld::cbor::DagCborCodec;
-use libipld::codec::assert_roundtrip;
-use libipld::{ipld, DagCbor};
+use libipld::codec::{assert_roundtrip, Codec};
+use libipld::{ipld, DagCbor, Ipld};
 
 #[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq)]
 #[ipld(repr = "keyed")]
@@ -64,6 +64,75 @@ fn union_kinded() {
     );
 }
 
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+pub struct Superset {
+    a: u32,
+    b: u32,
+}
+
+#[derive(Clone, DagCbor, Debug, PartialEq)]
+#[ipld(repr = "kinded")]
+pub enum KindedByKind {
+    #[ipld(repr = "value")]
+    #[ipld(kind = "map")]
+    Map(Superset),
+    #[ipld(repr = "value")]
+    Bytes(Box<[u8]>),
+    #[ipld(repr = "value")]
+    Float(f64),
+    List(u32),
+}
+
+#[test]
+fn union_kinded_dispatches_on_kind() {
+    assert_roundtrip(
+        DagCborCodec,
+        &KindedByKind::Map(Superset { a: 1, b: 2 }),
+        &ipld!({ "a": 1, "b": 2 }),
+    );
+    assert_roundtrip(
+        DagCborCodec,
+        &KindedByKind::Bytes(vec![1, 2].into_boxed_slice()),
+        &Ipld::Bytes(vec![1, 2]),
+    );
+    assert_roundtrip(DagCborCodec, &KindedByKind::Float(1.5), &ipld!(1.5));
+    assert_roundtrip(DagCborCodec, &KindedByKind::List(1), &ipld!([1]));
+
+    // A map only ever decodes as the map variant.
+    let bytes = DagCborCodec.encode(&ipld!({ "a": 1 })).unwrap();
+    assert!(DagCborCodec.decode::<KindedByKind>(&bytes).is_err());
+    let bytes = DagCborCodec.encode(&ipld!("string")).unwrap();
+    assert!(DagCborCodec.decode::<KindedByKind>(&bytes).is_err());
+}
+
+#[derive(Clone, DagCbor, Debug, PartialEq)]
+#[ipld(repr = "kinded")]
+pub enum KindedFallback {
+    #[ipld(repr = "value")]
+    Int(u32),
+    #[ipld(repr = "value")]
+    Kinded(Kinded),
+}
+
+#[test]
+fn union_kinded_falls_back_without_kind() {
+    assert_roundtrip(DagCborCodec, &KindedFallback::Int(1), &ipld!(1));
+    // Kinds that no other variant has are decoded by the variant without a kind.
+    assert_roundtrip(
+        DagCborCodec,
+        &KindedFallback::Kinded(Kinded::E { boolean: true }),
+        &ipld!({ "boolean": true }),
+    );
+    assert_roundtrip(
+        DagCborCodec,
+        &KindedFallback::Kinded(Kinded::D(true)),
+        &ipld!([true]),
+    );
+
+    let bytes = DagCborCodec.encode(&ipld!("string")).unwrap();
+    assert!(DagCborCodec.decode::<KindedFallback>(&bytes).is_err());
+}
+
 #[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq)]
 #[ipld(repr = "int-tuple")]
 pub enum IntTuple {
@@ -94,3 +163,107 @@ fn union_int_tuple() {
         &ipld!([4, { "boolean": true }]),
     );
 }
+
+#[derive(Clone, DagCbor, Debug, PartialEq)]
+#[ipld(repr = "inline", tag = "type")]
+pub enum Inline {
+    #[ipld(rename = "circle")]
+    Circle {
+        radius: u32,
+    },
+    #[ipld(rename = "rect")]
+    Rect {
+        w: u32,
+        height: u32,
+    },
+    Empty,
+}
+
+#[test]
+fn union_inline() {
+    assert_roundtrip(
+        DagCborCodec,
+        &Inline::Circle { radius: 1 },
+        &ipld!({ "type": "circle", "radius": 1 }),
+    );
+    assert_roundtrip(
+        DagCborCodec,
+        &Inline::Rect { w: 1, height: 2 },
+        &ipld!({ "w": 1, "type": "rect", "height": 2 }),
+    );
+    assert_roundtrip(DagCborCodec, &Inline::Empty, &ipld!({ "type": "Empty" }));
+
+    let bytes = DagCborCodec
+        .encode(&ipld!({ "type": "square", "radius": 1 }))
+        .unwrap();
+    assert!(DagCborCodec.decode::<Inline>(&bytes).is_err());
+    let bytes = DagCborCodec.encode(&ipld!({ "radius": 1 })).unwrap();
+    assert!(DagCborCodec.decode::<Inline>(&bytes).is_err());
+}
+
+#[derive(Clone, DagCbor, Debug, PartialEq)]
+#[ipld(repr = "envelope", tag = "tag", content = "c")]
+pub enum Envelope {
+    #[ipld(rename = "circle")]
+    Circle {
+        radius: u32,
+    },
+    #[ipld(repr = "value")]
+    Name(String),
+    Empty,
+}
+
+#[test]
+fn union_envelope() {
+    assert_roundtrip(
+        DagCborCodec,
+        &Envelope::Circle { radius: 1 },
+        &ipld!({ "c": { "radius": 1 }, "tag": "circle" }),
+    );
+    assert_roundtrip(
+        DagCborCodec,
+        &Envelope::Name("x".into()),
+        &ipld!({ "c": "x", "tag": "Name" }),
+    );
+    assert_roundtrip(
+        DagCborCodec,
+        &Envelope::Empty,
+        &ipld!({ "c": null, "tag": "Empty" }),
+    );
+
+    let bytes = DagCborCodec
+        .encode(&ipld!({ "c": { "radius": "x" }, "tag": "circle" }))
+        .unwrap();
+    let err = DagCborCodec.decode::<Envelope>(&bytes).unwrap_err();
+    let location = err
+        .downcast_ref::<libipld::error::DecodeLocation>()
+        .unwrap();
+    assert_eq!(location.path(), ".c.radius");
+    let bytes = DagCborCodec
+        .encode(&ipld!({ "c": null, "extra": 1, "tag": "Empty" }))
+        .unwrap();
+    assert!(DagCborCodec.decode::<Envelope>(&bytes).is_err());
+}
+
+#[derive(Clone, DagCbor, Debug, PartialEq)]
+#[ipld(rename_all = "snake_case")]
+pub enum RenameAll {
+    #[ipld(rename_all = "camelCase")]
+    FirstVariant { field_name: u32 },
+    #[ipld(rename = "second")]
+    SecondVariant,
+}
+
+#[test]
+fn union_rename_all() {
+    assert_roundtrip(
+        DagCborCodec,
+        &RenameAll::FirstVariant { field_name: 1 },
+        &ipld!({ "first_variant": { "fieldName": 1 } }),
+    );
+    assert_roundtrip(
+        DagCborCodec,
+        &RenameAll::SecondVariant,
+        &ipld!({ "second": null }),
+    );
+}
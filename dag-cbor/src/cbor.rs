@@ -1,4 +1,5 @@
 //! CBOR helper types for encoding and decoding.
+use std::cmp::Ordering;
 use std::convert::TryFrom;
 
 use crate::error::UnexpectedCode;
@@ -106,3 +107,12 @@ pub enum MajorKind {
     /// Other (major type 7).
     Other = 7,
 }
+
+/// Compares map keys in canonical order.
+///
+/// CBOR RFC-7049 specifies a canonical sort order, where keys are sorted by length first. This was
+/// later revised with RFC-8949, but we need to stick to the original order to stay compatible with
+/// existing data.
+pub fn cmp_keys(a: &str, b: &str) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
@@ -1,18 +1,22 @@
 //! CBOR decoder
-use crate::cbor::{Major, MajorKind, F32, F64, FALSE, NULL, TRUE};
+use crate::cbor::{cmp_keys, Major, MajorKind, F16, F32, F64, FALSE, NULL, TRUE};
 use crate::error::{
-    DuplicateKey, InvalidCidPrefix, LengthOutOfRange, NumberNotMinimal, NumberOutOfRange,
-    UnexpectedCode, UnexpectedEof, UnknownTag,
+    DuplicateKey, FloatNotCanonical, InvalidCidPrefix, KeyNotCanonical, LengthOutOfRange, Limit,
+    LimitExceeded, NumberNotMinimal, NumberOutOfRange, UnexpectedCode, UnexpectedEof, UnknownTag,
 };
 use crate::DagCborCodec as DagCbor;
 use byteorder::{BigEndian, ByteOrder};
 use core::convert::TryFrom;
+use libipld_core::cid::Cid;
 use libipld_core::codec::{Decode, References};
-use libipld_core::error::Result;
+use libipld_core::error::{with_location, with_path_segment, Error, PathSegment, Result};
 use libipld_core::ipld::Ipld;
-use libipld_core::{cid::Cid, raw_value::SkipOne};
+use libipld_core::ipld_ref::IpldRef;
+use libipld_core::raw_value::{RawValue, SkipOne};
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::sync::Arc;
 
 /// Reads a u8 from a byte stream.
@@ -84,8 +88,10 @@ pub fn read_list<R: Read + Seek, T: Decode<DagCbor>>(r: &mut R, len: u64) -> Res
     let max_alloc = (16 * 1024) / std::mem::size_of::<T>();
 
     let mut list: Vec<T> = Vec::with_capacity(len.min(max_alloc));
-    for _ in 0..len {
-        list.push(T::decode(DagCbor, r)?);
+    for i in 0..len {
+        let value =
+            T::decode(DagCbor, r).map_err(|err| with_path_segment(err, PathSegment::Index(i)))?;
+        list.push(value);
     }
     Ok(list)
 }
@@ -98,8 +104,12 @@ pub fn read_map<R: Read + Seek, K: Decode<DagCbor> + Ord, T: Decode<DagCbor>>(
     let len = usize::try_from(len).map_err(|_| LengthOutOfRange::new::<usize>())?;
     let mut map: BTreeMap<K, T> = BTreeMap::new();
     for _ in 0..len {
+        let pos = r.stream_position()?;
         let key = K::decode(DagCbor, r)?;
-        let value = T::decode(DagCbor, r)?;
+        let value = T::decode(DagCbor, r).map_err(|err| match key_segment(r, pos) {
+            Some(segment) => with_path_segment(err, segment),
+            None => err,
+        })?;
         let prev_value = map.insert(key, value);
         if prev_value.is_some() {
             return Err(DuplicateKey.into());
@@ -108,8 +118,70 @@ pub fn read_map<R: Read + Seek, K: Decode<DagCbor> + Ord, T: Decode<DagCbor>>(
     Ok(map)
 }
 
+/// Reads the map key at `pos` as a path segment, leaving the reader where it was.
+///
+/// Only used when decoding a value failed, so that typed keys don't need to be displayable.
+fn key_segment<R: Read + Seek>(r: &mut R, pos: u64) -> Option<PathSegment> {
+    let end = r.stream_position().ok()?;
+    r.seek(SeekFrom::Start(pos)).ok()?;
+    let key = String::decode(DagCbor, r).ok();
+    r.seek(SeekFrom::Start(end)).ok()?;
+    key.map(PathSegment::Key)
+}
+
+/// Reads a map from a list of `[key, value]` pairs, the listpairs representation.
+pub fn read_list_pairs<R: Read + Seek, K: Decode<DagCbor> + Ord, T: Decode<DagCbor>>(
+    r: &mut R,
+) -> Result<BTreeMap<K, T>> {
+    let major = read_major(r)?;
+    if major.kind() != MajorKind::Array {
+        return Err(UnexpectedCode::new::<BTreeMap<K, T>>(major.into()).into());
+    }
+    let len = read_uint(r, major)?;
+    let len = usize::try_from(len).map_err(|_| LengthOutOfRange::new::<usize>())?;
+    let mut map: BTreeMap<K, T> = BTreeMap::new();
+    for i in 0..len {
+        let (key, value) =
+            read_pair(r).map_err(|err| with_path_segment(err, PathSegment::Index(i)))?;
+        if map.insert(key, value).is_some() {
+            return Err(DuplicateKey.into());
+        }
+    }
+    Ok(map)
+}
+
+fn read_pair<R: Read + Seek, K: Decode<DagCbor>, T: Decode<DagCbor>>(r: &mut R) -> Result<(K, T)> {
+    let major = read_major(r)?;
+    if major.kind() != MajorKind::Array {
+        return Err(UnexpectedCode::new::<(K, T)>(major.into()).into());
+    }
+    if read_uint(r, major)? != 2 {
+        return Err(LengthOutOfRange::new::<(K, T)>().into());
+    }
+    let key = K::decode(DagCbor, r)?;
+    let value = T::decode(DagCbor, r)?;
+    Ok((key, value))
+}
+
+/// Reads a map without decoding its values, returning the encoded value of each key.
+pub fn read_map_entries<R: Read + Seek>(r: &mut R) -> Result<Vec<(String, Vec<u8>)>> {
+    let major = read_major(r)?;
+    if major.kind() != MajorKind::Map {
+        return Err(UnexpectedCode::new::<BTreeMap<String, Ipld>>(major.into()).into());
+    }
+    let len = read_uint(r, major)?;
+    let len = usize::try_from(len).map_err(|_| LengthOutOfRange::new::<usize>())?;
+    let mut entries = Vec::with_capacity(len.min(1024));
+    for _ in 0..len {
+        let key = String::decode(DagCbor, r)?;
+        let value = RawValue::<DagCbor>::decode(DagCbor, r)?;
+        entries.push((key, value.into()));
+    }
+    Ok(entries)
+}
+
 /// Reads a cid from a stream of cbor encoded bytes.
-pub fn read_link<R: Read + Seek>(r: &mut R) -> Result<Cid> {
+pub fn read_link<R: Read>(r: &mut R) -> Result<Cid> {
     let major = read_major(r)?;
     if major.kind() != MajorKind::ByteString {
         return Err(UnexpectedCode::new::<Cid>(major.into()).into());
@@ -177,6 +249,148 @@ pub fn read_uint<R: Read>(r: &mut R, major: Major) -> Result<u64> {
     }
 }
 
+/// Checks that a single value is in canonical form.
+///
+/// A value is canonical when decoding and re-encoding it yields exactly the same bytes. On top of
+/// the checks done by the regular decoder, this rejects floats that aren't finite 64 bit floats,
+/// map keys that aren't strings and maps whose keys aren't in canonical order.
+pub fn validate_canonical<R: Read + Seek>(r: &mut R) -> Result<()> {
+    // Uses an explicit stack instead of recursion, so that deeply nested input can't overflow the
+    // call stack.
+    let mut stack = Vec::new();
+    validate_canonical_items(r, &mut stack).map_err(|err| {
+        // The stack still holds the containers enclosing the value that failed to validate.
+        let segments = stack.iter().filter_map(Frame::segment).collect();
+        with_location(err, None, segments)
+    })
+}
+
+/// A list or map on the stack of a non-recursive decoder.
+pub(crate) struct Frame {
+    /// Number of items, counting keys and values of maps separately.
+    len: u64,
+    /// Number of items that haven't been started yet.
+    pub(crate) remaining: u64,
+    map: bool,
+    /// The key of the map entry that is being decoded.
+    pub(crate) last_key: Option<String>,
+}
+
+impl Frame {
+    /// Creates a frame for a list of `len` items.
+    pub(crate) fn list(len: u64) -> Self {
+        Self {
+            len,
+            remaining: len,
+            map: false,
+            last_key: None,
+        }
+    }
+
+    /// Creates a frame for a map of `len` entries.
+    pub(crate) fn map(len: u64) -> Result<Self> {
+        let len = len
+            .checked_mul(2)
+            .ok_or_else(LengthOutOfRange::new::<Ipld>)?;
+        Ok(Self {
+            len,
+            remaining: len,
+            map: true,
+            last_key: None,
+        })
+    }
+
+    /// Returns whether the next item is a map key.
+    pub(crate) fn key_next(&self) -> bool {
+        self.map && self.remaining % 2 != 1
+    }
+
+    /// Starts the next item and returns whether it is a map key.
+    pub(crate) fn next_item(&mut self) -> bool {
+        let key = self.key_next();
+        self.remaining -= 1;
+        key
+    }
+
+    /// Returns the declared length and the number of items started, counting map entries.
+    pub(crate) fn counts(&self) -> (u64, u64) {
+        let n = if self.map { 2 } else { 1 };
+        (self.len / n, (self.len - self.remaining).div_ceil(n))
+    }
+
+    /// Returns the path segment of the item that is being decoded.
+    pub(crate) fn segment(&self) -> Option<PathSegment> {
+        if !self.map {
+            Some(PathSegment::Index((self.len - self.remaining - 1) as usize))
+        } else if self.remaining % 2 == 1 {
+            // A key is being validated.
+            None
+        } else {
+            self.last_key.clone().map(PathSegment::Key)
+        }
+    }
+}
+
+fn validate_canonical_items<R: Read + Seek>(r: &mut R, stack: &mut Vec<Frame>) -> Result<()> {
+    loop {
+        let is_key = stack.last_mut().map(Frame::next_item).unwrap_or(false);
+        let major = read_major(r)?;
+        if is_key && major.kind() != MajorKind::TextString {
+            return Err(UnexpectedCode::new::<String>(major.into()).into());
+        }
+        match major.kind() {
+            MajorKind::UnsignedInt | MajorKind::NegativeInt => {
+                read_uint(r, major)?;
+            }
+            MajorKind::ByteString => {
+                let len = read_uint(r, major)?;
+                if std::io::copy(&mut r.take(len), &mut std::io::sink())? != len {
+                    return Err(UnexpectedEof.into());
+                }
+            }
+            MajorKind::TextString => {
+                let len = read_uint(r, major)?;
+                let s = read_str(r, len)?;
+                if is_key {
+                    let frame = stack.last_mut().expect("keys are only read within maps");
+                    if let Some(prev) = &frame.last_key {
+                        match cmp_keys(prev, &s) {
+                            Ordering::Less => {}
+                            Ordering::Equal => return Err(DuplicateKey.into()),
+                            Ordering::Greater => return Err(KeyNotCanonical(s).into()),
+                        }
+                    }
+                    frame.last_key = Some(s);
+                }
+            }
+            MajorKind::Array => stack.push(Frame::list(read_uint(r, major)?)),
+            MajorKind::Map => stack.push(Frame::map(read_uint(r, major)?)?),
+            MajorKind::Tag => match read_uint(r, major)? {
+                42 => {
+                    read_link(r)?;
+                }
+                tag => return Err(UnknownTag(tag).into()),
+            },
+            MajorKind::Other => match major {
+                FALSE | TRUE | NULL => {}
+                F64 => {
+                    if !read_f64(r)?.is_finite() {
+                        return Err(NumberOutOfRange::new::<f64>().into());
+                    }
+                }
+                F16 | F32 => return Err(FloatNotCanonical.into()),
+                m => return Err(UnexpectedCode::new::<Ipld>(m.into()).into()),
+            },
+        }
+        while stack.last().map(|frame| frame.remaining == 0) == Some(true) {
+            stack.pop();
+        }
+        if stack.is_empty() {
+            return Ok(());
+        }
+    }
+}
+
 impl Decode<DagCbor> for bool {
     fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
         Ok(match read_major(r)? {
@@ -368,47 +582,248 @@ impl<K: Decode<DagCbor> + Ord, T: Decode<DagCbor>> Decode<DagCbor> for BTreeMap<
     }
 }
 
-impl Decode<DagCbor> for Ipld {
-    fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
+/// Nesting depth of lists and maps that decoding into [`Ipld`] accepts by default.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// Limits for decoding untrusted input into [`Ipld`].
+///
+/// Plain decoding only limits the nesting depth to [`DEFAULT_MAX_DEPTH`], the other limits are
+/// only applied by [`DagCborCodec::decode_with_limits`](crate::DagCborCodec::decode_with_limits).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum nesting depth of lists and maps.
+    pub max_depth: usize,
+    /// Maximum number of bytes allocated for the decoded value.
+    pub max_allocation: usize,
+    /// Maximum number of items in a list or entries in a map.
+    pub max_collection_len: usize,
+    /// Maximum length of a string or byte string.
+    pub max_string_len: usize,
+}
+
+impl Default for DecodeLimits {
+    /// Only limits the nesting depth, so that decoding can't overflow the stack.
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_allocation: usize::MAX,
+            max_collection_len: usize::MAX,
+            max_string_len: usize::MAX,
+        }
+    }
+}
+
+/// Decodes an [`Ipld`] from a stream of cbor encoded bytes, enforcing the given limits.
+pub fn read_ipld<R: Read + Seek>(r: &mut R, limits: &DecodeLimits) -> Result<Ipld> {
+    IpldDecoder {
+        limits,
+        allocated: 0,
+    }
+    .read(r, 0)
+}
+
+/// Decodes an [`IpldRef`] from a buffer of cbor encoded bytes, enforcing the given limits.
+///
+/// Strings and bytes borrow from the buffer instead of being copied.
+pub fn read_ipld_ref<'b>(r: &mut Cursor<&'b [u8]>, limits: &DecodeLimits) -> Result<IpldRef<'b>> {
+    IpldDecoder {
+        limits,
+        allocated: 0,
+    }
+    .read_ref(r, 0)
+}
+
+/// Borrows `len` bytes from the buffer of a cursor.
+fn borrow_bytes<'b>(r: &mut Cursor<&'b [u8]>, len: u64) -> Result<&'b [u8]> {
+    let buf: &'b [u8] = r.get_ref();
+    let start = r.position();
+    let end = start.checked_add(len).ok_or(UnexpectedEof)?;
+    if end > buf.len() as u64 {
+        return Err(UnexpectedEof.into());
+    }
+    r.set_position(end);
+    Ok(&buf[start as usize..end as usize])
+}
+
+struct IpldDecoder<'a> {
+    limits: &'a DecodeLimits,
+    allocated: usize,
+}
+
+impl<'a> IpldDecoder<'a> {
+    fn exceeded(limit: Limit, max: usize) -> Error {
+        LimitExceeded { limit, max }.into()
+    }
+
+    fn alloc(&mut self, size: u64) -> Result<()> {
+        let max = self.limits.max_allocation;
+        self.allocated = usize::try_from(size)
+            .ok()
+            .and_then(|size| self.allocated.checked_add(size))
+            .filter(|allocated| *allocated <= max)
+            .ok_or_else(|| Self::exceeded(Limit::Allocation, max))?;
+        Ok(())
+    }
+
+    fn string_len<R: Read>(&mut self, r: &mut R, major: Major) -> Result<u64> {
+        let len = read_uint(r, major)?;
+        let max = self.limits.max_string_len;
+        if len > max as u64 {
+            return Err(Self::exceeded(Limit::StringLength, max));
+        }
+        self.alloc(len)?;
+        Ok(len)
+    }
+
+    fn collection_len<R: Read>(&mut self, r: &mut R, major: Major, item: usize) -> Result<u64> {
+        let len = read_uint(r, major)?;
+        let max = self.limits.max_collection_len;
+        if len > max as u64 {
+            return Err(Self::exceeded(Limit::CollectionLength, max));
+        }
+        self.alloc(len.saturating_mul(item as u64))?;
+        Ok(len)
+    }
+
+    fn enter(&self, depth: usize) -> Result<usize> {
+        let max = self.limits.max_depth;
+        if depth >= max {
+            return Err(Self::exceeded(Limit::Depth, max));
+        }
+        Ok(depth + 1)
+    }
+
+    fn read<R: Read + Seek>(&mut self, r: &mut R, depth: usize) -> Result<Ipld> {
         let major = read_major(r)?;
         let ipld = match major.kind() {
-            MajorKind::UnsignedInt => Self::Integer(read_uint(r, major)? as i128),
-            MajorKind::NegativeInt => Self::Integer(-1 - read_uint(r, major)? as i128),
+            MajorKind::UnsignedInt => Ipld::Integer(read_uint(r, major)? as i128),
+            MajorKind::NegativeInt => Ipld::Integer(-1 - read_uint(r, major)? as i128),
             MajorKind::ByteString => {
-                let len = read_uint(r, major)?;
-                Self::Bytes(read_bytes(r, len)?)
+                let len = self.string_len(r, major)?;
+                Ipld::Bytes(read_bytes(r, len)?)
             }
             MajorKind::TextString => {
-                let len = read_uint(r, major)?;
-                Self::String(read_str(r, len)?)
+                let len = self.string_len(r, major)?;
+                Ipld::String(read_str(r, len)?)
             }
             MajorKind::Array => {
-                let len = read_uint(r, major)?;
-                Self::List(read_list(r, len)?)
+                let depth = self.enter(depth)?;
+                let len = self.collection_len(r, major, std::mem::size_of::<Ipld>())?;
+                let mut list = Vec::with_capacity((len as usize).min(16 * 1024));
+                for i in 0..len as usize {
+                    let value = self
+                        .read(r, depth)
+                        .map_err(|err| with_path_segment(err, PathSegment::Index(i)))?;
+                    list.push(value);
+                }
+                Ipld::List(list)
             }
             MajorKind::Map => {
-                let len = read_uint(r, major)?;
-                Self::Map(read_map(r, len)?)
+                let depth = self.enter(depth)?;
+                let item = std::mem::size_of::<(String, Ipld)>();
+                let len = self.collection_len(r, major, item)?;
+                let mut map = BTreeMap::new();
+                for _ in 0..len {
+                    let major = read_major(r)?;
+                    if major.kind() != MajorKind::TextString {
+                        return Err(UnexpectedCode::new::<String>(major.into()).into());
+                    }
+                    let len = self.string_len(r, major)?;
+                    let key = read_str(r, len)?;
+                    let value = self
+                        .read(r, depth)
+                        .map_err(|err| with_path_segment(err, PathSegment::Key(key.clone())))?;
+                    if map.insert(key, value).is_some() {
+                        return Err(DuplicateKey.into());
+                    }
+                }
+                Ipld::Map(map)
             }
             MajorKind::Tag => {
                 let value = read_uint(r, major)?;
                 if value == 42 {
-                    Self::Link(read_link(r)?)
+                    Ipld::Link(read_link(r)?)
                 } else {
                     return Err(UnknownTag(value).into());
                 }
             }
             MajorKind::Other => match major {
-                FALSE => Self::Bool(false),
-                TRUE => Self::Bool(true),
-                NULL => Self::Null,
-                F32 => Self::Float(read_f32(r)? as f64),
-                F64 => Self::Float(read_f64(r)?),
-                m => return Err(UnexpectedCode::new::<Self>(m.into()).into()),
+                FALSE => Ipld::Bool(false),
+                TRUE => Ipld::Bool(true),
+                NULL => Ipld::Null,
+                F32 => Ipld::Float(read_f32(r)? as f64),
+                F64 => Ipld::Float(read_f64(r)?),
+                m => return Err(UnexpectedCode::new::<Ipld>(m.into()).into()),
             },
         };
         Ok(ipld)
     }
+
+    fn read_ref<'b>(&mut self, r: &mut Cursor<&'b [u8]>, depth: usize) -> Result<IpldRef<'b>> {
+        let major = read_major(r)?;
+        let ipld = match major.kind() {
+            MajorKind::ByteString => {
+                let len = self.string_len(r, major)?;
+                IpldRef::Bytes(Cow::Borrowed(borrow_bytes(r, len)?))
+            }
+            MajorKind::TextString => {
+                let len = self.string_len(r, major)?;
+                IpldRef::String(Cow::Borrowed(std::str::from_utf8(borrow_bytes(r, len)?)?))
+            }
+            MajorKind::Array => {
+                let depth = self.enter(depth)?;
+                let len = self.collection_len(r, major, std::mem::size_of::<IpldRef>())?;
+                let mut list = Vec::with_capacity((len as usize).min(16 * 1024));
+                for i in 0..len as usize {
+                    let value = self
+                        .read_ref(r, depth)
+                        .map_err(|err| with_path_segment(err, PathSegment::Index(i)))?;
+                    list.push(value);
+                }
+                IpldRef::List(list)
+            }
+            MajorKind::Map => {
+                let depth = self.enter(depth)?;
+                let item = std::mem::size_of::<(Cow<str>, IpldRef)>();
+                let len = self.collection_len(r, major, item)?;
+                let mut map = BTreeMap::new();
+                for _ in 0..len {
+                    let major = read_major(r)?;
+                    if major.kind() != MajorKind::TextString {
+                        return Err(UnexpectedCode::new::<String>(major.into()).into());
+                    }
+                    let len = self.string_len(r, major)?;
+                    let key = std::str::from_utf8(borrow_bytes(r, len)?)?;
+                    let value = self
+                        .read_ref(r, depth)
+                        .map_err(|err| with_path_segment(err, PathSegment::Key(key.into())))?;
+                    if map.insert(Cow::Borrowed(key), value).is_some() {
+                        return Err(DuplicateKey.into());
+                    }
+                }
+                IpldRef::Map(map)
+            }
+            // Everything else doesn't borrow.
+            _ => {
+                r.seek(SeekFrom::Current(-1))?;
+                match self.read(r, depth)? {
+                    Ipld::Null => IpldRef::Null,
+                    Ipld::Bool(b) => IpldRef::Bool(b),
+                    Ipld::Integer(i) => IpldRef::Integer(i),
+                    Ipld::Float(f) => IpldRef::Float(f),
+                    Ipld::Link(cid) => IpldRef::Link(cid),
+                    _ => unreachable!("only scalars are left"),
+                }
+            }
+        };
+        Ok(ipld)
+    }
+}
+
+impl Decode<DagCbor> for Ipld {
+    fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
+        read_ipld(r, &DecodeLimits::default())
+    }
 }
 
 impl References<DagCbor> for Ipld {
@@ -596,8 +1011,13 @@ impl SkipOne for DagCbor {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{error::UnexpectedEof, DagCborCodec};
+    use crate::error::{
+        FloatNotCanonical, KeyNotCanonical, LimitExceeded, TrailingData, UnexpectedEof,
+    };
+    use crate::DagCborCodec;
     use libipld_core::codec::Codec;
+    use libipld_core::error::DecodeLocation;
+    use libipld_macro::ipld;
     use std::num::{
         NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroU128, NonZeroU16,
         NonZeroU32, NonZeroU64, NonZeroU8,
@@ -713,4 +1133,237 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn strict_roundtrip() -> Result<()> {
+        let ipld = ipld!({
+            "b": [1, -1, 0.5, null, true],
+            "aa": { "bytes": vec![0, 1, 2], "string": "hello" },
+            "a": [],
+        });
+        let bytes = DagCborCodec.encode(&ipld)?;
+        let ipld2: Ipld = DagCborCodec.decode_strict(&bytes)?;
+        assert_eq!(ipld, ipld2);
+        Ok(())
+    }
+
+    #[test]
+    fn strict_float() {
+        let f16 = [0xf9, 0x3c, 0x00];
+        let f32 = [0xfa, 0x3f, 0x80, 0x00, 0x00];
+        for bytes in [&f16[..], &f32[..]] {
+            DagCborCodec
+                .decode_strict::<Ipld>(bytes)
+                .unwrap_err()
+                .downcast::<FloatNotCanonical>()
+                .unwrap();
+        }
+        assert_eq!(DagCborCodec.decode::<f64>(&f32).unwrap(), 1.0);
+
+        let infinity = [0xfb, 0x7f, 0xf0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        DagCborCodec
+            .decode_strict::<Ipld>(&infinity)
+            .unwrap_err()
+            .downcast::<NumberOutOfRange>()
+            .unwrap();
+    }
+
+    #[test]
+    fn strict_key_order() {
+        // {"bb": 1, "a": 2}
+        let bytes = [0xa2, 0x62, 0x62, 0x62, 0x01, 0x61, 0x61, 0x02];
+        let err = DagCborCodec
+            .decode_strict::<Ipld>(&bytes)
+            .unwrap_err()
+            .downcast::<KeyNotCanonical>()
+            .unwrap();
+        assert_eq!(err.0, "a");
+        DagCborCodec.decode::<Ipld>(&bytes).unwrap();
+
+        // {"b": 1, "aa": 2}
+        let bytes = [0xa2, 0x61, 0x62, 0x01, 0x62, 0x61, 0x61, 0x02];
+        DagCborCodec.decode_strict::<Ipld>(&bytes).unwrap();
+
+        // {"a": 1, "a": 2}
+        let bytes = [0xa2, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02];
+        DagCborCodec
+            .decode_strict::<Ipld>(&bytes)
+            .unwrap_err()
+            .downcast::<DuplicateKey>()
+            .unwrap();
+
+        // {1: 2}
+        let bytes = [0xa1, 0x01, 0x02];
+        DagCborCodec
+            .decode_strict::<Ipld>(&bytes)
+            .unwrap_err()
+            .downcast::<UnexpectedCode>()
+            .unwrap();
+    }
+
+    #[test]
+    fn strict_trailing_data() {
+        DagCborCodec.decode::<u8>(&[0x01, 0x02]).unwrap();
+        DagCborCodec
+            .decode_strict::<u8>(&[0x01, 0x02])
+            .unwrap_err()
+            .downcast::<TrailingData>()
+            .unwrap();
+    }
+
+    fn limit_exceeded(bytes: &[u8], limits: DecodeLimits) -> LimitExceeded {
+        DagCborCodec
+            .decode_with_limits(bytes, &limits)
+            .unwrap_err()
+            .downcast::<LimitExceeded>()
+            .unwrap()
+    }
+
+    #[test]
+    fn limit_depth() {
+        // A list nested a million times.
+        let mut bytes = vec![0x81; 1_000_000];
+        bytes.push(0x01);
+        let limits = DecodeLimits {
+            max_depth: 256,
+            ..Default::default()
+        };
+        assert_eq!(limit_exceeded(&bytes, limits).limit, Limit::Depth);
+
+        // Plain decoding only limits the depth to the default.
+        let err = DagCborCodec
+            .decode::<Ipld>(&bytes)
+            .unwrap_err()
+            .downcast::<LimitExceeded>()
+            .unwrap();
+        assert_eq!(err.limit, Limit::Depth);
+        DagCborCodec
+            .decode::<Ipld>(&bytes[bytes.len() - DEFAULT_MAX_DEPTH - 1..])
+            .unwrap();
+
+        let limits = DecodeLimits {
+            max_depth: 2,
+            ..Default::default()
+        };
+        DagCborCodec
+            .decode_with_limits(&bytes[bytes.len() - 3..], &limits)
+            .unwrap();
+        let err = limit_exceeded(&bytes[bytes.len() - 4..], limits);
+        assert_eq!(err.limit, Limit::Depth);
+    }
+
+    #[test]
+    fn limit_lengths() -> Result<()> {
+        let limits = DecodeLimits {
+            max_collection_len: 2,
+            max_string_len: 3,
+            ..Default::default()
+        };
+        let bytes = DagCborCodec.encode(&ipld!({ "abc": [1, 2] }))?;
+        DagCborCodec.decode_with_limits(&bytes, &limits)?;
+
+        let bytes = DagCborCodec.encode(&ipld!([1, 2, 3]))?;
+        assert_eq!(
+            limit_exceeded(&bytes, limits).limit,
+            Limit::CollectionLength
+        );
+        let bytes = DagCborCodec.encode(&ipld!({ "abcd": 1 }))?;
+        assert_eq!(limit_exceeded(&bytes, limits).limit, Limit::StringLength);
+        let bytes = DagCborCodec.encode(&ipld!(vec![0u8; 4]))?;
+        assert_eq!(limit_exceeded(&bytes, limits).limit, Limit::StringLength);
+        Ok(())
+    }
+
+    #[test]
+    fn limit_allocation() -> Result<()> {
+        let limits = DecodeLimits {
+            max_allocation: 1024,
+            ..Default::default()
+        };
+        let bytes = DagCborCodec.encode(&ipld!("a".repeat(1000)))?;
+        DagCborCodec.decode_with_limits(&bytes, &limits)?;
+
+        let bytes = DagCborCodec.encode(&ipld!(["a".repeat(1000), "b".repeat(1000)]))?;
+        assert_eq!(limit_exceeded(&bytes, limits).limit, Limit::Allocation);
+
+        // A huge list length is rejected before anything is read.
+        let bytes = [0x9b, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        assert_eq!(limit_exceeded(&bytes, limits).limit, Limit::Allocation);
+        Ok(())
+    }
+
+    #[test]
+    fn error_location() {
+        // {"foo": [1, 1("")]}
+        let bytes = [0xa1, 0x63, 0x66, 0x6f, 0x6f, 0x82, 0x01, 0xc1, 0x60];
+        let err = DagCborCodec.decode::<Ipld>(&bytes).unwrap_err();
+        let location = err.downcast_ref::<DecodeLocation>().unwrap();
+        assert_eq!(location.path(), ".foo[1]");
+        assert_eq!(location.offset, Some(8));
+        assert_eq!(
+            err.to_string(),
+            "Failed to decode `.foo[1]` at byte 8: Unknown cbor tag `1`."
+        );
+        err.downcast::<UnknownTag>().unwrap();
+
+        // [[], [1, 1("")]]
+        let bytes = [0x82, 0x80, 0x82, 0x01, 0xc1, 0x60];
+        let err = DagCborCodec.decode::<Vec<Vec<u8>>>(&bytes).unwrap_err();
+        let location = err.downcast_ref::<DecodeLocation>().unwrap();
+        assert_eq!(location.path(), "[1][1]");
+        assert_eq!(location.offset, Some(5));
+
+        // {"a": {"bb": 1, "a": 2}}
+        let bytes = [
+            0xa1, 0x61, 0x61, 0xa2, 0x62, 0x62, 0x62, 0x01, 0x61, 0x61, 0x02,
+        ];
+        let err = DagCborCodec.decode_strict::<Ipld>(&bytes).unwrap_err();
+        let location = err.downcast_ref::<DecodeLocation>().unwrap();
+        assert_eq!(location.path(), ".a");
+        assert_eq!(location.offset, Some(10));
+        assert_eq!(
+            location.to_string(),
+            "Failed to decode `.a` at byte 10: Map key `a` is not in canonical order."
+        );
+    }
+
+    #[test]
+    fn decode_ref() -> Result<()> {
+        let ipld = ipld!({
+            "bytes": vec![0, 1, 2],
+            "list": [1, -1, 0.5, null, true, "hello"],
+        });
+        let bytes = DagCborCodec.encode(&ipld)?;
+        let ipld_ref = DagCborCodec.decode_ref(&bytes)?;
+        match ipld_ref.get("bytes")? {
+            IpldRef::Bytes(Cow::Borrowed(b)) => assert_eq!(b, &[0, 1, 2]),
+            other => panic!("expected borrowed bytes, got {:?}", other),
+        }
+        match ipld_ref.get("list")?.get(5)? {
+            IpldRef::String(Cow::Borrowed(s)) => assert_eq!(*s, "hello"),
+            other => panic!("expected borrowed string, got {:?}", other),
+        }
+        assert_eq!(ipld_ref.into_owned(), ipld);
+
+        let limits = DecodeLimits {
+            max_string_len: 2,
+            ..Default::default()
+        };
+        let err = DagCborCodec
+            .decode_ref_with_limits(&bytes, &limits)
+            .unwrap_err()
+            .downcast::<LimitExceeded>()
+            .unwrap();
+        assert_eq!(err.limit, Limit::StringLength);
+
+        let mut bytes = vec![0x81; 1_000_000];
+        bytes.push(0x01);
+        let err = DagCborCodec
+            .decode_ref(&bytes)
+            .unwrap_err()
+            .downcast::<LimitExceeded>()
+            .unwrap();
+        assert_eq!(err.limit, Limit::Depth);
+        Ok(())
+    }
 }
@@ -1,6 +1,5 @@
 //! CBOR encoder.
 
-use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::io::Write;
 use std::iter::FromIterator;
@@ -13,8 +12,8 @@ use libipld_core::codec::Encode;
 use libipld_core::error::Result;
 use libipld_core::ipld::Ipld;
 
-use crate::cbor::{MajorKind, FALSE, TRUE};
-use crate::error::NumberOutOfRange;
+use crate::cbor::{cmp_keys, MajorKind, FALSE, TRUE};
+use crate::error::{DuplicateKey, NumberOutOfRange};
 use crate::DagCborCodec as DagCbor;
 
 /// Writes a null byte to a cbor encoded byte stream.
@@ -72,6 +71,36 @@ pub fn write_u64<W: Write>(w: &mut W, major: MajorKind, value: u64) -> Result<()
     Ok(())
 }
 
+/// Writes a map as a list of `[key, value]` pairs, the listpairs representation.
+pub fn write_list_pairs<W: Write, K: Encode<DagCbor>, T: Encode<DagCbor>>(
+    w: &mut W,
+    map: &BTreeMap<K, T>,
+) -> Result<()> {
+    write_u64(w, MajorKind::Array, map.len() as u64)?;
+    for (key, value) in map {
+        write_u8(w, MajorKind::Array, 2)?;
+        key.encode(DagCbor, w)?;
+        value.encode(DagCbor, w)?;
+    }
+    Ok(())
+}
+
+/// Writes a map from already encoded values, sorting the keys in canonical order.
+///
+/// Fails if a key occurs twice.
+pub fn write_map_entries<W: Write>(w: &mut W, mut entries: Vec<(String, Vec<u8>)>) -> Result<()> {
+    entries.sort_by(|(a, _), (b, _)| cmp_keys(a, b));
+    if entries.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+        return Err(DuplicateKey.into());
+    }
+    write_u64(w, MajorKind::Map, entries.len() as u64)?;
+    for (key, value) in entries {
+        key.encode(DagCbor, w)?;
+        w.write_all(&value)?;
+    }
+    Ok(())
+}
+
 /// Writes a tag to a cbor encoded byte stream.
 pub fn write_tag<W: Write>(w: &mut W, tag: u64) -> Result<()> {
     write_u64(w, MajorKind::Tag, tag)
@@ -276,17 +305,8 @@ impl<T: Encode<DagCbor>> Encode<DagCbor> for Vec<T> {
 impl<T: Encode<DagCbor> + 'static> Encode<DagCbor> for BTreeMap<String, T> {
     fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
         write_u64(w, MajorKind::Map, self.len() as u64)?;
-        // CBOR RFC-7049 specifies a canonical sort order, where keys are sorted by length first.
-        // This was later revised with RFC-8949, but we need to stick to the original order to stay
-        // compatible with existing data.
         let mut cbor_order = Vec::from_iter(self);
-        cbor_order.sort_unstable_by(|&(key_a, _), &(key_b, _)| {
-            match key_a.len().cmp(&key_b.len()) {
-                Ordering::Greater => Ordering::Greater,
-                Ordering::Less => Ordering::Less,
-                Ordering::Equal => key_a.cmp(key_b),
-            }
-        });
+        cbor_order.sort_unstable_by(|&(key_a, _), &(key_b, _)| cmp_keys(key_a, key_b));
         for (k, v) in cbor_order {
             k.encode(c, w)?;
             v.encode(c, w)?;
@@ -120,3 +120,56 @@ pub struct InvalidCidPrefix(pub u8);
 #[derive(Debug, Error)]
 #[error("Duplicate map key.")]
 pub struct DuplicateKey;
+
+/// Float not encoded as a 64 bit float.
+#[derive(Debug, Error)]
+#[error("Float not encoded as a 64 bit float.")]
+pub struct FloatNotCanonical;
+
+/// Map keys are not in canonical order.
+#[derive(Debug, Error)]
+#[error("Map key `{0}` is not in canonical order.")]
+pub struct KeyNotCanonical(pub String);
+
+/// Data after the end of the encoded value.
+#[derive(Debug, Error)]
+#[error("Trailing data after the encoded value.")]
+pub struct TrailingData;
+
+/// The number of items written doesn't match the declared length.
+#[derive(Debug, Error)]
+#[error("Declared a length of {declared} but wrote {written} items.")]
+pub struct LengthMismatch {
+    /// Declared number of items or map entries.
+    pub declared: u64,
+    /// Number of items or map entries written.
+    pub written: u64,
+}
+
+/// A token that isn't allowed at the current position of the writer.
+#[derive(Debug, Error)]
+#[error("Unexpected {0} token.")]
+pub struct UnexpectedToken(pub &'static str);
+
+/// A decode limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    /// Nesting depth of lists and maps.
+    Depth,
+    /// Total number of bytes allocated for the decoded value.
+    Allocation,
+    /// Number of items in a list or entries in a map.
+    CollectionLength,
+    /// Length of a string or byte string.
+    StringLength,
+}
+
+/// A decode limit was exceeded.
+#[derive(Debug, Error)]
+#[error("Decode limit exceeded: {limit:?} larger than {max}.")]
+pub struct LimitExceeded {
+    /// Limit.
+    pub limit: Limit,
+    /// Maximum allowed value.
+    pub max: usize,
+}
@@ -4,12 +4,15 @@
 
 use core::convert::TryFrom;
 use libipld_core::codec::{Codec, Decode, Encode};
+use libipld_core::error::with_offset;
 pub use libipld_core::error::{Result, UnsupportedCodec};
+use std::io::Cursor;
 
 pub mod cbor;
 pub mod decode;
 pub mod encode;
 pub mod error;
+pub mod token;
 
 /// CBOR codec.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
@@ -17,6 +20,51 @@ pub struct DagCborCodec;
 
 impl Codec for DagCborCodec {}
 
+impl DagCborCodec {
+    /// Decodes a decodable type, rejecting input that isn't in canonical form.
+    ///
+    /// Input accepted by this method re-encodes to exactly the same bytes, so a cid over the input
+    /// uniquely identifies the decoded value. See [`decode::validate_canonical`].
+    pub fn decode_strict<T: Decode<Self>>(&self, bytes: &[u8]) -> Result<T> {
+        let mut r = Cursor::new(bytes);
+        decode::validate_canonical(&mut r).map_err(|err| with_offset(err, r.position()))?;
+        if r.position() != bytes.len() as u64 {
+            return Err(with_offset(error::TrailingData.into(), r.position()));
+        }
+        self.decode(bytes)
+    }
+
+    /// Decodes an [`Ipld`](libipld_core::ipld::Ipld) from untrusted input, enforcing the given
+    /// limits.
+    pub fn decode_with_limits(
+        &self,
+        bytes: &[u8],
+        limits: &decode::DecodeLimits,
+    ) -> Result<libipld_core::ipld::Ipld> {
+        let mut r = Cursor::new(bytes);
+        decode::read_ipld(&mut r, limits).map_err(|err| with_offset(err, r.position()))
+    }
+
+    /// Decodes an [`IpldRef`](libipld_core::ipld_ref::IpldRef) that borrows strings and bytes
+    /// from `bytes` instead of copying them.
+    ///
+    /// Only the default limits are enforced, see [`DagCborCodec::decode_ref_with_limits`].
+    pub fn decode_ref<'a>(&self, bytes: &'a [u8]) -> Result<libipld_core::ipld_ref::IpldRef<'a>> {
+        self.decode_ref_with_limits(bytes, &decode::DecodeLimits::default())
+    }
+
+    /// Decodes an [`IpldRef`](libipld_core::ipld_ref::IpldRef) from untrusted input like
+    /// [`DagCborCodec::decode_ref`], enforcing the given limits.
+    pub fn decode_ref_with_limits<'a>(
+        &self,
+        bytes: &'a [u8],
+        limits: &decode::DecodeLimits,
+    ) -> Result<libipld_core::ipld_ref::IpldRef<'a>> {
+        let mut r = Cursor::new(bytes);
+        decode::read_ipld_ref(&mut r, limits).map_err(|err| with_offset(err, r.position()))
+    }
+}
+
 impl From<DagCborCodec> for u64 {
     fn from(_: DagCborCodec) -> Self {
         0x71
@@ -0,0 +1,463 @@
+//! Streaming token reader and writer.
+//!
+//! Reads or writes a dag-cbor encoded value token by token without building an [`Ipld`] tree, so
+//! that large blocks can be processed incrementally.
+use crate::cbor::{cmp_keys, MajorKind, F32, F64, FALSE, NULL, TRUE};
+use crate::decode::{
+    read_bytes, read_f32, read_f64, read_link, read_major, read_str, read_uint, Frame,
+};
+use crate::encode::{write_null, write_u64};
+use crate::error::{
+    DuplicateKey, KeyNotCanonical, LengthMismatch, TrailingData, UnexpectedCode, UnexpectedEof,
+    UnexpectedToken, UnknownTag,
+};
+use crate::DagCborCodec;
+use libipld_core::cid::Cid;
+use libipld_core::codec::Encode;
+use libipld_core::error::{with_location, Result};
+use libipld_core::ipld::Ipld;
+use std::cmp::Ordering;
+use std::io::{Read, Write};
+
+/// A token of a dag-cbor encoded value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    /// Null.
+    Null,
+    /// Boolean.
+    Bool(bool),
+    /// Integer.
+    Integer(i128),
+    /// Float.
+    Float(f64),
+    /// String that isn't a map key.
+    String(String),
+    /// Bytes.
+    Bytes(Vec<u8>),
+    /// Link.
+    Link(Cid),
+    /// Start of a list with the given number of items.
+    ListStart(u64),
+    /// Start of a map with the given number of entries.
+    MapStart(u64),
+    /// Key of a map entry. It is followed by the tokens of the value.
+    Key(String),
+    /// End of the innermost list or map.
+    End,
+}
+
+/// Counts the bytes read, so that errors can report their offset.
+struct CountingReader<R> {
+    inner: R,
+    offset: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+/// Reads the tokens of a single dag-cbor encoded value.
+///
+/// Lists and maps are tracked on a heap allocated stack, so deeply nested input can't overflow
+/// the call stack. Returns `None` once the value is complete or after an error.
+///
+/// Map keys are returned in the order they are encoded. The reader doesn't check that they are
+/// unique or in canonical order, use [`validate_canonical`](crate::decode::validate_canonical)
+/// first when that matters.
+pub struct TokenReader<R> {
+    r: CountingReader<R>,
+    stack: Vec<Frame>,
+    started: bool,
+    failed: bool,
+}
+
+impl<R: Read> TokenReader<R> {
+    /// Creates a new token reader.
+    pub fn new(r: R) -> Self {
+        Self {
+            r: CountingReader {
+                inner: r,
+                offset: 0,
+            },
+            stack: vec![],
+            started: false,
+            failed: false,
+        }
+    }
+
+    /// Returns the number of bytes read so far.
+    pub fn offset(&self) -> u64 {
+        self.r.offset
+    }
+
+    /// Returns the number of lists and maps enclosing the next token.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.r.inner
+    }
+
+    /// Reads the next token.
+    pub fn next_token(&mut self) -> Result<Option<Token>> {
+        if self.failed {
+            return Ok(None);
+        }
+        self.read_token().map_err(|err| {
+            self.failed = true;
+            // The stack still holds the containers enclosing the value that failed to decode.
+            let segments = self.stack.iter().filter_map(Frame::segment).collect();
+            with_location(err, Some(self.r.offset), segments)
+        })
+    }
+
+    /// Skips the next value, including all of its nested tokens.
+    ///
+    /// When the next token is a key, the whole map entry is skipped. Returns an
+    /// [`UnexpectedToken`] error when the next token is the end of a list or map.
+    pub fn skip_value(&mut self) -> Result<()> {
+        if let Some(frame) = self.stack.last() {
+            if frame.remaining == 0 {
+                return Err(UnexpectedToken("end").into());
+            }
+        }
+        let depth = self.depth();
+        while let Some(token) = self.next_token()? {
+            let done = match token {
+                Token::ListStart(_) | Token::MapStart(_) | Token::Key(_) => false,
+                _ => self.depth() <= depth,
+            };
+            if done {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_token(&mut self) -> Result<Option<Token>> {
+        match self.stack.last_mut() {
+            Some(frame) if frame.remaining == 0 => {
+                self.stack.pop();
+                return Ok(Some(Token::End));
+            }
+            Some(_) => {}
+            None if self.started => return Ok(None),
+            None => self.started = true,
+        }
+        let r = &mut self.r;
+        let is_key = self.stack.last_mut().map(Frame::next_item).unwrap_or(false);
+        let major = read_major(r)?;
+        if is_key {
+            if major.kind() != MajorKind::TextString {
+                return Err(UnexpectedCode::new::<String>(major.into()).into());
+            }
+            let len = read_uint(r, major)?;
+            let key = read_str(r, len)?;
+            if let Some(frame) = self.stack.last_mut() {
+                frame.last_key = Some(key.clone());
+            }
+            return Ok(Some(Token::Key(key)));
+        }
+        let token = match major.kind() {
+            MajorKind::UnsignedInt => Token::Integer(read_uint(r, major)? as i128),
+            MajorKind::NegativeInt => Token::Integer(-1 - read_uint(r, major)? as i128),
+            MajorKind::ByteString => {
+                let len = read_uint(r, major)?;
+                Token::Bytes(read_bytes(r, len)?)
+            }
+            MajorKind::TextString => {
+                let len = read_uint(r, major)?;
+                Token::String(read_str(r, len)?)
+            }
+            MajorKind::Array => {
+                let len = read_uint(r, major)?;
+                self.stack.push(Frame::list(len));
+                Token::ListStart(len)
+            }
+            MajorKind::Map => {
+                let len = read_uint(r, major)?;
+                self.stack.push(Frame::map(len)?);
+                Token::MapStart(len)
+            }
+            MajorKind::Tag => match read_uint(r, major)? {
+                42 => Token::Link(read_link(r)?),
+                tag => return Err(UnknownTag(tag).into()),
+            },
+            MajorKind::Other => match major {
+                FALSE => Token::Bool(false),
+                TRUE => Token::Bool(true),
+                NULL => Token::Null,
+                F32 => Token::Float(read_f32(r)? as f64),
+                F64 => Token::Float(read_f64(r)?),
+                m => return Err(UnexpectedCode::new::<Ipld>(m.into()).into()),
+            },
+        };
+        Ok(Some(token))
+    }
+}
+
+impl<R: Read> Iterator for TokenReader<R> {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token().transpose()
+    }
+}
+
+/// Writes a single dag-cbor encoded value token by token.
+///
+/// Every list and map has to declare its length up front and is checked to contain exactly that
+/// many items. Map keys have to be written in canonical order, so the output is always valid
+/// canonical dag-cbor. The writer shouldn't be used after it returned an error.
+pub struct TokenWriter<W> {
+    w: W,
+    stack: Vec<Frame>,
+    started: bool,
+}
+
+impl<W: Write> TokenWriter<W> {
+    /// Creates a new token writer.
+    pub fn new(w: W) -> Self {
+        Self {
+            w,
+            stack: vec![],
+            started: false,
+        }
+    }
+
+    /// Returns the number of lists and maps that are still open.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Writes a token.
+    pub fn write_token(&mut self, token: &Token) -> Result<()> {
+        match token {
+            Token::Null => {
+                self.start_value()?;
+                write_null(&mut self.w)
+            }
+            Token::Bool(b) => self.write(b),
+            Token::Integer(i) => self.write(i),
+            Token::Float(f) => self.write(f),
+            Token::String(s) => self.write(s),
+            Token::Bytes(b) => self.write(b.as_slice()),
+            Token::Link(cid) => self.write(cid),
+            Token::ListStart(len) => self.begin_list(*len),
+            Token::MapStart(len) => self.begin_map(*len),
+            Token::Key(key) => self.key(key),
+            Token::End => self.end(),
+        }
+    }
+
+    /// Writes a complete value as a single item.
+    pub fn write<T: Encode<DagCborCodec> + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.start_value()?;
+        value.encode(DagCborCodec, &mut self.w)
+    }
+
+    /// Starts a list of `len` items.
+    pub fn begin_list(&mut self, len: u64) -> Result<()> {
+        self.start_value()?;
+        write_u64(&mut self.w, MajorKind::Array, len)?;
+        self.stack.push(Frame::list(len));
+        Ok(())
+    }
+
+    /// Starts a map of `len` entries.
+    pub fn begin_map(&mut self, len: u64) -> Result<()> {
+        let frame = Frame::map(len)?;
+        self.start_value()?;
+        write_u64(&mut self.w, MajorKind::Map, len)?;
+        self.stack.push(frame);
+        Ok(())
+    }
+
+    /// Writes the key of the next map entry.
+    ///
+    /// Keys have to be strictly greater than the previous key of the map in canonical order.
+    pub fn key(&mut self, key: &str) -> Result<()> {
+        let frame = match self.stack.last_mut() {
+            Some(frame) if frame.remaining > 0 && frame.key_next() => frame,
+            _ => return Err(UnexpectedToken("key").into()),
+        };
+        match frame.last_key.as_deref().map(|last| cmp_keys(last, key)) {
+            Some(Ordering::Equal) => return Err(DuplicateKey.into()),
+            Some(Ordering::Greater) => return Err(KeyNotCanonical(key.into()).into()),
+            _ => {}
+        }
+        frame.next_item();
+        frame.last_key = Some(key.into());
+        key.encode(DagCborCodec, &mut self.w)
+    }
+
+    /// Ends the innermost list or map.
+    pub fn end(&mut self) -> Result<()> {
+        match self.stack.last() {
+            Some(frame) if frame.remaining > 0 => {
+                let (declared, written) = frame.counts();
+                Err(LengthMismatch { declared, written }.into())
+            }
+            Some(_) => {
+                self.stack.pop();
+                Ok(())
+            }
+            None => Err(UnexpectedToken("end").into()),
+        }
+    }
+
+    /// Returns the inner writer, checking that a complete value was written.
+    pub fn finish(self) -> Result<W> {
+        if !self.started || !self.stack.is_empty() {
+            return Err(UnexpectedEof.into());
+        }
+        Ok(self.w)
+    }
+
+    fn start_value(&mut self) -> Result<()> {
+        match self.stack.last_mut() {
+            Some(frame) if frame.remaining == 0 => {
+                let (declared, written) = frame.counts();
+                Err(LengthMismatch {
+                    declared,
+                    written: written + 1,
+                }
+                .into())
+            }
+            Some(frame) if frame.key_next() => Err(UnexpectedToken("value").into()),
+            Some(frame) => {
+                frame.next_item();
+                Ok(())
+            }
+            None if self.started => Err(TrailingData.into()),
+            None => {
+                self.started = true;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DagCborCodec;
+    use libipld_core::codec::Codec;
+    use libipld_core::error::DecodeLocation;
+    use libipld_macro::ipld;
+
+    #[test]
+    fn test_tokens() -> Result<()> {
+        let bytes = DagCborCodec.encode(&ipld!({"a": [1, "x"], "bb": {}, "c": null}))?;
+        let tokens = TokenReader::new(&bytes[..]).collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            tokens,
+            vec![
+                Token::MapStart(3),
+                Token::Key("a".into()),
+                Token::ListStart(2),
+                Token::Integer(1),
+                Token::String("x".into()),
+                Token::End,
+                Token::Key("c".into()),
+                Token::Null,
+                Token::Key("bb".into()),
+                Token::MapStart(0),
+                Token::End,
+                Token::End,
+            ]
+        );
+
+        let bytes = DagCborCodec.encode(&ipld!(true))?;
+        let tokens = TokenReader::new(&bytes[..]).collect::<Result<Vec<_>>>()?;
+        assert_eq!(tokens, vec![Token::Bool(true)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip() -> Result<()> {
+        let bytes = DagCborCodec.encode(&ipld!([[1, [2]], {"a": 3}, 4]))?;
+        let mut reader = TokenReader::new(&bytes[..]);
+        assert_eq!(reader.next_token()?, Some(Token::ListStart(3)));
+        reader.skip_value()?;
+        assert_eq!(reader.next_token()?, Some(Token::MapStart(1)));
+        reader.skip_value()?;
+        assert_eq!(reader.next_token()?, Some(Token::End));
+        assert_eq!(reader.next_token()?, Some(Token::Integer(4)));
+        assert!(reader.skip_value().unwrap_err().is::<UnexpectedToken>());
+        assert_eq!(reader.next_token()?, Some(Token::End));
+        assert_eq!(reader.next_token()?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_error() {
+        // {"foo": [1, 1("")]}
+        let bytes = [0xa1, 0x63, 0x66, 0x6f, 0x6f, 0x82, 0x01, 0xc1, 0x60];
+        let mut reader = TokenReader::new(&bytes[..]);
+        let err = reader.by_ref().find_map(|token| token.err()).unwrap();
+        let location = err.downcast_ref::<DecodeLocation>().unwrap();
+        assert_eq!(location.path(), ".foo[1]");
+        assert_eq!(location.offset, Some(8));
+        err.downcast::<UnknownTag>().unwrap();
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_writer() -> Result<()> {
+        let ipld = ipld!({"a": [1, "x"], "bb": {}, "c": null});
+        let bytes = DagCborCodec.encode(&ipld)?;
+        let mut writer = TokenWriter::new(vec![]);
+        for token in TokenReader::new(&bytes[..]) {
+            writer.write_token(&token?)?;
+        }
+        assert_eq!(writer.finish()?, bytes);
+
+        let mut writer = TokenWriter::new(vec![]);
+        writer.begin_map(3)?;
+        writer.key("a")?;
+        writer.write(ipld.get("a")?)?;
+        writer.key("c")?;
+        writer.write(&Ipld::Null)?;
+        writer.key("bb")?;
+        writer.begin_map(0)?;
+        writer.end()?;
+        writer.end()?;
+        assert_eq!(writer.finish()?, bytes);
+        Ok(())
+    }
+
+    #[test]
+    fn test_writer_errors() -> Result<()> {
+        let mut writer = TokenWriter::new(vec![]);
+        writer.begin_map(2)?;
+        writer.key("bb")?;
+        writer.write(&1)?;
+        assert!(writer.key("a").unwrap_err().is::<KeyNotCanonical>());
+        assert!(writer.key("bb").unwrap_err().is::<DuplicateKey>());
+        assert!(writer.write(&1).unwrap_err().is::<UnexpectedToken>());
+        let err = writer.end().unwrap_err();
+        let err = err.downcast::<LengthMismatch>().unwrap();
+        assert_eq!((err.declared, err.written), (2, 1));
+        writer.key("cc")?;
+        assert!(writer.end().unwrap_err().is::<LengthMismatch>());
+        writer.write(&2)?;
+        assert!(writer.key("dd").unwrap_err().is::<UnexpectedToken>());
+        assert!(writer.write(&3).unwrap_err().is::<LengthMismatch>());
+        writer.end()?;
+        assert!(writer.end().unwrap_err().is::<UnexpectedToken>());
+        assert!(writer.write(&4).unwrap_err().is::<TrailingData>());
+        writer.finish()?;
+
+        let mut writer = TokenWriter::new(vec![]);
+        writer.begin_list(1)?;
+        assert!(writer.finish().unwrap_err().is::<UnexpectedEof>());
+        Ok(())
+    }
+}
@@ -1,14 +1,20 @@
+use crate::decode::unread_number_end;
 use core::convert::TryFrom;
 use libipld_core::cid::Cid;
+use libipld_core::error::{with_location, PathSegment};
 use libipld_core::ipld::Ipld;
+use libipld_core::ipld_ref::IpldRef;
 use libipld_core::multibase::Base;
+use serde::de::DeserializeSeed;
 use serde::de::Error as SerdeError;
-use serde::{de, ser, Deserialize, Serialize};
+use serde::{de, ser, Serialize};
 use serde_json::ser::Serializer;
 use serde_json::Error;
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fmt;
-use std::io::{Read, Write};
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
 
 const RESERVED_KEY: &str = "/";
 const BYTES_KEY: &str = "bytes";
@@ -19,9 +25,63 @@ pub fn encode<W: Write>(ipld: &Ipld, writer: &mut W) -> Result<(), Error> {
     Ok(())
 }
 
-pub fn decode<R: Read>(r: &mut R) -> Result<Ipld, Error> {
-    let mut de = serde_json::Deserializer::from_reader(r);
-    deserialize(&mut de)
+pub fn decode<R: Read + Seek>(r: &mut R) -> libipld_core::error::Result<Ipld> {
+    let start = r.stream_position()?;
+    let mut path = Vec::new();
+    let res = {
+        let mut de = serde_json::Deserializer::from_reader(&mut *r);
+        IpldSeed::<Ipld>::new(&mut path).deserialize(&mut de)
+    };
+    match res {
+        Ok(ipld) => {
+            if matches!(ipld, Ipld::Integer(_) | Ipld::Float(_)) {
+                unread_number_end(r)?;
+            }
+            Ok(ipld)
+        }
+        Err(err) => Err(locate(err, r, start, path)),
+    }
+}
+
+pub fn decode_ref(bytes: &[u8]) -> libipld_core::error::Result<IpldRef<'_>> {
+    let mut path = Vec::new();
+    let mut de = serde_json::Deserializer::from_slice(bytes);
+    IpldSeed::<IpldRef>::new(&mut path)
+        .deserialize(&mut de)
+        .map_err(|err| locate(err, &mut Cursor::new(bytes), 0, path))
+}
+
+/// Attaches the location to an error.
+///
+/// The path still holds the segments leading to the value that failed to decode.
+fn locate<R: Read + Seek>(
+    err: Error,
+    r: &mut R,
+    start: u64,
+    segments: Vec<PathSegment>,
+) -> libipld_core::error::Error {
+    // The reader has moved past the error, so the offset is computed from the line and column of
+    // the error instead.
+    let offset = error_offset(r, start, &err);
+    with_location(err.into(), offset, segments)
+}
+
+/// Converts the line and column of an error into a byte offset.
+fn error_offset<R: Read + Seek>(r: &mut R, start: u64, err: &Error) -> Option<u64> {
+    if err.line() == 0 {
+        return None;
+    }
+    r.seek(SeekFrom::Start(start)).ok()?;
+    let mut line_start = 0;
+    let mut line = 1;
+    let mut bytes = BufReader::new(r).bytes();
+    while line < err.line() {
+        if bytes.next()?.ok()? == b'\n' {
+            line += 1;
+        }
+        line_start += 1;
+    }
+    Some(start + line_start + err.column().saturating_sub(1) as u64)
 }
 
 fn serialize<S: ser::Serializer>(ipld: &Ipld, ser: S) -> Result<S::Ok, S::Error> {
@@ -54,11 +114,6 @@ fn serialize<S: ser::Serializer>(ipld: &Ipld, ser: S) -> Result<S::Ok, S::Error>
     }
 }
 
-fn deserialize<'de, D: de::Deserializer<'de>>(deserializer: D) -> Result<Ipld, D::Error> {
-    // Sadly such a PhantomData hack is needed
-    deserializer.deserialize_any(JsonVisitor)
-}
-
 // Needed for `collect_seq` and `collect_map` in Seserializer
 struct Wrapper<'a>(&'a Ipld);
 
@@ -71,16 +126,194 @@ impl<'a> Serialize for Wrapper<'a> {
     }
 }
 
+// Value built by the visitor, either an owned `Ipld` or an `IpldRef` that borrows from the input.
+trait JsonValue<'de>: Sized {
+    type Key: Ord + From<Cow<'de, str>>;
+
+    // Converts a null, bool, number or link.
+    fn scalar(ipld: Ipld) -> Self;
+    fn string(value: Cow<'de, str>) -> Self;
+    fn bytes(value: Cow<'de, [u8]>) -> Self;
+    fn list(list: Vec<Self>) -> Self;
+    fn map(map: BTreeMap<Self::Key, Self>) -> Self;
+    fn as_str(&self) -> Option<&str>;
+    // Returns the first entry of a map.
+    fn first_entry(&self) -> Option<(&str, &Self)>;
+}
+
+impl<'de> JsonValue<'de> for Ipld {
+    type Key = String;
+
+    fn scalar(ipld: Ipld) -> Self {
+        ipld
+    }
+
+    fn string(value: Cow<'de, str>) -> Self {
+        Ipld::String(value.into_owned())
+    }
+
+    fn bytes(value: Cow<'de, [u8]>) -> Self {
+        Ipld::Bytes(value.into_owned())
+    }
+
+    fn list(list: Vec<Self>) -> Self {
+        Ipld::List(list)
+    }
+
+    fn map(map: BTreeMap<String, Self>) -> Self {
+        Ipld::Map(map)
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Ipld::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn first_entry(&self) -> Option<(&str, &Self)> {
+        match self {
+            Ipld::Map(map) => map.iter().next().map(|(key, value)| (key.as_str(), value)),
+            _ => None,
+        }
+    }
+}
+
+impl<'de> JsonValue<'de> for IpldRef<'de> {
+    type Key = Cow<'de, str>;
+
+    fn scalar(ipld: Ipld) -> Self {
+        match ipld {
+            Ipld::Null => IpldRef::Null,
+            Ipld::Bool(b) => IpldRef::Bool(b),
+            Ipld::Integer(i) => IpldRef::Integer(i),
+            Ipld::Float(f) => IpldRef::Float(f),
+            Ipld::Link(cid) => IpldRef::Link(cid),
+            _ => unreachable!("only scalars are converted"),
+        }
+    }
+
+    fn string(value: Cow<'de, str>) -> Self {
+        IpldRef::String(value)
+    }
+
+    fn bytes(value: Cow<'de, [u8]>) -> Self {
+        IpldRef::Bytes(value)
+    }
+
+    fn list(list: Vec<Self>) -> Self {
+        IpldRef::List(list)
+    }
+
+    fn map(map: BTreeMap<Cow<'de, str>, Self>) -> Self {
+        IpldRef::Map(map)
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            IpldRef::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn first_entry(&self) -> Option<(&str, &Self)> {
+        match self {
+            IpldRef::Map(map) => map.iter().next().map(|(key, value)| (key.as_ref(), value)),
+            _ => None,
+        }
+    }
+}
+
+// Deserializes a single value, keeping track of the data model path to it. The segment of a list
+// item is pushed before it is decoded and popped afterwards, the key of a map entry is only added
+// when its value fails to decode, so that on error the path leads to the value that failed to
+// decode.
+struct IpldSeed<'p, T> {
+    path: &'p mut Vec<PathSegment>,
+    _marker: PhantomData<T>,
+}
+
+impl<'p, T> IpldSeed<'p, T> {
+    fn new(path: &'p mut Vec<PathSegment>) -> Self {
+        Self {
+            path,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'de, 'p, T: JsonValue<'de>> DeserializeSeed<'de> for IpldSeed<'p, T> {
+    type Value = T;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<T, D::Error> {
+        deserializer.deserialize_any(JsonVisitor::<T>::new(self.path))
+    }
+}
+
+// Deserializes a map key, borrowing it from the input if possible.
+struct KeySeed;
+
+impl<'de> DeserializeSeed<'de> for KeySeed {
+    type Value = Cow<'de, str>;
+
+    fn deserialize<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Cow<'de, str>, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> de::Visitor<'de> for KeySeed {
+    type Value = Cow<'de, str>;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("a string")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, value: &'de str) -> Result<Self::Value, E> {
+        Ok(Cow::Borrowed(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(Cow::Owned(value.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+        Ok(Cow::Owned(value))
+    }
+}
+
 // serde deserializer visitor that is used by Deseraliazer to decode
 // json into IPLD.
-struct JsonVisitor;
-impl<'de> de::Visitor<'de> for JsonVisitor {
-    type Value = Ipld;
+struct JsonVisitor<'p, T> {
+    path: &'p mut Vec<PathSegment>,
+    _marker: PhantomData<T>,
+}
+
+impl<'p, T> JsonVisitor<'p, T> {
+    fn new(path: &'p mut Vec<PathSegment>) -> Self {
+        Self {
+            path,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'de, 'p, T: JsonValue<'de>> de::Visitor<'de> for JsonVisitor<'p, T> {
+    type Value = T;
 
     fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt.write_str("any valid JSON value")
     }
 
+    fn visit_borrowed_str<E>(self, value: &'de str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(T::string(Cow::Borrowed(value)))
+    }
+
     fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
     where
         E: de::Error,
@@ -92,8 +325,16 @@ impl<'de> de::Visitor<'de> for JsonVisitor {
     where
         E: de::Error,
     {
-        Ok(Ipld::String(value))
+        Ok(T::string(Cow::Owned(value)))
+    }
+
+    fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(T::bytes(Cow::Borrowed(v)))
     }
+
     fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
     where
         E: de::Error,
@@ -105,35 +346,35 @@ impl<'de> de::Visitor<'de> for JsonVisitor {
     where
         E: de::Error,
     {
-        Ok(Ipld::Bytes(v))
+        Ok(T::bytes(Cow::Owned(v)))
     }
 
     fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        Ok(Ipld::Integer(v.into()))
+        Ok(T::scalar(Ipld::Integer(v.into())))
     }
 
     fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        Ok(Ipld::Integer(v.into()))
+        Ok(T::scalar(Ipld::Integer(v.into())))
     }
 
     fn visit_i128<E>(self, v: i128) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        Ok(Ipld::Integer(v))
+        Ok(T::scalar(Ipld::Integer(v)))
     }
 
     fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        Ok(Ipld::Bool(v))
+        Ok(T::scalar(Ipld::Bool(v)))
     }
 
     fn visit_none<E>(self) -> Result<Self::Value, E>
@@ -147,91 +388,86 @@ impl<'de> de::Visitor<'de> for JsonVisitor {
     where
         E: de::Error,
     {
-        Ok(Ipld::Null)
+        Ok(T::scalar(Ipld::Null))
     }
 
     fn visit_seq<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
     where
         V: de::SeqAccess<'de>,
     {
-        let mut vec: Vec<WrapperOwned> = Vec::new();
-
-        while let Some(elem) = visitor.next_element()? {
-            vec.push(elem);
+        let mut list = Vec::new();
+        loop {
+            self.path.push(PathSegment::Index(list.len()));
+            let elem = visitor.next_element_seed(IpldSeed::<T>::new(&mut *self.path))?;
+            self.path.pop();
+            match elem {
+                Some(elem) => list.push(elem),
+                None => break,
+            }
         }
-
-        let unwrapped = vec.into_iter().map(|WrapperOwned(ipld)| ipld).collect();
-        Ok(Ipld::List(unwrapped))
+        Ok(T::list(list))
     }
 
     fn visit_map<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
     where
         V: de::MapAccess<'de>,
     {
-        let mut values: Vec<(String, WrapperOwned)> = Vec::new();
+        let mut values: Vec<(Cow<'de, str>, T)> = Vec::new();
 
-        while let Some((key, value)) = visitor.next_entry()? {
-            values.push((key, value));
+        while let Some(key) = visitor.next_key_seed(KeySeed)? {
+            let depth = self.path.len();
+            match visitor.next_value_seed(IpldSeed::<T>::new(&mut *self.path)) {
+                Ok(value) => values.push((key, value)),
+                Err(err) => {
+                    // The segments of the nested values were pushed after this one.
+                    self.path.insert(depth, PathSegment::Key(key.into_owned()));
+                    return Err(err);
+                }
+            }
         }
 
         // JSON Object represents an IPLD Link if it is a slash, followed by a string
         // (`{ "/": "...." }`) therefore we validate if that is the case here.
-        if let Some((key, WrapperOwned(Ipld::String(value)))) = values.first() {
+        if let Some((key, value)) = values.first() {
             if key == RESERVED_KEY && values.len() == 1 {
-                let cid = Cid::try_from(value.clone()).map_err(SerdeError::custom)?;
-                return Ok(Ipld::Link(cid));
+                if let Some(value) = value.as_str() {
+                    let cid = Cid::try_from(value).map_err(SerdeError::custom)?;
+                    return Ok(T::scalar(Ipld::Link(cid)));
+                }
             }
         }
         // JSON Object represents IPLD bytes if it is a slash, followed by an object which contains
         // only a single key called "bytes", where the value is a string.
-        if let Some((key, WrapperOwned(Ipld::Map(map)))) = values.first() {
+        if let Some((key, value)) = values.first() {
             // Object with a slash
             if key == RESERVED_KEY && values.len() == 1 {
-                if let Some((bytes_key, Ipld::String(bytes_value))) = map.iter().next() {
-                    if bytes_key == BYTES_KEY && values.len() == 1 {
-                        let decoded_bytes = Base::Base64.decode(bytes_value).map_err(|_| {
-                            SerdeError::custom("bytes kind must be base-64 encoded")
-                        })?;
-                        return Ok(Ipld::Bytes(decoded_bytes));
+                if let Some((bytes_key, bytes_value)) = value.first_entry() {
+                    if let Some(bytes_value) = bytes_value.as_str() {
+                        if bytes_key == BYTES_KEY && values.len() == 1 {
+                            let decoded_bytes = Base::Base64.decode(bytes_value).map_err(|_| {
+                                SerdeError::custom("bytes kind must be base-64 encoded")
+                            })?;
+                            return Ok(T::bytes(Cow::Owned(decoded_bytes)));
+                        }
                     }
                 }
             }
         }
 
         let mut unwrapped = BTreeMap::new();
-        for (key, WrapperOwned(value)) in values {
-            let prev_value = unwrapped.insert(key, value);
+        for (key, value) in values {
+            let prev_value = unwrapped.insert(T::Key::from(key), value);
             if prev_value.is_some() {
                 return Err(SerdeError::custom("duplicate map key".to_string()));
             }
         }
-        Ok(Ipld::Map(unwrapped))
+        Ok(T::map(unwrapped))
     }
 
     fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        Ok(Ipld::Float(v))
-    }
-}
-
-// Needed for `visit_seq` and `visit_map` in Deserializer
-/// We cannot directly implement `serde::Deserializer` for `Ipld` as it is a remote type.
-/// Instead wrap it into a newtype struct and implement `serde::Deserialize` for that one.
-/// All the deserializer does is calling the `deserialize()` function we defined which returns
-/// an unwrapped `Ipld` instance. Wrap that `Ipld` instance in `Wrapper` and return it.
-/// Users of this wrapper will then unwrap it again so that they can return the expected `Ipld`
-/// instance.
-struct WrapperOwned(Ipld);
-
-impl<'de> Deserialize<'de> for WrapperOwned {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: de::Deserializer<'de>,
-    {
-        let deserialized = deserialize(deserializer);
-        // Better version of Ok(Wrapper(deserialized.unwrap()))
-        deserialized.map(Self)
+        Ok(T::scalar(Ipld::Float(v)))
     }
 }
@@ -0,0 +1,405 @@
+//! Json decoder.
+//!
+//! Reads dag-json directly into rust types. Scalars are deserialized with `serde_json`, lists,
+//! maps, links and bytes are read piece by piece so that they can be decoded into any type.
+use crate::error::{DuplicateKey, InvalidBytes, NumberOutOfRange, UnexpectedChar, UnexpectedKey};
+use crate::DagJsonCodec as DagJson;
+use core::convert::TryFrom;
+use libipld_core::cid::Cid;
+use libipld_core::codec::{Decode, Encode};
+use libipld_core::error::{with_path_segment, PathSegment, Result};
+use libipld_core::ipld::Ipld;
+use libipld_core::multibase::Base;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+/// Reads the next byte that isn't whitespace.
+pub fn read_byte<R: Read>(r: &mut R) -> Result<u8> {
+    let mut buf = [0; 1];
+    loop {
+        r.read_exact(&mut buf)?;
+        if !matches!(buf[0], b' ' | b'\t' | b'\n' | b'\r') {
+            return Ok(buf[0]);
+        }
+    }
+}
+
+/// Returns the next byte that isn't whitespace without consuming it.
+pub fn peek_byte<R: Read + Seek>(r: &mut R) -> Result<u8> {
+    let byte = read_byte(r)?;
+    r.seek(SeekFrom::Current(-1))?;
+    Ok(byte)
+}
+
+/// Data model kind of a json value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// `null`.
+    Null,
+    /// `true` or `false`.
+    Bool,
+    /// A number without a fraction or exponent.
+    Integer,
+    /// A number with a fraction or exponent.
+    Float,
+    /// A string.
+    String,
+    /// `{"/": {"bytes": "<base64>"}}`.
+    Bytes,
+    /// An array.
+    List,
+    /// An object that isn't a link or bytes.
+    Map,
+    /// `{"/": "<cid>"}`.
+    Link,
+}
+
+/// Returns the kind of the next value without consuming it.
+///
+/// Only reads as far as needed to tell the kind apart, the value itself isn't validated.
+pub fn peek_kind<T, R: Read + Seek>(r: &mut R) -> Result<Kind> {
+    let pos = r.stream_position()?;
+    let kind = match read_byte(r)? {
+        b'n' => Kind::Null,
+        b't' | b'f' => Kind::Bool,
+        b'"' => Kind::String,
+        b'[' => Kind::List,
+        byte if is_number_start(byte) => {
+            let mut kind = Kind::Integer;
+            let mut buf = [0; 1];
+            while r.read(&mut buf)? == 1 {
+                match buf[0] {
+                    b'.' | b'e' | b'E' => kind = Kind::Float,
+                    b'0'..=b'9' | b'-' | b'+' => {}
+                    _ => break,
+                }
+            }
+            kind
+        }
+        b'{' => match peek_byte(r)? {
+            b'"' if read_key::<T, _>(r)? == "/" => match peek_byte(r)? {
+                b'"' => Kind::Link,
+                b'{' => Kind::Bytes,
+                _ => Kind::Map,
+            },
+            _ => Kind::Map,
+        },
+        byte => return Err(UnexpectedChar::new::<T>(byte).into()),
+    };
+    r.seek(SeekFrom::Start(pos))?;
+    Ok(kind)
+}
+
+/// Reads the next byte that isn't whitespace and checks that it is `expected`.
+pub fn expect_byte<T, R: Read>(r: &mut R, expected: u8) -> Result<()> {
+    let byte = read_byte(r)?;
+    if byte != expected {
+        return Err(UnexpectedChar::new::<T>(byte).into());
+    }
+    Ok(())
+}
+
+/// Reads the separator in front of item `i` of a list or map that is closed by `close`.
+///
+/// Returns `false` once the closing bracket was read.
+pub fn read_separator<T, R: Read + Seek>(r: &mut R, close: u8, i: usize) -> Result<bool> {
+    match read_byte(r)? {
+        byte if byte == close => Ok(false),
+        b',' if i > 0 => Ok(true),
+        _ if i == 0 => {
+            r.seek(SeekFrom::Current(-1))?;
+            Ok(true)
+        }
+        byte => Err(UnexpectedChar::new::<T>(byte).into()),
+    }
+}
+
+/// Reads a map key and the colon following it.
+pub fn read_key<T, R: Read + Seek>(r: &mut R) -> Result<String> {
+    let key = read_value(r)?;
+    expect_byte::<T, _>(r, b':')?;
+    Ok(key)
+}
+
+/// Deserializes a scalar value with `serde_json`.
+pub fn read_value<T: DeserializeOwned, R: Read + Seek>(r: &mut R) -> Result<T> {
+    let number = is_number_start(peek_byte(r)?);
+    let value = T::deserialize(&mut serde_json::Deserializer::from_reader(&mut *r))?;
+    if number {
+        unread_number_end(r)?;
+    }
+    Ok(value)
+}
+
+/// Returns whether a json value starting with `byte` is a number.
+pub(crate) fn is_number_start(byte: u8) -> bool {
+    byte == b'-' || byte.is_ascii_digit()
+}
+
+/// Seeks back over the byte `serde_json` reads after a number to find its end.
+///
+/// Numbers always end with a digit, so if the last byte read isn't one, it is the byte after the
+/// number. Otherwise the number ended at the end of the input.
+pub(crate) fn unread_number_end<R: Read + Seek>(r: &mut R) -> Result<()> {
+    r.seek(SeekFrom::Current(-1))?;
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    if !buf[0].is_ascii_digit() {
+        r.seek(SeekFrom::Current(-1))?;
+    }
+    Ok(())
+}
+
+/// Reads the opening of a `{"/": ...}` object.
+fn read_reserved<T, R: Read + Seek>(r: &mut R, key: &str) -> Result<()> {
+    expect_byte::<T, _>(r, b'{')?;
+    let found = read_key::<T, _>(r)?;
+    if found != key {
+        return Err(UnexpectedKey::new::<T>(found).into());
+    }
+    Ok(())
+}
+
+/// Reads a link encoded as `{"/": "<cid>"}`.
+pub fn read_link<R: Read + Seek>(r: &mut R) -> Result<Cid> {
+    read_reserved::<Cid, _>(r, "/")?;
+    let cid: String = read_value(r)?;
+    let cid = Cid::try_from(cid.as_str())?;
+    expect_byte::<Cid, _>(r, b'}')?;
+    Ok(cid)
+}
+
+/// Reads bytes encoded as `{"/": {"bytes": "<base64>"}}`.
+pub fn read_bytes<R: Read + Seek>(r: &mut R) -> Result<Vec<u8>> {
+    read_reserved::<Vec<u8>, _>(r, "/")?;
+    read_reserved::<Vec<u8>, _>(r, "bytes")?;
+    let bytes: String = read_value(r)?;
+    let bytes = Base::Base64.decode(bytes).map_err(|_| InvalidBytes)?;
+    expect_byte::<Vec<u8>, _>(r, b'}')?;
+    expect_byte::<Vec<u8>, _>(r, b'}')?;
+    Ok(bytes)
+}
+
+/// Reads a list of any type that implements `Decode<DagJsonCodec>`.
+pub fn read_list<R: Read + Seek, T: Decode<DagJson>>(r: &mut R) -> Result<Vec<T>> {
+    expect_byte::<Vec<T>, _>(r, b'[')?;
+    let mut list = Vec::new();
+    while read_separator::<Vec<T>, _>(r, b']', list.len())? {
+        let value = T::decode(DagJson, r)
+            .map_err(|err| with_path_segment(err, PathSegment::Index(list.len())))?;
+        list.push(value);
+    }
+    Ok(list)
+}
+
+/// Reads a map of any type that implements `Decode<DagJsonCodec>`.
+pub fn read_map<R: Read + Seek, T: Decode<DagJson>>(r: &mut R) -> Result<BTreeMap<String, T>> {
+    expect_byte::<BTreeMap<String, T>, _>(r, b'{')?;
+    let mut map = BTreeMap::new();
+    while read_separator::<BTreeMap<String, T>, _>(r, b'}', map.len())? {
+        let key = read_key::<BTreeMap<String, T>, _>(r)?;
+        let value = T::decode(DagJson, r)
+            .map_err(|err| with_path_segment(err, PathSegment::Key(key.clone())))?;
+        if map.insert(key, value).is_some() {
+            return Err(DuplicateKey.into());
+        }
+    }
+    Ok(map)
+}
+
+/// Reads a value without decoding it into a rust type, returning its encoding.
+pub fn read_raw_value<R: Read + Seek>(r: &mut R) -> Result<Vec<u8>> {
+    let ipld = Ipld::decode(DagJson, r)?;
+    let mut value = Vec::new();
+    ipld.encode(DagJson, &mut value)?;
+    Ok(value)
+}
+
+/// Reads a map without decoding its values, returning the encoded value of each key.
+pub fn read_map_entries<R: Read + Seek>(r: &mut R) -> Result<Vec<(String, Vec<u8>)>> {
+    expect_byte::<BTreeMap<String, Ipld>, _>(r, b'{')?;
+    let mut entries = Vec::new();
+    while read_separator::<BTreeMap<String, Ipld>, _>(r, b'}', entries.len())? {
+        let key = read_key::<BTreeMap<String, Ipld>, _>(r)?;
+        let value = read_raw_value(r)
+            .map_err(|err| with_path_segment(err, PathSegment::Key(key.clone())))?;
+        entries.push((key, value));
+    }
+    Ok(entries)
+}
+
+/// Reads a map from a list of `[key, value]` pairs, the listpairs representation.
+pub fn read_list_pairs<R: Read + Seek, K: Decode<DagJson> + Ord, T: Decode<DagJson>>(
+    r: &mut R,
+) -> Result<BTreeMap<K, T>> {
+    expect_byte::<BTreeMap<K, T>, _>(r, b'[')?;
+    let mut map = BTreeMap::new();
+    let mut i = 0;
+    while read_separator::<BTreeMap<K, T>, _>(r, b']', i)? {
+        let (key, value) =
+            read_pair(r).map_err(|err| with_path_segment(err, PathSegment::Index(i)))?;
+        if map.insert(key, value).is_some() {
+            return Err(DuplicateKey.into());
+        }
+        i += 1;
+    }
+    Ok(map)
+}
+
+fn read_pair<R: Read + Seek, K: Decode<DagJson>, T: Decode<DagJson>>(r: &mut R) -> Result<(K, T)> {
+    expect_byte::<(K, T), _>(r, b'[')?;
+    let key = K::decode(DagJson, r)?;
+    expect_byte::<(K, T), _>(r, b',')?;
+    let value = T::decode(DagJson, r)?;
+    expect_byte::<(K, T), _>(r, b']')?;
+    Ok((key, value))
+}
+
+macro_rules! impl_value {
+    ($($t:ty),*) => {
+        $(
+            impl Decode<DagJson> for $t {
+                fn decode<R: Read + Seek>(_: DagJson, r: &mut R) -> Result<Self> {
+                    read_value(r)
+                }
+            }
+        )*
+    };
+}
+
+impl_value!(bool, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, String);
+
+impl Decode<DagJson> for f32 {
+    fn decode<R: Read + Seek>(c: DagJson, r: &mut R) -> Result<Self> {
+        let num = f64::decode(c, r)?;
+        let converted = num as Self;
+        if f64::from(converted) != num {
+            return Err(NumberOutOfRange::new::<Self>().into());
+        }
+        Ok(converted)
+    }
+}
+
+impl Decode<DagJson> for f64 {
+    fn decode<R: Read + Seek>(_: DagJson, r: &mut R) -> Result<Self> {
+        let num: Self = read_value(r)?;
+        if !num.is_finite() {
+            return Err(NumberOutOfRange::new::<Self>().into());
+        }
+        Ok(num)
+    }
+}
+
+impl Decode<DagJson> for Cid {
+    fn decode<R: Read + Seek>(_: DagJson, r: &mut R) -> Result<Self> {
+        read_link(r)
+    }
+}
+
+impl Decode<DagJson> for Box<[u8]> {
+    fn decode<R: Read + Seek>(_: DagJson, r: &mut R) -> Result<Self> {
+        Ok(read_bytes(r)?.into_boxed_slice())
+    }
+}
+
+impl<T: Decode<DagJson>> Decode<DagJson> for Option<T> {
+    fn decode<R: Read + Seek>(c: DagJson, r: &mut R) -> Result<Self> {
+        if peek_byte(r)? == b'n' {
+            read_value::<(), _>(r)?;
+            Ok(None)
+        } else {
+            Ok(Some(T::decode(c, r)?))
+        }
+    }
+}
+
+impl<T: Decode<DagJson>> Decode<DagJson> for Vec<T> {
+    fn decode<R: Read + Seek>(_: DagJson, r: &mut R) -> Result<Self> {
+        read_list(r)
+    }
+}
+
+impl<T: Decode<DagJson>> Decode<DagJson> for BTreeMap<String, T> {
+    fn decode<R: Read + Seek>(_: DagJson, r: &mut R) -> Result<Self> {
+        read_map(r)
+    }
+}
+
+impl<T: Decode<DagJson>> Decode<DagJson> for Arc<T> {
+    fn decode<R: Read + Seek>(c: DagJson, r: &mut R) -> Result<Self> {
+        Ok(Arc::new(T::decode(c, r)?))
+    }
+}
+
+impl Decode<DagJson> for () {
+    fn decode<R: Read + Seek>(_: DagJson, r: &mut R) -> Result<Self> {
+        expect_byte::<Self, _>(r, b'[')?;
+        expect_byte::<Self, _>(r, b']')?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld_core::codec::Codec;
+    use libipld_core::ipld::Ipld;
+
+    #[test]
+    fn test_nested_numbers() -> Result<()> {
+        let json = br#"{"a": [1, 2.5, -3], "b": {"c": 4}, "d": 5}"#;
+        let map: BTreeMap<String, Ipld> = DagJson.decode(json)?;
+        assert_eq!(
+            map["a"],
+            Ipld::List(vec![1.into(), 2.5.into(), (-3).into()])
+        );
+        assert_eq!(map["d"], Ipld::Integer(5));
+        let list: Vec<Option<u8>> = DagJson.decode(b" [1 ,null,\n3] ")?;
+        assert_eq!(list, vec![Some(1), None, Some(3)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_peek_kind() -> Result<()> {
+        let kinds = [
+            (&b" null"[..], Kind::Null),
+            (b"false", Kind::Bool),
+            (b"-12", Kind::Integer),
+            (b"1.5", Kind::Float),
+            (b"1e3,", Kind::Float),
+            (br#""a""#, Kind::String),
+            (b"[]", Kind::List),
+            (b"{}", Kind::Map),
+            (br#"{"a": 1}"#, Kind::Map),
+            (br#"{ "/" : "cid"}"#, Kind::Link),
+            (br#"{"/": {"bytes": ""}}"#, Kind::Bytes),
+        ];
+        for (json, kind) in kinds {
+            let mut r = std::io::Cursor::new(json);
+            assert_eq!(peek_kind::<Ipld, _>(&mut r)?, kind);
+            assert_eq!(r.position(), 0);
+        }
+        let mut r = std::io::Cursor::new(b"x");
+        assert!(peek_kind::<Ipld, _>(&mut r)
+            .unwrap_err()
+            .is::<UnexpectedChar>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = DagJson.decode::<Vec<u8>>(b"[1, 2 3]").unwrap_err();
+        assert!(err.is::<UnexpectedChar>());
+        let err = DagJson
+            .decode::<BTreeMap<String, u8>>(br#"{"a": 1, "a": 2}"#)
+            .unwrap_err();
+        assert!(err.is::<DuplicateKey>());
+        let err = DagJson.decode::<Vec<u8>>(b"[1, 256]").unwrap_err();
+        let location = err
+            .downcast_ref::<libipld_core::error::DecodeLocation>()
+            .unwrap();
+        assert_eq!(location.path(), "[1]");
+    }
+}
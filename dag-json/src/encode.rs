@@ -0,0 +1,188 @@
+//! Json encoder.
+use crate::error::{DuplicateKey, NumberOutOfRange};
+use crate::DagJsonCodec as DagJson;
+use libipld_core::cid::Cid;
+use libipld_core::codec::Encode;
+use libipld_core::error::Result;
+use libipld_core::multibase::Base;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::Arc;
+
+/// Writes a scalar value with `serde_json`.
+pub fn write_value<W: Write, T: Serialize + ?Sized>(w: &mut W, value: &T) -> Result<()> {
+    serde_json::to_writer(w, value)?;
+    Ok(())
+}
+
+/// Writes null.
+pub fn write_null<W: Write>(w: &mut W) -> Result<()> {
+    w.write_all(b"null")?;
+    Ok(())
+}
+
+/// Writes the key of a map entry, preceded by a comma unless it is the first entry.
+pub fn write_key<W: Write>(w: &mut W, key: &str, first: &mut bool) -> Result<()> {
+    if !*first {
+        w.write_all(b",")?;
+    }
+    *first = false;
+    write_value(w, key)?;
+    w.write_all(b":")?;
+    Ok(())
+}
+
+/// Writes a link as `{"/": "<cid>"}`.
+pub fn write_link<W: Write>(w: &mut W, cid: &Cid) -> Result<()> {
+    w.write_all(b"{\"/\":")?;
+    write_value(w, &cid.to_string())?;
+    w.write_all(b"}")?;
+    Ok(())
+}
+
+/// Writes bytes as `{"/": {"bytes": "<base64>"}}`.
+pub fn write_bytes<W: Write>(w: &mut W, bytes: &[u8]) -> Result<()> {
+    w.write_all(b"{\"/\":{\"bytes\":")?;
+    write_value(w, &Base::Base64.encode(bytes))?;
+    w.write_all(b"}}")?;
+    Ok(())
+}
+
+/// Writes a map from already encoded values, sorting the keys bytewise.
+///
+/// Fails if a key occurs twice.
+pub fn write_map_entries<W: Write>(w: &mut W, mut entries: Vec<(String, Vec<u8>)>) -> Result<()> {
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    if entries.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+        return Err(DuplicateKey.into());
+    }
+    w.write_all(b"{")?;
+    let mut first = true;
+    for (key, value) in entries {
+        write_key(w, &key, &mut first)?;
+        w.write_all(&value)?;
+    }
+    w.write_all(b"}")?;
+    Ok(())
+}
+
+/// Writes a map as a list of `[key, value]` pairs, the listpairs representation.
+pub fn write_list_pairs<W: Write, K: Encode<DagJson>, T: Encode<DagJson>>(
+    w: &mut W,
+    map: &BTreeMap<K, T>,
+) -> Result<()> {
+    w.write_all(b"[")?;
+    for (i, (key, value)) in map.iter().enumerate() {
+        if i > 0 {
+            w.write_all(b",")?;
+        }
+        w.write_all(b"[")?;
+        key.encode(DagJson, w)?;
+        w.write_all(b",")?;
+        value.encode(DagJson, w)?;
+        w.write_all(b"]")?;
+    }
+    w.write_all(b"]")?;
+    Ok(())
+}
+
+macro_rules! impl_value {
+    ($($t:ty),*) => {
+        $(
+            impl Encode<DagJson> for $t {
+                fn encode<W: Write>(&self, _: DagJson, w: &mut W) -> Result<()> {
+                    write_value(w, self)
+                }
+            }
+        )*
+    };
+}
+
+impl_value!(bool, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, str, String);
+
+impl Encode<DagJson> for f32 {
+    fn encode<W: Write>(&self, c: DagJson, w: &mut W) -> Result<()> {
+        // IPLD maximally encodes floats.
+        f64::from(*self).encode(c, w)
+    }
+}
+
+impl Encode<DagJson> for f64 {
+    fn encode<W: Write>(&self, _: DagJson, w: &mut W) -> Result<()> {
+        // IPLD forbids nan, infinities, etc.
+        if !self.is_finite() {
+            return Err(NumberOutOfRange::new::<f64>().into());
+        }
+        write_value(w, self)
+    }
+}
+
+impl Encode<DagJson> for Cid {
+    fn encode<W: Write>(&self, _: DagJson, w: &mut W) -> Result<()> {
+        write_link(w, self)
+    }
+}
+
+impl Encode<DagJson> for [u8] {
+    fn encode<W: Write>(&self, _: DagJson, w: &mut W) -> Result<()> {
+        write_bytes(w, self)
+    }
+}
+
+impl Encode<DagJson> for Box<[u8]> {
+    fn encode<W: Write>(&self, _: DagJson, w: &mut W) -> Result<()> {
+        write_bytes(w, self)
+    }
+}
+
+impl<T: Encode<DagJson>> Encode<DagJson> for Option<T> {
+    fn encode<W: Write>(&self, c: DagJson, w: &mut W) -> Result<()> {
+        if let Some(value) = self {
+            value.encode(c, w)
+        } else {
+            write_null(w)
+        }
+    }
+}
+
+impl<T: Encode<DagJson>> Encode<DagJson> for Vec<T> {
+    fn encode<W: Write>(&self, c: DagJson, w: &mut W) -> Result<()> {
+        w.write_all(b"[")?;
+        for (i, value) in self.iter().enumerate() {
+            if i > 0 {
+                w.write_all(b",")?;
+            }
+            value.encode(c, w)?;
+        }
+        w.write_all(b"]")?;
+        Ok(())
+    }
+}
+
+impl<T: Encode<DagJson>> Encode<DagJson> for BTreeMap<String, T> {
+    fn encode<W: Write>(&self, c: DagJson, w: &mut W) -> Result<()> {
+        // Dag-json sorts keys bytewise, which is the order of the `BTreeMap`.
+        w.write_all(b"{")?;
+        let mut first = true;
+        for (key, value) in self {
+            write_key(w, key, &mut first)?;
+            value.encode(c, w)?;
+        }
+        w.write_all(b"}")?;
+        Ok(())
+    }
+}
+
+impl<T: Encode<DagJson>> Encode<DagJson> for Arc<T> {
+    fn encode<W: Write>(&self, c: DagJson, w: &mut W) -> Result<()> {
+        self.as_ref().encode(c, w)
+    }
+}
+
+impl Encode<DagJson> for () {
+    fn encode<W: Write>(&self, _: DagJson, w: &mut W) -> Result<()> {
+        w.write_all(b"[]")?;
+        Ok(())
+    }
+}
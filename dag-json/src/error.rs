@@ -0,0 +1,107 @@
+//! Json error types.
+use std::any::type_name;
+use thiserror::Error;
+
+/// Number out of range for the type, or a float that isn't finite.
+#[derive(Debug, Error)]
+#[error("Number out of range for {ty}.")]
+pub struct NumberOutOfRange {
+    /// Type.
+    pub ty: &'static str,
+}
+
+impl NumberOutOfRange {
+    /// Creates a new `NumberOutOfRange` error.
+    pub fn new<T>() -> Self {
+        Self {
+            ty: type_name::<T>(),
+        }
+    }
+}
+
+/// Number of items doesn't match the expected length.
+#[derive(Debug, Error)]
+#[error("Length out of range when decoding {ty}.")]
+pub struct LengthOutOfRange {
+    /// Type.
+    pub ty: &'static str,
+}
+
+impl LengthOutOfRange {
+    /// Creates a new `LengthOutOfRange` error.
+    pub fn new<T>() -> Self {
+        Self {
+            ty: type_name::<T>(),
+        }
+    }
+}
+
+/// Unexpected json character.
+#[derive(Debug, Error)]
+#[error("Unexpected character `{found}` when decoding `{ty}`.")]
+pub struct UnexpectedChar {
+    /// Character.
+    pub found: char,
+    /// Type.
+    pub ty: &'static str,
+}
+
+impl UnexpectedChar {
+    /// Creates a new `UnexpectedChar` error.
+    pub fn new<T>(found: u8) -> Self {
+        Self {
+            found: found.into(),
+            ty: type_name::<T>(),
+        }
+    }
+}
+
+/// Unexpected key.
+#[derive(Debug, Error)]
+#[error("Unexpected key `{key}` when decoding `{ty}`.")]
+pub struct UnexpectedKey {
+    /// Key.
+    pub key: String,
+    /// Type.
+    pub ty: &'static str,
+}
+
+impl UnexpectedKey {
+    /// Creates a new `UnexpectedKey` error.
+    pub fn new<T>(key: String) -> Self {
+        Self {
+            key,
+            ty: type_name::<T>(),
+        }
+    }
+}
+
+/// Missing key.
+#[derive(Debug, Error)]
+#[error("Missing key `{key}` for decoding `{ty}`.")]
+pub struct MissingKey {
+    /// Key.
+    pub key: &'static str,
+    /// Type.
+    pub ty: &'static str,
+}
+
+impl MissingKey {
+    /// Creates a new `MissingKey` error.
+    pub fn new<T>(key: &'static str) -> Self {
+        Self {
+            key,
+            ty: type_name::<T>(),
+        }
+    }
+}
+
+/// A duplicate key within a map.
+#[derive(Debug, Error)]
+#[error("Duplicate map key.")]
+pub struct DuplicateKey;
+
+/// Bytes that aren't base64 encoded.
+#[derive(Debug, Error)]
+#[error("Bytes must be base64 encoded.")]
+pub struct InvalidBytes;
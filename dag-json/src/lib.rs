@@ -7,11 +7,15 @@ use libipld_core::cid::Cid;
 use libipld_core::codec::{Codec, Decode, Encode, References};
 use libipld_core::error::{Result, UnsupportedCodec};
 use libipld_core::ipld::Ipld;
+use libipld_core::ipld_ref::IpldRef;
 // TODO vmx 2020-05-28: Don't expose the `serde_json` error directly, but wrap it in a custom one
 pub use serde_json::Error;
 use std::io::{Read, Seek, Write};
 
 mod codec;
+pub mod decode;
+pub mod encode;
+pub mod error;
 
 /// Json codec.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -19,6 +23,13 @@ pub struct DagJsonCodec;
 
 impl Codec for DagJsonCodec {}
 
+impl DagJsonCodec {
+    /// Decodes an [`IpldRef`], borrowing strings from `bytes` where they contain no escapes.
+    pub fn decode_ref<'a>(&self, bytes: &'a [u8]) -> Result<IpldRef<'a>> {
+        codec::decode_ref(bytes)
+    }
+}
+
 impl From<DagJsonCodec> for u64 {
     fn from(_: DagJsonCodec) -> Self {
         0x0129
@@ -41,7 +52,7 @@ impl Encode<DagJsonCodec> for Ipld {
 
 impl Decode<DagJsonCodec> for Ipld {
     fn decode<R: Read + Seek>(_: DagJsonCodec, r: &mut R) -> Result<Self> {
-        Ok(codec::decode(r)?)
+        codec::decode(r)
     }
 }
 
@@ -56,6 +67,11 @@ impl References<DagJsonCodec> for Ipld {
     }
 }
 
+/// Marker trait for types supporting the `DagJsonCodec`.
+pub trait DagJson: Encode<DagJsonCodec> + Decode<DagJsonCodec> {}
+
+impl<T: Encode<DagJsonCodec> + Decode<DagJsonCodec>> DagJson for T {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -90,4 +106,50 @@ mod tests {
         let contact_decoded: Ipld = DagJsonCodec.decode(&contact_encoded).unwrap();
         assert_eq!(contact_decoded, contact);
     }
+
+    #[test]
+    fn error_location() {
+        let json = br#"{"foo": [1, {"/": "invalid"}]}"#;
+        let err = DagJsonCodec.decode::<Ipld>(json).unwrap_err();
+        let location = err
+            .downcast_ref::<libipld_core::error::DecodeLocation>()
+            .unwrap();
+        assert_eq!(location.path(), ".foo[1]");
+        assert_eq!(location.offset, Some(27));
+        err.downcast::<Error>().unwrap();
+
+        let json = b"[1,\n 2,\n x]";
+        let err = DagJsonCodec.decode::<Ipld>(json).unwrap_err();
+        let location = err
+            .downcast_ref::<libipld_core::error::DecodeLocation>()
+            .unwrap();
+        assert_eq!(location.path(), "[2]");
+        assert_eq!(location.offset, Some(9));
+
+        let json = br#"{"a": {"b": 1, "c": [x]}}"#;
+        let err = DagJsonCodec.decode_ref(json).unwrap_err();
+        let location = err
+            .downcast_ref::<libipld_core::error::DecodeLocation>()
+            .unwrap();
+        assert_eq!(location.path(), ".a.c[0]");
+    }
+
+    #[test]
+    fn decode_ref() {
+        use std::borrow::Cow;
+
+        let json = br#"{"escaped": "a\"b", "plain": "ab", "bytes": {"/": {"bytes": "AAEC"}}}"#;
+        let ipld_ref = DagJsonCodec.decode_ref(json).unwrap();
+        assert!(matches!(
+            ipld_ref.get("plain").unwrap(),
+            IpldRef::String(Cow::Borrowed("ab"))
+        ));
+        assert!(matches!(
+            ipld_ref.get("escaped").unwrap(),
+            IpldRef::String(Cow::Owned(_))
+        ));
+        let ipld: Ipld = DagJsonCodec.decode(json).unwrap();
+        assert_eq!(ipld_ref.into_owned(), ipld);
+        assert_eq!(ipld.get("bytes").unwrap(), &Ipld::Bytes(vec![0, 1, 2]));
+    }
 }
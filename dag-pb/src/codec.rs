@@ -3,7 +3,7 @@ use std::collections::BTreeMap;
 
 use bytes::Bytes;
 use libipld_core::cid::Cid;
-use libipld_core::error::{Result, TypeError, TypeErrorType};
+use libipld_core::error::{with_location, PathSegment, Result, TypeError, TypeErrorType};
 use libipld_core::ipld::Ipld;
 use quick_protobuf::sizeofs::{sizeof_len, sizeof_varint};
 use quick_protobuf::{BytesReader, MessageRead, MessageWrite, Writer, WriterBackend};
@@ -45,8 +45,7 @@ impl PbNode {
 
     /// Deserializes a `PbNode` from bytes.
     pub fn from_bytes(buf: Bytes) -> Result<Self> {
-        let mut reader = BytesReader::from_bytes(&buf);
-        let node = PbNodeRef::from_reader(&mut reader, &buf)?;
+        let node = PbNodeRef::from_bytes(&buf)?;
         let data = node.data.map(|d| buf.slice_ref(d));
 
         Ok(PbNode {
@@ -281,33 +280,49 @@ impl MessageWrite for PbLink {
     }
 }
 
-impl<'a> MessageRead<'a> for PbNodeRef<'a> {
-    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> quick_protobuf::Result<Self> {
+impl<'a> PbNodeRef<'a> {
+    /// Deserializes a `PbNodeRef` from bytes.
+    ///
+    /// Errors carry a [`DecodeLocation`](libipld_core::error::DecodeLocation) with the offset of
+    /// the field that failed to decode.
+    fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+        let mut r = BytesReader::from_bytes(bytes);
         let mut msg = Self::default();
         let mut links_before_data = false;
         while !r.is_eof() {
-            match r.next_tag(bytes) {
+            // The reader spans the whole buffer, so the remaining length gives the offset.
+            let offset = (bytes.len() - r.len()) as u64;
+            let mut segments = vec![];
+            let res = match r.next_tag(bytes) {
                 Ok(18) => {
+                    segments.push(PathSegment::Key("Links".into()));
+                    segments.push(PathSegment::Index(msg.links.len()));
                     // Links and data might be in any order, but they may not be interleaved.
                     if links_before_data {
-                        return Err(quick_protobuf::Error::Message(
+                        Err(quick_protobuf::Error::Message(
                             "duplicate Links section".to_string(),
-                        ));
+                        ))
+                    } else {
+                        r.read_message::<PbLink>(bytes)
+                            .map(|link| msg.links.push(link))
                     }
-                    msg.links.push(r.read_message::<PbLink>(bytes)?)
                 }
                 Ok(10) => {
-                    msg.data = Some(r.read_bytes(bytes)?);
-                    if !msg.links.is_empty() {
-                        links_before_data = true
-                    }
-                }
-                Ok(_) => {
-                    return Err(quick_protobuf::Error::Message(
-                        "unexpected bytes".to_string(),
-                    ))
+                    segments.push(PathSegment::Key("Data".into()));
+                    r.read_bytes(bytes).map(|data| {
+                        msg.data = Some(data);
+                        if !msg.links.is_empty() {
+                            links_before_data = true
+                        }
+                    })
                 }
-                Err(e) => return Err(e),
+                Ok(_) => Err(quick_protobuf::Error::Message(
+                    "unexpected bytes".to_string(),
+                )),
+                Err(e) => Err(e),
+            };
+            if let Err(err) = res {
+                return Err(with_location(err.into(), Some(offset), segments));
             }
         }
         Ok(msg)
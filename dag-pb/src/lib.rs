@@ -89,4 +89,28 @@ mod tests {
         let data2 = DagPbCodec.decode(&bytes).unwrap();
         assert_eq!(data, data2);
     }
+
+    #[test]
+    fn error_location() {
+        let digest = Code::Blake3_256.digest(&b"cid"[..]);
+        let cid = Cid::new_v1(0x55, digest);
+        let mut link = BTreeMap::<String, Ipld>::new();
+        link.insert("Hash".to_string(), cid.into());
+        let mut node = BTreeMap::<String, Ipld>::new();
+        node.insert(
+            "Links".to_string(),
+            vec![Ipld::from(link.clone()); 2].into(),
+        );
+        let mut bytes = DagPbCodec.encode(&Ipld::from(node)).unwrap();
+        // Corrupt the cid of the second link.
+        let second = bytes.len() / 2;
+        bytes[second + 4] = 0xff;
+
+        let err = DagPbCodec.decode::<Ipld>(&bytes).unwrap_err();
+        let location = err
+            .downcast_ref::<libipld_core::error::DecodeLocation>()
+            .unwrap();
+        assert_eq!(location.path(), ".Links[1]");
+        assert_eq!(location.offset, Some(second as u64));
+    }
 }
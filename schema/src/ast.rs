@@ -0,0 +1,348 @@
+//! Typed schema AST.
+//!
+//! The types mirror the schema-schema (the schema describing schemas), so a [`Schema`] converts
+//! losslessly to and from its [`Ipld`] form.
+use libipld_core::ipld::Ipld;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A set of named type definitions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schema {
+    /// Type definitions by name.
+    pub types: BTreeMap<String, TypeDefn>,
+}
+
+/// A type definition.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeDefn {
+    /// Boolean type.
+    Bool,
+    /// String type.
+    String,
+    /// Bytes type.
+    Bytes,
+    /// Integer type.
+    Int,
+    /// Float type.
+    Float,
+    /// Map type.
+    Map(TypeMap),
+    /// List type.
+    List(TypeList),
+    /// Link type.
+    Link(TypeLink),
+    /// Union type.
+    Union(TypeUnion),
+    /// Struct type.
+    Struct(TypeStruct),
+    /// Enum type.
+    Enum(TypeEnum),
+    /// Unit type.
+    Unit(UnitRepr),
+    /// Any type.
+    Any,
+    /// Copy of another named type.
+    Copy(String),
+}
+
+/// A reference to a named type or an inline anonymous type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeTerm {
+    /// Named type.
+    Name(String),
+    /// Inline map type.
+    Map(Box<TypeMap>),
+    /// Inline list type.
+    List(Box<TypeList>),
+    /// Inline link type.
+    Link(TypeLink),
+}
+
+/// Map type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeMap {
+    /// Name of the key type.
+    pub key_type: String,
+    /// Value type.
+    pub value_type: TypeTerm,
+    /// Whether values may be null.
+    pub value_nullable: bool,
+    /// Representation strategy.
+    pub representation: MapRepr,
+}
+
+/// Map representation strategy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MapRepr {
+    /// Data model map.
+    Map,
+    /// String of delimited key value pairs.
+    StringPairs {
+        /// Delimiter between a key and its value.
+        inner_delim: String,
+        /// Delimiter between entries.
+        entry_delim: String,
+    },
+    /// List of `[key, value]` lists.
+    ListPairs,
+}
+
+/// List type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeList {
+    /// Value type.
+    pub value_type: TypeTerm,
+    /// Whether values may be null.
+    pub value_nullable: bool,
+}
+
+/// Link type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeLink {
+    /// Name of the type the link is expected to point to, `Any` if unconstrained.
+    pub expected_type: String,
+}
+
+impl Default for TypeLink {
+    fn default() -> Self {
+        Self {
+            expected_type: "Any".into(),
+        }
+    }
+}
+
+/// Struct type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeStruct {
+    /// Fields by name.
+    pub fields: BTreeMap<String, StructField>,
+    /// Representation strategy.
+    pub representation: StructRepr,
+}
+
+/// Struct field.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructField {
+    /// Field type.
+    pub ty: TypeTerm,
+    /// Whether the field may be absent.
+    pub optional: bool,
+    /// Whether the field may be null.
+    pub nullable: bool,
+}
+
+/// Struct representation strategy.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StructRepr {
+    /// Map keyed by field name.
+    Map(BTreeMap<String, FieldDetails>),
+    /// List of field values.
+    Tuple {
+        /// Order of the fields.
+        field_order: Vec<String>,
+    },
+    /// String of delimited key value pairs.
+    StringPairs {
+        /// Delimiter between a key and its value.
+        inner_delim: String,
+        /// Delimiter between entries.
+        entry_delim: String,
+    },
+    /// String of joined field values.
+    StringJoin {
+        /// Delimiter between values.
+        join: String,
+        /// Order of the fields.
+        field_order: Vec<String>,
+    },
+    /// List of `[key, value]` lists.
+    ListPairs,
+}
+
+/// Representation details of a field in a map represented struct.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FieldDetails {
+    /// Key used in the representation.
+    pub rename: Option<String>,
+    /// Value the field takes when it is absent from the representation.
+    pub implicit: Option<Ipld>,
+}
+
+/// Enum type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeEnum {
+    /// Members in declaration order.
+    pub members: Vec<String>,
+    /// Representation strategy.
+    pub representation: EnumRepr,
+}
+
+/// Enum representation strategy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EnumRepr {
+    /// String values, defaulting to the member name.
+    String(BTreeMap<String, String>),
+    /// Integer values of every member.
+    Int(BTreeMap<String, i128>),
+}
+
+/// Union type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeUnion {
+    /// Members in declaration order.
+    pub members: Vec<UnionMember>,
+    /// Representation strategy.
+    pub representation: UnionRepr,
+}
+
+/// Union member.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UnionMember {
+    /// Named type.
+    Name(String),
+    /// Inline link type.
+    Link(String),
+}
+
+/// Union representation strategy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UnionRepr {
+    /// Member is selected by the data model kind.
+    Kinded(BTreeMap<Kind, UnionMember>),
+    /// Single entry map keyed by discriminant.
+    Keyed(BTreeMap<String, UnionMember>),
+    /// Map with a discriminant entry and a content entry.
+    Envelope {
+        /// Key of the discriminant entry.
+        discriminant_key: String,
+        /// Key of the content entry.
+        content_key: String,
+        /// Members by discriminant.
+        discriminant_table: BTreeMap<String, UnionMember>,
+    },
+    /// Map with a discriminant entry next to the member's fields.
+    Inline {
+        /// Key of the discriminant entry.
+        discriminant_key: String,
+        /// Members by discriminant.
+        discriminant_table: BTreeMap<String, UnionMember>,
+    },
+    /// String with a discriminant prefix.
+    StringPrefix(BTreeMap<String, UnionMember>),
+    /// Bytes with a discriminant prefix, given as hex.
+    BytesPrefix(BTreeMap<String, UnionMember>),
+}
+
+/// Unit representation strategy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnitRepr {
+    /// Represented as null.
+    Null,
+    /// Represented as true.
+    True,
+    /// Represented as false.
+    False,
+    /// Represented as an empty map.
+    EmptyMap,
+}
+
+impl UnitRepr {
+    /// Returns the name of the representation.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::True => "true",
+            Self::False => "false",
+            Self::EmptyMap => "emptymap",
+        }
+    }
+
+    /// Parses the name of a representation.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "null" => Self::Null,
+            "true" => Self::True,
+            "false" => Self::False,
+            "emptymap" => Self::EmptyMap,
+            _ => return None,
+        })
+    }
+}
+
+/// Data model kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Kind {
+    /// Null kind.
+    Null,
+    /// Boolean kind.
+    Bool,
+    /// Integer kind.
+    Int,
+    /// Float kind.
+    Float,
+    /// String kind.
+    String,
+    /// Bytes kind.
+    Bytes,
+    /// List kind.
+    List,
+    /// Map kind.
+    Map,
+    /// Link kind.
+    Link,
+}
+
+impl Kind {
+    /// Returns the name of the kind.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Bool => "bool",
+            Self::Int => "int",
+            Self::Float => "float",
+            Self::String => "string",
+            Self::Bytes => "bytes",
+            Self::List => "list",
+            Self::Map => "map",
+            Self::Link => "link",
+        }
+    }
+
+    /// Parses the name of a kind.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "null" => Self::Null,
+            "bool" => Self::Bool,
+            "int" => Self::Int,
+            "float" => Self::Float,
+            "string" => Self::String,
+            "bytes" => Self::Bytes,
+            "list" => Self::List,
+            "map" => Self::Map,
+            "link" => Self::Link,
+            _ => return None,
+        })
+    }
+
+    /// Returns the kind of an ipld value.
+    pub fn of(ipld: &Ipld) -> Self {
+        match ipld {
+            Ipld::Null => Self::Null,
+            Ipld::Bool(_) => Self::Bool,
+            Ipld::Integer(_) => Self::Int,
+            Ipld::Float(_) => Self::Float,
+            Ipld::String(_) => Self::String,
+            Ipld::Bytes(_) => Self::Bytes,
+            Ipld::List(_) => Self::List,
+            Ipld::Map(_) => Self::Map,
+            Ipld::Link(_) => Self::Link,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
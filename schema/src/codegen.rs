@@ -0,0 +1,517 @@
+//! Rust code generation from schemas.
+//!
+//! Generated types derive `DagCbor` from the `libipld` crate, so the crate including them needs
+//! `libipld` with the `derive` feature as a dependency. Fields and union members through which a
+//! type would contain itself are boxed.
+use crate::ast::*;
+use crate::error::UnsupportedType;
+use libipld_core::error::Result;
+use libipld_core::ipld::Ipld;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Generates rust types for the schema at `input` and writes them to `output`.
+///
+/// Meant to be called from a build script with an `output` in `OUT_DIR`, which is then
+/// included with `include!(concat!(env!("OUT_DIR"), "/schema.rs"))`.
+pub fn compile(input: impl AsRef<Path>, output: impl AsRef<Path>) -> Result<()> {
+    let input = input.as_ref();
+    println!("cargo:rerun-if-changed={}", input.display());
+    let schema: Schema = std::fs::read_to_string(input)?.parse()?;
+    std::fs::write(output, generate(&schema)?)?;
+    Ok(())
+}
+
+/// Generates rust types for every type of the schema.
+pub fn generate(schema: &Schema) -> Result<String> {
+    let mut out = String::from("// Generated from an IPLD schema, do not edit.\n");
+    for (name, ty) in &schema.types {
+        if is_prelude(name, ty) {
+            continue;
+        }
+        out.push('\n');
+        gen_type(&mut out, schema, name, ty).map_err(|reason| UnsupportedType {
+            name: name.clone(),
+            reason,
+        })?;
+    }
+    Ok(out)
+}
+
+const DERIVE: &str = "#[derive(Clone, Debug, PartialEq, libipld::DagCbor)]";
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while", "abstract", "become", "box", "do", "final", "macro", "override", "priv",
+    "try", "typeof", "unsized", "virtual", "yield",
+];
+
+/// Whether the definition is a prelude type that maps to a rust type directly.
+fn is_prelude(name: &str, ty: &TypeDefn) -> bool {
+    matches!(
+        (name, ty),
+        ("Bool", TypeDefn::Bool)
+            | ("String", TypeDefn::String)
+            | ("Bytes", TypeDefn::Bytes)
+            | ("Int", TypeDefn::Int)
+            | ("Float", TypeDefn::Float)
+            | ("Any", TypeDefn::Any)
+    ) || (name == "Link" && *ty == TypeDefn::Link(TypeLink::default()))
+}
+
+fn ident(name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    if KEYWORDS.contains(&ident.as_str()) {
+        ident.insert_str(0, "r#");
+    }
+    ident
+}
+
+fn field_ident(name: &str) -> String {
+    let mut snake = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_uppercase() && prev_lower {
+            snake.push('_');
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        snake.extend(c.to_lowercase());
+    }
+    ident(&snake)
+}
+
+fn name_type(name: &str) -> String {
+    match name {
+        "Bool" => "bool".into(),
+        "String" => "String".into(),
+        "Bytes" => "Box<[u8]>".into(),
+        "Int" => "i64".into(),
+        "Float" => "f64".into(),
+        "Link" => "libipld::Cid".into(),
+        "Any" => "libipld::Ipld".into(),
+        "Map" => "std::collections::BTreeMap<String, libipld::Ipld>".into(),
+        "List" => "Vec<libipld::Ipld>".into(),
+        _ => ident(name),
+    }
+}
+
+fn link_type(link: &TypeLink) -> String {
+    if link.expected_type == "Any" {
+        "libipld::Cid".into()
+    } else {
+        format!("libipld::Link<{}>", name_type(&link.expected_type))
+    }
+}
+
+fn nullable(ty: String, nullable: bool) -> String {
+    if nullable {
+        format!("Option<{}>", ty)
+    } else {
+        ty
+    }
+}
+
+/// Returns the named types that the type `name` contains by value, so that they add to its size.
+fn inline_types<'a>(schema: &'a Schema, name: &str) -> Vec<&'a str> {
+    match schema.types.get(name) {
+        Some(TypeDefn::Struct(ty)) => ty
+            .fields
+            .values()
+            .filter_map(|field| match &field.ty {
+                TypeTerm::Name(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect(),
+        Some(TypeDefn::Union(ty)) => ty
+            .members
+            .iter()
+            .filter_map(|member| match member {
+                UnionMember::Name(name) => Some(name.as_str()),
+                UnionMember::Link(_) => None,
+            })
+            .collect(),
+        Some(TypeDefn::Copy(from)) => vec![from.as_str()],
+        _ => Vec::new(),
+    }
+}
+
+/// Whether a value of type `to` inside the type `from` has to be boxed, because `to` contains
+/// `from` by value. Cycles through lists, maps and links don't need a box.
+fn needs_box(schema: &Schema, from: &str, to: &str) -> bool {
+    let mut stack = vec![to];
+    let mut seen = BTreeSet::new();
+    while let Some(name) = stack.pop() {
+        if name == from {
+            return true;
+        }
+        if seen.insert(name) {
+            stack.extend(inline_types(schema, name));
+        }
+    }
+    false
+}
+
+/// Returns the type of a field or union member of the type `from`.
+fn inline_type(schema: &Schema, from: &str, term: &TypeTerm) -> Result<String, String> {
+    match term {
+        TypeTerm::Name(to) if needs_box(schema, from, to) => Ok(format!("Box<{}>", name_type(to))),
+        _ => term_type(schema, term),
+    }
+}
+
+fn term_type(schema: &Schema, term: &TypeTerm) -> Result<String, String> {
+    Ok(match term {
+        TypeTerm::Name(name) => name_type(name),
+        TypeTerm::Map(ty) => map_type(schema, ty)?,
+        TypeTerm::List(ty) => list_type(schema, ty)?,
+        TypeTerm::Link(link) => link_type(link),
+    })
+}
+
+/// Map keys are encoded as strings, so the key type has to be a string.
+fn key_type(schema: &Schema, name: &str) -> Result<String, String> {
+    let mut resolved = name;
+    for _ in 0..=schema.types.len() {
+        match schema.types.get(resolved) {
+            Some(TypeDefn::Copy(from)) => resolved = from,
+            Some(TypeDefn::String) => return Ok(name_type(name)),
+            None if resolved == "String" => return Ok(name_type(name)),
+            _ => break,
+        }
+    }
+    Err(format!("map keys of type `{}` are not supported", name))
+}
+
+fn map_type(schema: &Schema, ty: &TypeMap) -> Result<String, String> {
+    match ty.representation {
+        MapRepr::Map => {}
+        MapRepr::StringPairs { .. } => {
+            return Err("the stringpairs representation of maps is not supported".into())
+        }
+        MapRepr::ListPairs => {
+            return Err("the listpairs representation of maps needs a named type".into())
+        }
+    }
+    let key = key_type(schema, &ty.key_type)?;
+    let value = nullable(term_type(schema, &ty.value_type)?, ty.value_nullable);
+    Ok(format!("std::collections::BTreeMap<{}, {}>", key, value))
+}
+
+fn list_type(schema: &Schema, ty: &TypeList) -> Result<String, String> {
+    let value = nullable(term_type(schema, &ty.value_type)?, ty.value_nullable);
+    Ok(format!("Vec<{}>", value))
+}
+
+fn literal(ipld: &Ipld) -> Result<String, String> {
+    Ok(match ipld {
+        Ipld::Bool(b) => b.to_string(),
+        Ipld::Integer(i) => i.to_string(),
+        Ipld::String(s) => format!("String::from({:?})", s),
+        _ => return Err(format!("unsupported implicit value {:?}", ipld)),
+    })
+}
+
+fn gen_type(out: &mut String, schema: &Schema, name: &str, ty: &TypeDefn) -> Result<(), String> {
+    let schema_name = name;
+    let name = ident(name);
+    let alias = match ty {
+        TypeDefn::Bool => name_type("Bool"),
+        TypeDefn::String => name_type("String"),
+        TypeDefn::Bytes => name_type("Bytes"),
+        TypeDefn::Int => name_type("Int"),
+        TypeDefn::Float => name_type("Float"),
+        TypeDefn::Any => name_type("Any"),
+        TypeDefn::Map(ty) if ty.representation == MapRepr::ListPairs => {
+            let key = key_type(schema, &ty.key_type)?;
+            let value = nullable(term_type(schema, &ty.value_type)?, ty.value_nullable);
+            writeln!(
+                out,
+                "{}\n#[ipld(repr = \"value\")]\npub struct {}(\n    #[ipld(repr = \"listpairs\")] pub std::collections::BTreeMap<{}, {}>,\n);",
+                DERIVE, name, key, value
+            )
+            .unwrap();
+            return Ok(());
+        }
+        TypeDefn::Map(ty) => map_type(schema, ty)?,
+        TypeDefn::List(ty) => list_type(schema, ty)?,
+        TypeDefn::Link(link) => link_type(link),
+        TypeDefn::Copy(from) => name_type(from),
+        TypeDefn::Struct(ty) => return gen_struct(out, schema, schema_name, ty),
+        TypeDefn::Enum(ty) => return gen_enum(out, &name, ty),
+        TypeDefn::Union(ty) => return gen_union(out, schema, schema_name, ty),
+        TypeDefn::Unit(UnitRepr::Null) => {
+            writeln!(out, "{}\npub struct {};", DERIVE, name).unwrap();
+            return Ok(());
+        }
+        TypeDefn::Unit(repr) => {
+            return Err(format!(
+                "the {} representation of units is not supported",
+                repr.as_str()
+            ))
+        }
+    };
+    writeln!(out, "pub type {} = {};", name, alias).unwrap();
+    Ok(())
+}
+
+fn gen_struct(
+    out: &mut String,
+    schema: &Schema,
+    name: &str,
+    ty: &TypeStruct,
+) -> Result<(), String> {
+    let mut fields = String::new();
+    let repr = match &ty.representation {
+        StructRepr::Map(details) => {
+            gen_map_fields(&mut fields, schema, name, ty, details)?;
+            "repr = \"map\"".to_string()
+        }
+        StructRepr::ListPairs => {
+            gen_map_fields(&mut fields, schema, name, ty, &Default::default())?;
+            "repr = \"listpairs\"".to_string()
+        }
+        StructRepr::Tuple { field_order } => {
+            gen_ordered_fields(&mut fields, schema, name, ty, field_order, "tuple", false)?;
+            "repr = \"tuple\"".to_string()
+        }
+        StructRepr::StringJoin { join, field_order } => {
+            gen_ordered_fields(
+                &mut fields,
+                schema,
+                name,
+                ty,
+                field_order,
+                "stringjoin",
+                true,
+            )?;
+            format!("repr = \"stringjoin\", join = {:?}", join)
+        }
+        StructRepr::StringPairs {
+            inner_delim,
+            entry_delim,
+        } => {
+            let field_order: Vec<String> = ty.fields.keys().cloned().collect();
+            gen_ordered_fields(
+                &mut fields,
+                schema,
+                name,
+                ty,
+                &field_order,
+                "stringpairs",
+                true,
+            )?;
+            format!(
+                "repr = \"stringpairs\", inner_delim = {:?}, entry_delim = {:?}",
+                inner_delim, entry_delim
+            )
+        }
+    };
+    writeln!(
+        out,
+        "{}\n#[ipld({})]\npub struct {} {{\n{}}}",
+        DERIVE,
+        repr,
+        ident(name),
+        fields
+    )
+    .unwrap();
+    Ok(())
+}
+
+fn gen_map_fields(
+    fields: &mut String,
+    schema: &Schema,
+    name: &str,
+    ty: &TypeStruct,
+    details: &BTreeMap<String, FieldDetails>,
+) -> Result<(), String> {
+    for (key, field) in &ty.fields {
+        let details = details.get(key).cloned().unwrap_or_default();
+        let ident = field_ident(key);
+        let rename = details.rename.unwrap_or_else(|| key.clone());
+        if rename != ident {
+            writeln!(fields, "    #[ipld(rename = {:?})]", rename).unwrap();
+        }
+        let mut ty = nullable(inline_type(schema, name, &field.ty)?, field.nullable);
+        if let Some(implicit) = &details.implicit {
+            writeln!(fields, "    #[ipld(default = {})]", literal(implicit)?).unwrap();
+        } else if field.optional {
+            writeln!(fields, "    #[ipld(optional)]").unwrap();
+            ty = format!("Option<{}>", ty);
+        }
+        writeln!(fields, "    pub {}: {},", ident, ty).unwrap();
+    }
+    Ok(())
+}
+
+/// Fields of string representations must be plain named types, which are
+/// formatted with `Display` and parsed with `FromStr`.
+fn gen_ordered_fields(
+    fields: &mut String,
+    schema: &Schema,
+    name: &str,
+    ty: &TypeStruct,
+    field_order: &[String],
+    repr: &str,
+    string: bool,
+) -> Result<(), String> {
+    for key in field_order {
+        let field = ty
+            .fields
+            .get(key)
+            .ok_or_else(|| format!("field order lists unknown field `{}`", key))?;
+        if field.optional {
+            return Err(format!(
+                "optional field `{}` is not supported by the {} representation",
+                key, repr
+            ));
+        }
+        if string && (field.nullable || !matches!(field.ty, TypeTerm::Name(_))) {
+            return Err(format!(
+                "field `{}` must be a scalar to use the {} representation",
+                key, repr
+            ));
+        }
+        let ty = nullable(inline_type(schema, name, &field.ty)?, field.nullable);
+        writeln!(fields, "    pub {}: {},", field_ident(key), ty).unwrap();
+    }
+    Ok(())
+}
+
+fn gen_enum(out: &mut String, name: &str, ty: &TypeEnum) -> Result<(), String> {
+    let mut variants = String::new();
+    let repr = match &ty.representation {
+        EnumRepr::String(values) => {
+            for member in &ty.members {
+                let variant = ident(member);
+                let value = values.get(member).unwrap_or(member);
+                if *value != variant {
+                    writeln!(variants, "    #[ipld(rename = {:?})]", value).unwrap();
+                }
+                writeln!(variants, "    {},", variant).unwrap();
+            }
+            "string"
+        }
+        EnumRepr::Int(values) => {
+            for member in &ty.members {
+                let value = *values
+                    .get(member)
+                    .ok_or_else(|| format!("member `{}` has no value", member))?;
+                if value < 0 {
+                    return Err(format!("negative value of `{}` is not supported", member));
+                }
+                writeln!(variants, "    {} = {},", ident(member), value).unwrap();
+            }
+            "int"
+        }
+    };
+    writeln!(
+        out,
+        "#[derive(Clone, Copy, Debug, Eq, PartialEq, libipld::DagCbor)]\n\
+         #[ipld(repr = {:?})]\npub enum {} {{\n{}}}",
+        repr, name, variants
+    )
+    .unwrap();
+    Ok(())
+}
+
+fn gen_union(out: &mut String, schema: &Schema, name: &str, ty: &TypeUnion) -> Result<(), String> {
+    fn table(table: &BTreeMap<String, UnionMember>) -> Vec<(String, &UnionMember)> {
+        table
+            .iter()
+            .map(|(key, member)| (key.clone(), member))
+            .collect()
+    }
+    let kinded = matches!(ty.representation, UnionRepr::Kinded(_));
+    let (repr, keys) = match &ty.representation {
+        UnionRepr::Keyed(keys) => ("\"keyed\"".to_string(), table(keys)),
+        UnionRepr::Kinded(kinds) => (
+            "\"kinded\"".to_string(),
+            kinds
+                .iter()
+                .map(|(kind, member)| (kind.to_string(), member))
+                .collect(),
+        ),
+        UnionRepr::Envelope {
+            discriminant_key,
+            content_key,
+            discriminant_table,
+        } => (
+            format!(
+                "\"envelope\", tag = {:?}, content = {:?}",
+                discriminant_key, content_key
+            ),
+            table(discriminant_table),
+        ),
+        UnionRepr::Inline { .. } => return Err(unsupported_union_repr("inline")),
+        UnionRepr::StringPrefix(_) => return Err(unsupported_union_repr("stringprefix")),
+        UnionRepr::BytesPrefix(_) => return Err(unsupported_union_repr("bytesprefix")),
+    };
+    let mut variants = String::new();
+    for member in &ty.members {
+        let (variant, ty) = match member {
+            UnionMember::Name(to) => (
+                ident(to),
+                inline_type(schema, name, &TypeTerm::Name(to.clone()))?,
+            ),
+            UnionMember::Link(expected_type) => (
+                format!("{}Link", ident(expected_type)),
+                link_type(&TypeLink {
+                    expected_type: expected_type.clone(),
+                }),
+            ),
+        };
+        let key = keys
+            .iter()
+            .find(|(_, m)| *m == member)
+            .map(|(key, _)| key.as_str())
+            .ok_or_else(|| format!("member `{}` is missing from the representation", variant))?;
+        if kinded {
+            writeln!(variants, "    #[ipld(kind = {:?})]", key).unwrap();
+        } else if key != variant {
+            writeln!(variants, "    #[ipld(rename = {:?})]", key).unwrap();
+        }
+        writeln!(
+            variants,
+            "    #[ipld(repr = \"value\")]\n    {}({}),",
+            variant, ty
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "{}\n#[ipld(repr = {})]\npub enum {} {{\n{}}}",
+        DERIVE,
+        repr,
+        ident(name),
+        variants
+    )
+    .unwrap();
+    Ok(())
+}
+
+fn unsupported_union_repr(repr: &str) -> String {
+    format!("the {} representation of unions is not supported", repr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_ident() {
+        assert_eq!(field_ident("fieldOrder"), "field_order");
+        assert_eq!(field_ident("type"), "r#type");
+        assert_eq!(field_ident("v2Name"), "v2_name");
+        assert_eq!(field_ident("ID"), "id");
+    }
+}
@@ -0,0 +1,54 @@
+//! Schema error definitions.
+use libipld_core::error::PathSegment;
+use thiserror::Error;
+
+/// The schema DSL could not be parsed.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[error("Parse error at {line}:{column}: {message}")]
+pub struct ParseError {
+    /// Line of the offending token, starting at 1.
+    pub line: usize,
+    /// Column of the offending token, starting at 1.
+    pub column: usize,
+    /// What went wrong.
+    pub message: String,
+}
+
+/// A schema is malformed.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[error("Invalid schema: {0}")]
+pub struct InvalidSchema(pub String);
+
+/// A value does not match its schema.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[error("Invalid value at `{}`: {message}", path_to_string(.segments))]
+pub struct ValidationError {
+    /// Data model path to the offending value.
+    pub segments: Vec<PathSegment>,
+    /// What went wrong.
+    pub message: String,
+}
+
+impl ValidationError {
+    /// Returns the data model path, for example `.foo[3].bar`. The root is `.`.
+    pub fn path(&self) -> String {
+        path_to_string(&self.segments)
+    }
+}
+
+fn path_to_string(path: &[PathSegment]) -> String {
+    if path.is_empty() {
+        return ".".into();
+    }
+    path.iter().map(|segment| segment.to_string()).collect()
+}
+
+/// No rust type can be generated for a schema type.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[error("Cannot generate `{name}`: {reason}")]
+pub struct UnsupportedType {
+    /// Name of the schema type.
+    pub name: String,
+    /// Why it is unsupported.
+    pub reason: String,
+}
@@ -0,0 +1,581 @@
+//! Conversion between the schema AST and its schema-schema [`Ipld`] form.
+use crate::ast::*;
+use crate::error::InvalidSchema;
+use libipld_core::error::{
+    with_path_segment, PathSegment, Result, TypeError, TypeErrorType, UnknownVariant,
+};
+use libipld_core::ipld::Ipld;
+use std::collections::BTreeMap;
+
+fn map<const N: usize>(entries: [(&str, Ipld); N]) -> Ipld {
+    Ipld::Map(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+    )
+}
+
+fn string(s: &str) -> Ipld {
+    Ipld::String(s.to_string())
+}
+
+fn strings(list: &[String]) -> Ipld {
+    Ipld::List(list.iter().map(|s| string(s)).collect())
+}
+
+fn table<K: AsRef<str>, V>(table: &BTreeMap<K, V>, f: impl Fn(&V) -> Ipld) -> Ipld {
+    Ipld::Map(
+        table
+            .iter()
+            .map(|(key, value)| (key.as_ref().to_string(), f(value)))
+            .collect(),
+    )
+}
+
+/// Sets `key` to true if `value` is, leaving implicit false values out.
+fn set_flag(ipld: &mut Ipld, key: &str, value: bool) {
+    if let (Ipld::Map(map), true) = (ipld, value) {
+        map.insert(key.into(), Ipld::Bool(true));
+    }
+}
+
+impl Schema {
+    /// Returns the schema-schema form of the schema.
+    pub fn to_ipld(&self) -> Ipld {
+        map([("types", table(&self.types, TypeDefn::to_ipld))])
+    }
+
+    /// Reads a schema from its schema-schema form.
+    ///
+    /// Struct fields are kept in a map, so the declaration order is only preserved through the
+    /// `fieldOrder` of tuple and stringjoin representations.
+    pub fn from_ipld(ipld: &Ipld) -> Result<Self> {
+        let types = field(as_map(ipld)?, "types", |ipld| {
+            read_table(ipld, TypeDefn::from_ipld)
+        })?;
+        Ok(Self { types })
+    }
+}
+
+impl TypeDefn {
+    fn to_ipld(&self) -> Ipld {
+        let (kind, body) = match self {
+            Self::Bool => ("bool", map([])),
+            Self::String => ("string", map([])),
+            Self::Bytes => ("bytes", map([])),
+            Self::Int => ("int", map([])),
+            Self::Float => ("float", map([])),
+            Self::Map(ty) => ("map", ty.to_ipld()),
+            Self::List(ty) => ("list", ty.to_ipld()),
+            Self::Link(ty) => ("link", ty.to_ipld()),
+            Self::Union(ty) => ("union", ty.to_ipld()),
+            Self::Struct(ty) => ("struct", ty.to_ipld()),
+            Self::Enum(ty) => ("enum", ty.to_ipld()),
+            Self::Unit(repr) => ("unit", map([("representation", string(repr.as_str()))])),
+            Self::Any => ("any", map([])),
+            Self::Copy(from) => ("copy", map([("fromType", string(from))])),
+        };
+        map([(kind, body)])
+    }
+
+    fn from_ipld(ipld: &Ipld) -> Result<Self> {
+        read_keyed(ipld, |kind, body| {
+            Ok(Some(match kind {
+                "bool" => Self::Bool,
+                "string" => Self::String,
+                "bytes" => Self::Bytes,
+                "int" => Self::Int,
+                "float" => Self::Float,
+                "map" => Self::Map(TypeMap::from_ipld(body)?),
+                "list" => Self::List(TypeList::from_ipld(body)?),
+                "link" => Self::Link(TypeLink::from_ipld(body)?),
+                "union" => Self::Union(TypeUnion::from_ipld(body)?),
+                "struct" => Self::Struct(TypeStruct::from_ipld(body)?),
+                "enum" => Self::Enum(TypeEnum::from_ipld(body)?),
+                "unit" => Self::Unit(field(as_map(body)?, "representation", |ipld| {
+                    let repr = read_string(ipld)?;
+                    UnitRepr::from_name(&repr)
+                        .ok_or_else(|| UnknownVariant::new::<UnitRepr>(repr).into())
+                })?),
+                "any" => Self::Any,
+                "copy" => Self::Copy(field(as_map(body)?, "fromType", read_string)?),
+                _ => return Ok(None),
+            }))
+        })
+    }
+}
+
+impl TypeTerm {
+    fn to_ipld(&self) -> Ipld {
+        match self {
+            Self::Name(name) => string(name),
+            Self::Map(ty) => map([("map", ty.to_ipld())]),
+            Self::List(ty) => map([("list", ty.to_ipld())]),
+            Self::Link(ty) => map([("link", ty.to_ipld())]),
+        }
+    }
+
+    fn from_ipld(ipld: &Ipld) -> Result<Self> {
+        if let Ipld::String(name) = ipld {
+            return Ok(Self::Name(name.clone()));
+        }
+        read_keyed(ipld, |kind, body| {
+            Ok(Some(match kind {
+                "map" => Self::Map(Box::new(TypeMap::from_ipld(body)?)),
+                "list" => Self::List(Box::new(TypeList::from_ipld(body)?)),
+                "link" => Self::Link(TypeLink::from_ipld(body)?),
+                _ => return Ok(None),
+            }))
+        })
+    }
+}
+
+impl TypeMap {
+    fn to_ipld(&self) -> Ipld {
+        let representation = match &self.representation {
+            MapRepr::Map => map([("map", map([]))]),
+            MapRepr::StringPairs {
+                inner_delim,
+                entry_delim,
+            } => map([(
+                "stringpairs",
+                map([
+                    ("innerDelim", string(inner_delim)),
+                    ("entryDelim", string(entry_delim)),
+                ]),
+            )]),
+            MapRepr::ListPairs => map([("listpairs", map([]))]),
+        };
+        let mut ipld = map([
+            ("keyType", string(&self.key_type)),
+            ("valueType", self.value_type.to_ipld()),
+            ("representation", representation),
+        ]);
+        set_flag(&mut ipld, "valueNullable", self.value_nullable);
+        ipld
+    }
+
+    fn from_ipld(ipld: &Ipld) -> Result<Self> {
+        let body = as_map(ipld)?;
+        let representation = opt_field(body, "representation", |ipld| {
+            read_keyed(ipld, |repr, body| {
+                Ok(Some(match repr {
+                    "map" => MapRepr::Map,
+                    "stringpairs" => {
+                        let body = as_map(body)?;
+                        MapRepr::StringPairs {
+                            inner_delim: field(body, "innerDelim", read_string)?,
+                            entry_delim: field(body, "entryDelim", read_string)?,
+                        }
+                    }
+                    "listpairs" => MapRepr::ListPairs,
+                    _ => return Ok(None),
+                }))
+            })
+        })?;
+        Ok(Self {
+            key_type: field(body, "keyType", read_string)?,
+            value_type: field(body, "valueType", TypeTerm::from_ipld)?,
+            value_nullable: read_flag(body, "valueNullable")?,
+            representation: representation.unwrap_or(MapRepr::Map),
+        })
+    }
+}
+
+impl TypeList {
+    fn to_ipld(&self) -> Ipld {
+        let mut ipld = map([("valueType", self.value_type.to_ipld())]);
+        set_flag(&mut ipld, "valueNullable", self.value_nullable);
+        ipld
+    }
+
+    fn from_ipld(ipld: &Ipld) -> Result<Self> {
+        let body = as_map(ipld)?;
+        Ok(Self {
+            value_type: field(body, "valueType", TypeTerm::from_ipld)?,
+            value_nullable: read_flag(body, "valueNullable")?,
+        })
+    }
+}
+
+impl TypeLink {
+    fn to_ipld(&self) -> Ipld {
+        if self.expected_type == "Any" {
+            map([])
+        } else {
+            map([("expectedType", string(&self.expected_type))])
+        }
+    }
+
+    fn from_ipld(ipld: &Ipld) -> Result<Self> {
+        let expected_type = opt_field(as_map(ipld)?, "expectedType", read_string)?;
+        Ok(Self {
+            expected_type: expected_type.unwrap_or_else(|| "Any".into()),
+        })
+    }
+}
+
+impl TypeStruct {
+    fn to_ipld(&self) -> Ipld {
+        let fields = table(&self.fields, |field| {
+            let mut ipld = map([("type", field.ty.to_ipld())]);
+            set_flag(&mut ipld, "optional", field.optional);
+            set_flag(&mut ipld, "nullable", field.nullable);
+            ipld
+        });
+        let representation = match &self.representation {
+            StructRepr::Map(details) if details.is_empty() => map([("map", map([]))]),
+            StructRepr::Map(details) => {
+                let details = table(details, |details| {
+                    let mut ipld = BTreeMap::new();
+                    if let Some(rename) = &details.rename {
+                        ipld.insert("rename".into(), string(rename));
+                    }
+                    if let Some(implicit) = &details.implicit {
+                        ipld.insert("implicit".into(), implicit.clone());
+                    }
+                    Ipld::Map(ipld)
+                });
+                map([("map", map([("fields", details)]))])
+            }
+            StructRepr::Tuple { field_order } => {
+                map([("tuple", map([("fieldOrder", strings(field_order))]))])
+            }
+            StructRepr::StringPairs {
+                inner_delim,
+                entry_delim,
+            } => map([(
+                "stringpairs",
+                map([
+                    ("innerDelim", string(inner_delim)),
+                    ("entryDelim", string(entry_delim)),
+                ]),
+            )]),
+            StructRepr::StringJoin { join, field_order } => map([(
+                "stringjoin",
+                map([("join", string(join)), ("fieldOrder", strings(field_order))]),
+            )]),
+            StructRepr::ListPairs => map([("listpairs", map([]))]),
+        };
+        map([("fields", fields), ("representation", representation)])
+    }
+
+    fn from_ipld(ipld: &Ipld) -> Result<Self> {
+        let body = as_map(ipld)?;
+        let fields = field(body, "fields", |ipld| {
+            read_table(ipld, |ipld| {
+                let field = as_map(ipld)?;
+                Ok(StructField {
+                    ty: self::field(field, "type", TypeTerm::from_ipld)?,
+                    optional: read_flag(field, "optional")?,
+                    nullable: read_flag(field, "nullable")?,
+                })
+            })
+        })?;
+        let names: Vec<String> = fields.keys().cloned().collect();
+        let field_order = |body: &BTreeMap<String, Ipld>| -> Result<Vec<String>> {
+            let order = opt_field(body, "fieldOrder", |ipld| read_list(ipld, read_string))?;
+            Ok(order.unwrap_or_else(|| names.clone()))
+        };
+        let representation = field(body, "representation", |ipld| {
+            read_keyed(ipld, |repr, body| {
+                let body = as_map(body)?;
+                Ok(Some(match repr {
+                    "map" => {
+                        let details = opt_field(body, "fields", |ipld| {
+                            read_table(ipld, |ipld| {
+                                let details = as_map(ipld)?;
+                                Ok(FieldDetails {
+                                    rename: opt_field(details, "rename", read_string)?,
+                                    implicit: details.get("implicit").cloned(),
+                                })
+                            })
+                        })?;
+                        StructRepr::Map(details.unwrap_or_default())
+                    }
+                    "tuple" => StructRepr::Tuple {
+                        field_order: field_order(body)?,
+                    },
+                    "stringpairs" => StructRepr::StringPairs {
+                        inner_delim: field(body, "innerDelim", read_string)?,
+                        entry_delim: field(body, "entryDelim", read_string)?,
+                    },
+                    "stringjoin" => StructRepr::StringJoin {
+                        join: field(body, "join", read_string)?,
+                        field_order: field_order(body)?,
+                    },
+                    "listpairs" => StructRepr::ListPairs,
+                    _ => return Ok(None),
+                }))
+            })
+        })?;
+        Ok(Self {
+            fields,
+            representation,
+        })
+    }
+}
+
+impl TypeEnum {
+    fn to_ipld(&self) -> Ipld {
+        let representation = match &self.representation {
+            EnumRepr::String(values) => map([("string", table(values, |value| string(value)))]),
+            EnumRepr::Int(values) => map([("int", table(values, |value| Ipld::Integer(*value)))]),
+        };
+        map([
+            ("members", strings(&self.members)),
+            ("representation", representation),
+        ])
+    }
+
+    fn from_ipld(ipld: &Ipld) -> Result<Self> {
+        let body = as_map(ipld)?;
+        let members = field(body, "members", |ipld| read_list(ipld, read_string))?;
+        let representation = field(body, "representation", |ipld| {
+            read_keyed(ipld, |repr, body| {
+                Ok(Some(match repr {
+                    "string" => EnumRepr::String(read_table(body, read_string)?),
+                    "int" => EnumRepr::Int(read_table(body, |ipld| match ipld {
+                        Ipld::Integer(int) => Ok(*int),
+                        _ => Err(TypeError::new(TypeErrorType::Integer, ipld).into()),
+                    })?),
+                    _ => return Ok(None),
+                }))
+            })
+        })?;
+        Ok(Self {
+            members,
+            representation,
+        })
+    }
+}
+
+impl UnionMember {
+    fn to_ipld(&self) -> Ipld {
+        match self {
+            Self::Name(name) => string(name),
+            Self::Link(expected_type) => map([(
+                "link",
+                TypeLink {
+                    expected_type: expected_type.clone(),
+                }
+                .to_ipld(),
+            )]),
+        }
+    }
+
+    fn from_ipld(ipld: &Ipld) -> Result<Self> {
+        if let Ipld::String(name) = ipld {
+            return Ok(Self::Name(name.clone()));
+        }
+        read_keyed(ipld, |kind, body| {
+            Ok(match kind {
+                "link" => Some(Self::Link(TypeLink::from_ipld(body)?.expected_type)),
+                _ => None,
+            })
+        })
+    }
+}
+
+impl TypeUnion {
+    fn to_ipld(&self) -> Ipld {
+        let members = Ipld::List(self.members.iter().map(UnionMember::to_ipld).collect());
+        let representation = match &self.representation {
+            UnionRepr::Kinded(members) => map([(
+                "kinded",
+                Ipld::Map(
+                    members
+                        .iter()
+                        .map(|(kind, member)| (kind.to_string(), member.to_ipld()))
+                        .collect(),
+                ),
+            )]),
+            UnionRepr::Keyed(members) => map([("keyed", table(members, UnionMember::to_ipld))]),
+            UnionRepr::Envelope {
+                discriminant_key,
+                content_key,
+                discriminant_table,
+            } => map([(
+                "envelope",
+                map([
+                    ("discriminantKey", string(discriminant_key)),
+                    ("contentKey", string(content_key)),
+                    (
+                        "discriminantTable",
+                        table(discriminant_table, UnionMember::to_ipld),
+                    ),
+                ]),
+            )]),
+            UnionRepr::Inline {
+                discriminant_key,
+                discriminant_table,
+            } => map([(
+                "inline",
+                map([
+                    ("discriminantKey", string(discriminant_key)),
+                    (
+                        "discriminantTable",
+                        table(discriminant_table, UnionMember::to_ipld),
+                    ),
+                ]),
+            )]),
+            UnionRepr::StringPrefix(prefixes) => map([(
+                "stringprefix",
+                map([("prefixes", table(prefixes, UnionMember::to_ipld))]),
+            )]),
+            UnionRepr::BytesPrefix(prefixes) => map([(
+                "bytesprefix",
+                map([("prefixes", table(prefixes, UnionMember::to_ipld))]),
+            )]),
+        };
+        map([("members", members), ("representation", representation)])
+    }
+
+    fn from_ipld(ipld: &Ipld) -> Result<Self> {
+        let body = as_map(ipld)?;
+        let members = field(body, "members", |ipld| {
+            read_list(ipld, UnionMember::from_ipld)
+        })?;
+        let representation = field(body, "representation", |ipld| {
+            read_keyed(ipld, |repr, body| {
+                let table = |ipld: &Ipld| read_table(ipld, UnionMember::from_ipld);
+                Ok(Some(match repr {
+                    "kinded" => {
+                        let members = table(body)?
+                            .into_iter()
+                            .map(|(kind, member)| match Kind::from_name(&kind) {
+                                Some(kind) => Ok((kind, member)),
+                                None => Err(with_path_segment(
+                                    UnknownVariant::new::<Kind>(kind.clone()).into(),
+                                    PathSegment::Key(kind),
+                                )),
+                            })
+                            .collect::<Result<_>>()?;
+                        UnionRepr::Kinded(members)
+                    }
+                    "keyed" => UnionRepr::Keyed(table(body)?),
+                    "envelope" => {
+                        let body = as_map(body)?;
+                        UnionRepr::Envelope {
+                            discriminant_key: field(body, "discriminantKey", read_string)?,
+                            content_key: field(body, "contentKey", read_string)?,
+                            discriminant_table: field(body, "discriminantTable", table)?,
+                        }
+                    }
+                    "inline" => {
+                        let body = as_map(body)?;
+                        UnionRepr::Inline {
+                            discriminant_key: field(body, "discriminantKey", read_string)?,
+                            discriminant_table: field(body, "discriminantTable", table)?,
+                        }
+                    }
+                    "stringprefix" => {
+                        UnionRepr::StringPrefix(field(as_map(body)?, "prefixes", table)?)
+                    }
+                    "bytesprefix" => {
+                        UnionRepr::BytesPrefix(field(as_map(body)?, "prefixes", table)?)
+                    }
+                    _ => return Ok(None),
+                }))
+            })
+        })?;
+        Ok(Self {
+            members,
+            representation,
+        })
+    }
+}
+
+fn as_map(ipld: &Ipld) -> Result<&BTreeMap<String, Ipld>> {
+    match ipld {
+        Ipld::Map(map) => Ok(map),
+        _ => Err(TypeError::new(TypeErrorType::Map, ipld).into()),
+    }
+}
+
+fn read_string(ipld: &Ipld) -> Result<String> {
+    match ipld {
+        Ipld::String(s) => Ok(s.clone()),
+        _ => Err(TypeError::new(TypeErrorType::String, ipld).into()),
+    }
+}
+
+fn read_flag(map: &BTreeMap<String, Ipld>, key: &str) -> Result<bool> {
+    let flag = opt_field(map, key, |ipld| match ipld {
+        Ipld::Bool(flag) => Ok(*flag),
+        _ => Err(TypeError::new(TypeErrorType::Bool, ipld).into()),
+    })?;
+    Ok(flag.unwrap_or_default())
+}
+
+fn read_list<T>(ipld: &Ipld, f: impl Fn(&Ipld) -> Result<T>) -> Result<Vec<T>> {
+    match ipld {
+        Ipld::List(list) => list
+            .iter()
+            .enumerate()
+            .map(|(i, ipld)| f(ipld).map_err(|err| with_path_segment(err, PathSegment::Index(i))))
+            .collect(),
+        _ => Err(TypeError::new(TypeErrorType::List, ipld).into()),
+    }
+}
+
+fn read_table<T>(ipld: &Ipld, f: impl Fn(&Ipld) -> Result<T>) -> Result<BTreeMap<String, T>> {
+    as_map(ipld)?
+        .iter()
+        .map(|(key, ipld)| {
+            let value =
+                f(ipld).map_err(|err| with_path_segment(err, PathSegment::Key(key.clone())))?;
+            Ok((key.clone(), value))
+        })
+        .collect()
+}
+
+fn field<T>(
+    map: &BTreeMap<String, Ipld>,
+    key: &str,
+    f: impl FnOnce(&Ipld) -> Result<T>,
+) -> Result<T> {
+    match opt_field(map, key, f)? {
+        Some(value) => Ok(value),
+        None => Err(TypeError::new(TypeErrorType::Key(key.into()), TypeErrorType::Map).into()),
+    }
+}
+
+fn opt_field<T>(
+    map: &BTreeMap<String, Ipld>,
+    key: &str,
+    f: impl FnOnce(&Ipld) -> Result<T>,
+) -> Result<Option<T>> {
+    match map.get(key) {
+        Some(ipld) => f(ipld)
+            .map(Some)
+            .map_err(|err| with_path_segment(err, PathSegment::Key(key.into()))),
+        None => Ok(None),
+    }
+}
+
+/// Reads a single entry map whose key selects the variant of `T`.
+///
+/// `f` returns `None` for unknown keys, which are reported as unknown variants of `T`.
+fn read_keyed<T>(ipld: &Ipld, f: impl FnOnce(&str, &Ipld) -> Result<Option<T>>) -> Result<T> {
+    let map = as_map(ipld)?;
+    let (key, body) = match map.iter().next() {
+        Some(entry) if map.len() == 1 => entry,
+        _ => {
+            return Err(InvalidSchema(format!(
+                "expected a single entry map but found {} entries",
+                map.len()
+            ))
+            .into())
+        }
+    };
+    match f(key, body) {
+        Ok(Some(value)) => Ok(value),
+        Ok(None) => Err(with_path_segment(
+            UnknownVariant::new::<T>(key.clone()).into(),
+            PathSegment::Key(key.clone()),
+        )),
+        Err(err) => Err(with_path_segment(err, PathSegment::Key(key.clone()))),
+    }
+}
@@ -0,0 +1,28 @@
+//! IPLD schemas.
+//!
+//! Parses the schema DSL into a typed [`Schema`] that converts to and from its schema-schema
+//! [`Ipld`](libipld_core::ipld::Ipld) form, validates ipld values against it and generates rust
+//! types from it.
+#![deny(missing_docs)]
+#![deny(warnings)]
+
+pub mod ast;
+pub mod codegen;
+pub mod error;
+mod ipld;
+pub mod parse;
+pub mod validate;
+
+pub use crate::ast::*;
+pub use crate::error::{InvalidSchema, ParseError, UnsupportedType, ValidationError};
+pub use crate::parse::parse;
+
+use core::str::FromStr;
+
+impl FromStr for Schema {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s)
+    }
+}
@@ -0,0 +1,681 @@
+//! Schema DSL parser.
+use crate::ast::*;
+use crate::error::ParseError;
+use libipld_core::ipld::Ipld;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Tok {
+    Ident(String),
+    Str(String),
+    Int(i128),
+    Punct(char),
+    Eof,
+}
+
+impl Tok {
+    fn describe(&self) -> String {
+        match self {
+            Self::Ident(ident) => format!("`{}`", ident),
+            Self::Str(s) => format!("{:?}", s),
+            Self::Int(i) => i.to_string(),
+            Self::Punct(c) => format!("`{}`", c),
+            Self::Eof => "end of input".into(),
+        }
+    }
+}
+
+struct Spanned {
+    tok: Tok,
+    line: usize,
+    column: usize,
+}
+
+fn lex(input: &str) -> Result<Vec<Spanned>, ParseError> {
+    let mut toks = Vec::new();
+    let mut chars = input.chars().peekable();
+    let (mut line, mut column) = (1, 1);
+    macro_rules! bump {
+        () => {{
+            let c = chars.next();
+            if c == Some('\n') {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+            c
+        }};
+    }
+    while let Some(&c) = chars.peek() {
+        let (start_line, start_column) = (line, column);
+        let error = |message: String| ParseError {
+            line: start_line,
+            column: start_column,
+            message,
+        };
+        let tok = match c {
+            c if c.is_whitespace() || c == ',' => {
+                bump!();
+                continue;
+            }
+            '#' => {
+                while !matches!(chars.peek(), None | Some('\n')) {
+                    bump!();
+                }
+                continue;
+            }
+            '"' => {
+                bump!();
+                let mut s = String::new();
+                loop {
+                    match bump!() {
+                        Some('"') => break,
+                        Some('\\') => match bump!() {
+                            Some('n') => s.push('\n'),
+                            Some('t') => s.push('\t'),
+                            Some(c @ ('"' | '\\')) => s.push(c),
+                            Some(c) => return Err(error(format!("invalid escape `\\{}`", c))),
+                            None => return Err(error("unterminated string".into())),
+                        },
+                        Some(c) => s.push(c),
+                        None => return Err(error("unterminated string".into())),
+                    }
+                }
+                Tok::Str(s)
+            }
+            c if c == '-' || c.is_ascii_digit() => {
+                let mut s = String::new();
+                s.extend(bump!());
+                while let Some(c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                    s.push(*c);
+                    bump!();
+                }
+                let int = s
+                    .parse()
+                    .map_err(|_| error(format!("invalid integer `{}`", s)))?;
+                Tok::Int(int)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut s = String::new();
+                while let Some(c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                    s.push(*c);
+                    bump!();
+                }
+                Tok::Ident(s)
+            }
+            '{' | '}' | '[' | ']' | '(' | ')' | ':' | '|' | '&' | '=' => {
+                bump!();
+                Tok::Punct(c)
+            }
+            c => return Err(error(format!("unexpected character `{}`", c))),
+        };
+        toks.push(Spanned {
+            tok,
+            line: start_line,
+            column: start_column,
+        });
+    }
+    toks.push(Spanned {
+        tok: Tok::Eof,
+        line,
+        column,
+    });
+    Ok(toks)
+}
+
+/// Key of a union member, either a discriminant or a kind.
+enum MemberKey {
+    Str(String),
+    Kind(String),
+}
+
+struct Parser {
+    toks: Vec<Spanned>,
+    pos: usize,
+}
+
+type Params = BTreeMap<String, (Ipld, usize)>;
+
+impl Parser {
+    fn peek(&self) -> &Tok {
+        &self.toks[self.pos].tok
+    }
+
+    fn next(&mut self) -> Tok {
+        let tok = self.toks[self.pos].tok.clone();
+        if tok != Tok::Eof {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn error_at(&self, pos: usize, message: impl Into<String>) -> ParseError {
+        let tok = &self.toks[pos];
+        ParseError {
+            line: tok.line,
+            column: tok.column,
+            message: message.into(),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> ParseError {
+        self.error_at(
+            self.pos,
+            format!("expected {} but found {}", expected, self.peek().describe()),
+        )
+    }
+
+    fn punct(&mut self, c: char) -> bool {
+        if *self.peek() == Tok::Punct(c) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<(), ParseError> {
+        if self.punct(c) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{}`", c)))
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Tok::Ident(ident) if ident == keyword) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Tok::Ident(_) => match self.next() {
+                Tok::Ident(ident) => Ok(ident),
+                _ => unreachable!(),
+            },
+            _ => Err(self.unexpected("an identifier")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Tok::Str(_) => match self.next() {
+                Tok::Str(s) => Ok(s),
+                _ => unreachable!(),
+            },
+            _ => Err(self.unexpected("a string")),
+        }
+    }
+
+    fn literal(&mut self) -> Result<Ipld, ParseError> {
+        let ipld = match self.peek() {
+            Tok::Str(s) => Ipld::String(s.clone()),
+            Tok::Int(i) => Ipld::Integer(*i),
+            Tok::Ident(ident) if ident == "true" => Ipld::Bool(true),
+            Tok::Ident(ident) if ident == "false" => Ipld::Bool(false),
+            Tok::Punct('[') => {
+                self.next();
+                let mut list = Vec::new();
+                while !self.punct(']') {
+                    list.push(Ipld::String(self.string()?));
+                }
+                return Ok(Ipld::List(list));
+            }
+            _ => return Err(self.unexpected("a literal")),
+        };
+        self.next();
+        Ok(ipld)
+    }
+
+    fn schema(&mut self) -> Result<Schema, ParseError> {
+        let mut schema = Schema::default();
+        while *self.peek() != Tok::Eof {
+            if !self.keyword("type") {
+                return Err(self.unexpected("`type`"));
+            }
+            let pos = self.pos;
+            let name = self.ident()?;
+            if schema.types.contains_key(&name) {
+                return Err(self.error_at(pos, format!("duplicate type `{}`", name)));
+            }
+            let defn = self.type_defn()?;
+            schema.types.insert(name, defn);
+        }
+        Ok(schema)
+    }
+
+    fn type_defn(&mut self) -> Result<TypeDefn, ParseError> {
+        let pos = self.pos;
+        Ok(match self.next() {
+            Tok::Ident(kind) => match kind.as_str() {
+                "bool" => TypeDefn::Bool,
+                "string" => TypeDefn::String,
+                "bytes" => TypeDefn::Bytes,
+                "int" => TypeDefn::Int,
+                "float" => TypeDefn::Float,
+                "any" => TypeDefn::Any,
+                "link" => TypeDefn::Link(TypeLink::default()),
+                "struct" => TypeDefn::Struct(self.type_struct()?),
+                "enum" => TypeDefn::Enum(self.type_enum()?),
+                "union" => TypeDefn::Union(self.type_union()?),
+                "unit" => {
+                    if !self.keyword("representation") {
+                        return Err(self.unexpected("`representation`"));
+                    }
+                    let pos = self.pos;
+                    let name = self.ident()?;
+                    let repr = UnitRepr::from_name(&name).ok_or_else(|| {
+                        self.error_at(pos, format!("unknown unit representation `{}`", name))
+                    })?;
+                    TypeDefn::Unit(repr)
+                }
+                _ => return Err(self.error_at(pos, format!("unknown type kind `{}`", kind))),
+            },
+            Tok::Punct('&') => TypeDefn::Link(TypeLink {
+                expected_type: self.ident()?,
+            }),
+            Tok::Punct('=') => TypeDefn::Copy(self.ident()?),
+            Tok::Punct('{') => {
+                let mut map = self.type_map()?;
+                if let Some((name, pos, mut params)) = self.representation()? {
+                    map.representation = match name.as_str() {
+                        "map" => MapRepr::Map,
+                        "stringpairs" => MapRepr::StringPairs {
+                            inner_delim: self.param_string(&mut params, "innerDelim", pos)?,
+                            entry_delim: self.param_string(&mut params, "entryDelim", pos)?,
+                        },
+                        "listpairs" => MapRepr::ListPairs,
+                        _ => return Err(self.unknown_repr(pos, "map", &name)),
+                    };
+                    self.no_params(params)?;
+                }
+                TypeDefn::Map(map)
+            }
+            Tok::Punct('[') => TypeDefn::List(self.type_list()?),
+            _ => {
+                self.pos = pos;
+                return Err(self.unexpected("a type definition"));
+            }
+        })
+    }
+
+    /// Parses a map body after the opening `{`.
+    fn type_map(&mut self) -> Result<TypeMap, ParseError> {
+        let key_type = self.ident()?;
+        self.expect_punct(':')?;
+        let value_nullable = self.keyword("nullable");
+        let value_type = self.type_term()?;
+        self.expect_punct('}')?;
+        Ok(TypeMap {
+            key_type,
+            value_type,
+            value_nullable,
+            representation: MapRepr::Map,
+        })
+    }
+
+    /// Parses a list body after the opening `[`.
+    fn type_list(&mut self) -> Result<TypeList, ParseError> {
+        let value_nullable = self.keyword("nullable");
+        let value_type = self.type_term()?;
+        self.expect_punct(']')?;
+        Ok(TypeList {
+            value_type,
+            value_nullable,
+        })
+    }
+
+    fn type_term(&mut self) -> Result<TypeTerm, ParseError> {
+        if self.punct('&') {
+            Ok(TypeTerm::Link(TypeLink {
+                expected_type: self.ident()?,
+            }))
+        } else if self.punct('{') {
+            Ok(TypeTerm::Map(Box::new(self.type_map()?)))
+        } else if self.punct('[') {
+            Ok(TypeTerm::List(Box::new(self.type_list()?)))
+        } else {
+            match self.peek() {
+                Tok::Ident(_) => Ok(TypeTerm::Name(self.ident()?)),
+                _ => Err(self.unexpected("a type")),
+            }
+        }
+    }
+
+    fn type_struct(&mut self) -> Result<TypeStruct, ParseError> {
+        self.expect_punct('{')?;
+        let mut fields = BTreeMap::new();
+        let mut order = Vec::new();
+        let mut details = BTreeMap::new();
+        while !self.punct('}') {
+            let pos = self.pos;
+            let name = self.ident()?;
+            if fields.contains_key(&name) {
+                return Err(self.error_at(pos, format!("duplicate field `{}`", name)));
+            }
+            let optional = self.keyword("optional");
+            let nullable = self.keyword("nullable");
+            let ty = self.type_term()?;
+            if self.punct('(') {
+                let mut field = FieldDetails::default();
+                while !self.punct(')') {
+                    let pos = self.pos;
+                    match self.ident()?.as_str() {
+                        "rename" => field.rename = Some(self.string()?),
+                        "implicit" => field.implicit = Some(self.literal()?),
+                        param => {
+                            return Err(
+                                self.error_at(pos, format!("unknown field parameter `{}`", param))
+                            )
+                        }
+                    }
+                }
+                details.insert(name.clone(), (field, pos));
+            }
+            fields.insert(
+                name.clone(),
+                StructField {
+                    ty,
+                    optional,
+                    nullable,
+                },
+            );
+            order.push(name);
+        }
+        let representation = match self.representation()? {
+            None => StructRepr::Map(BTreeMap::new()),
+            Some((name, pos, mut params)) => {
+                let repr = match name.as_str() {
+                    "map" => StructRepr::Map(BTreeMap::new()),
+                    "tuple" => StructRepr::Tuple {
+                        field_order: self.field_order(&mut params, &order)?,
+                    },
+                    "stringpairs" => StructRepr::StringPairs {
+                        inner_delim: self.param_string(&mut params, "innerDelim", pos)?,
+                        entry_delim: self.param_string(&mut params, "entryDelim", pos)?,
+                    },
+                    "stringjoin" => StructRepr::StringJoin {
+                        join: self.param_string(&mut params, "join", pos)?,
+                        field_order: self.field_order(&mut params, &order)?,
+                    },
+                    "listpairs" => StructRepr::ListPairs,
+                    _ => return Err(self.unknown_repr(pos, "struct", &name)),
+                };
+                self.no_params(params)?;
+                repr
+            }
+        };
+        let representation = match representation {
+            StructRepr::Map(_) => StructRepr::Map(
+                details
+                    .into_iter()
+                    .map(|(name, (field, _))| (name, field))
+                    .collect(),
+            ),
+            repr => {
+                if let Some((_, pos)) = details.values().next() {
+                    return Err(self.error_at(
+                        *pos,
+                        "field parameters are only supported by the map representation",
+                    ));
+                }
+                repr
+            }
+        };
+        Ok(TypeStruct {
+            fields,
+            representation,
+        })
+    }
+
+    fn type_enum(&mut self) -> Result<TypeEnum, ParseError> {
+        self.expect_punct('{')?;
+        let mut members = Vec::new();
+        let mut values = Vec::new();
+        while !self.punct('}') {
+            self.expect_punct('|')?;
+            let pos = self.pos;
+            let name = self.ident()?;
+            if members.contains(&name) {
+                return Err(self.error_at(pos, format!("duplicate member `{}`", name)));
+            }
+            let value = if self.punct('(') {
+                let value = self.string()?;
+                self.expect_punct(')')?;
+                Some(value)
+            } else {
+                None
+            };
+            members.push(name.clone());
+            values.push((name, value, pos));
+        }
+        let repr = self.representation()?;
+        let representation = match repr.as_ref().map(|(name, _, _)| name.as_str()) {
+            None | Some("string") => EnumRepr::String(
+                values
+                    .into_iter()
+                    .filter_map(|(name, value, _)| Some((name, value?)))
+                    .collect(),
+            ),
+            Some("int") => {
+                let mut ints = BTreeMap::new();
+                for (name, value, pos) in values {
+                    let int = value.and_then(|value| value.parse().ok()).ok_or_else(|| {
+                        self.error_at(pos, format!("member `{}` needs an integer value", name))
+                    })?;
+                    ints.insert(name, int);
+                }
+                EnumRepr::Int(ints)
+            }
+            Some(name) => return Err(self.unknown_repr(repr.as_ref().unwrap().1, "enum", name)),
+        };
+        if let Some((_, _, params)) = repr {
+            self.no_params(params)?;
+        }
+        Ok(TypeEnum {
+            members,
+            representation,
+        })
+    }
+
+    fn type_union(&mut self) -> Result<TypeUnion, ParseError> {
+        self.expect_punct('{')?;
+        let mut members = Vec::new();
+        let mut keys = Vec::new();
+        while !self.punct('}') {
+            self.expect_punct('|')?;
+            let member = if self.punct('&') {
+                UnionMember::Link(self.ident()?)
+            } else {
+                UnionMember::Name(self.ident()?)
+            };
+            let pos = self.pos;
+            let key = match self.next() {
+                Tok::Str(s) => MemberKey::Str(s),
+                Tok::Ident(kind) => MemberKey::Kind(kind),
+                _ => {
+                    self.pos = pos;
+                    return Err(self.unexpected("a discriminant or kind"));
+                }
+            };
+            members.push(member.clone());
+            keys.push((key, member, pos));
+        }
+        let (name, pos, mut params) = match self.representation()? {
+            Some(repr) => repr,
+            None => return Err(self.unexpected("`representation`")),
+        };
+        let representation = if name == "kinded" {
+            let mut table = BTreeMap::new();
+            for (key, member, pos) in keys {
+                let kind = match key {
+                    MemberKey::Kind(kind) => Kind::from_name(&kind)
+                        .ok_or_else(|| self.error_at(pos, format!("unknown kind `{}`", kind)))?,
+                    MemberKey::Str(_) => return Err(self.error_at(pos, "expected a kind")),
+                };
+                if table.insert(kind, member).is_some() {
+                    return Err(self.error_at(pos, format!("duplicate kind `{}`", kind)));
+                }
+            }
+            UnionRepr::Kinded(table)
+        } else {
+            let mut table = BTreeMap::new();
+            for (key, member, pos) in keys {
+                let key = match key {
+                    MemberKey::Str(key) => key,
+                    MemberKey::Kind(_) => return Err(self.error_at(pos, "expected a discriminant")),
+                };
+                if table.contains_key(&key) {
+                    return Err(self.error_at(pos, format!("duplicate discriminant {:?}", key)));
+                }
+                table.insert(key, member);
+            }
+            match name.as_str() {
+                "keyed" => UnionRepr::Keyed(table),
+                "envelope" => UnionRepr::Envelope {
+                    discriminant_key: self.param_string(&mut params, "discriminantKey", pos)?,
+                    content_key: self.param_string(&mut params, "contentKey", pos)?,
+                    discriminant_table: table,
+                },
+                "inline" => UnionRepr::Inline {
+                    discriminant_key: self.param_string(&mut params, "discriminantKey", pos)?,
+                    discriminant_table: table,
+                },
+                "stringprefix" => UnionRepr::StringPrefix(table),
+                "bytesprefix" => UnionRepr::BytesPrefix(table),
+                _ => return Err(self.unknown_repr(pos, "union", &name)),
+            }
+        };
+        self.no_params(params)?;
+        Ok(TypeUnion {
+            members,
+            representation,
+        })
+    }
+
+    /// Parses an optional `representation name { params }` clause.
+    fn representation(&mut self) -> Result<Option<(String, usize, Params)>, ParseError> {
+        if !self.keyword("representation") {
+            return Ok(None);
+        }
+        let pos = self.pos;
+        let name = self.ident()?;
+        let mut params = BTreeMap::new();
+        if self.punct('{') {
+            while !self.punct('}') {
+                let pos = self.pos;
+                let key = self.ident()?;
+                if params.contains_key(&key) {
+                    return Err(self.error_at(pos, format!("duplicate parameter `{}`", key)));
+                }
+                let value = self.literal()?;
+                params.insert(key, (value, pos));
+            }
+        }
+        Ok(Some((name, pos, params)))
+    }
+
+    fn unknown_repr(&self, pos: usize, kind: &str, name: &str) -> ParseError {
+        self.error_at(pos, format!("unknown {} representation `{}`", kind, name))
+    }
+
+    fn param_string(
+        &self,
+        params: &mut Params,
+        key: &str,
+        pos: usize,
+    ) -> Result<String, ParseError> {
+        match params.remove(key) {
+            Some((Ipld::String(s), _)) => Ok(s),
+            Some((_, pos)) => Err(self.error_at(pos, format!("`{}` must be a string", key))),
+            None => Err(self.error_at(pos, format!("missing parameter `{}`", key))),
+        }
+    }
+
+    /// Takes an optional `fieldOrder` parameter, defaulting to the declaration order.
+    fn field_order(
+        &self,
+        params: &mut Params,
+        fields: &[String],
+    ) -> Result<Vec<String>, ParseError> {
+        let (list, pos) = match params.remove("fieldOrder") {
+            Some((Ipld::List(list), pos)) => (list, pos),
+            Some((_, pos)) => return Err(self.error_at(pos, "`fieldOrder` must be a list")),
+            None => return Ok(fields.to_vec()),
+        };
+        let order: Vec<String> = list
+            .into_iter()
+            .filter_map(|ipld| match ipld {
+                Ipld::String(s) => Some(s),
+                _ => None,
+            })
+            .collect();
+        let mut sorted = order.clone();
+        sorted.sort();
+        let mut expected = fields.to_vec();
+        expected.sort();
+        if sorted != expected {
+            return Err(self.error_at(pos, "`fieldOrder` must list every field once"));
+        }
+        Ok(order)
+    }
+
+    fn no_params(&self, params: Params) -> Result<(), ParseError> {
+        match params.into_iter().next() {
+            Some((key, (_, pos))) => {
+                Err(self.error_at(pos, format!("unknown parameter `{}`", key)))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses a schema written in the schema DSL.
+pub fn parse(input: &str) -> Result<Schema, ParseError> {
+    let toks = lex(input)?;
+    Parser { toks, pos: 0 }.schema()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse("type Foo struct {\n  a Int\n  a String\n}").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 3));
+        assert_eq!(err.message, "duplicate field `a`");
+
+        let err = parse("type Foo union {\n  | Bar \"bar\"\n}").unwrap_err();
+        assert_eq!(
+            err.message,
+            "expected `representation` but found end of input"
+        );
+
+        let err =
+            parse("type Foo union { | Bar map | Baz \"baz\" } representation kinded").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 34));
+
+        let err = parse("type Foo enum { | A | B } representation int").unwrap_err();
+        assert_eq!(err.message, "member `A` needs an integer value");
+
+        let err = parse("type Foo struct { a Int } representation tuple { fieldOrder [\"b\"] }")
+            .unwrap_err();
+        assert_eq!(err.message, "`fieldOrder` must list every field once");
+
+        let err =
+            parse("type Foo struct { a Int (rename \"A\") } representation tuple").unwrap_err();
+        assert_eq!(
+            err.message,
+            "field parameters are only supported by the map representation"
+        );
+
+        let err = parse("type Foo \"bar\"").unwrap_err();
+        assert_eq!(err.message, "expected a type definition but found \"bar\"");
+    }
+}
@@ -0,0 +1,681 @@
+//! Validation of ipld values against a schema.
+use crate::ast::*;
+use crate::error::ValidationError;
+use libipld_core::cid::Cid;
+use libipld_core::error::{PathSegment, Result};
+use libipld_core::ipld::Ipld;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Loads the value of a linked block.
+type Loader<'a> = &'a mut dyn FnMut(&Cid) -> Result<Ipld>;
+
+impl Schema {
+    /// Validates `ipld` against the type named `root`, returning every mismatch found.
+    ///
+    /// Besides the types defined in the schema, the prelude types `Bool`, `String`, `Bytes`,
+    /// `Int`, `Float`, `Map`, `List`, `Link` and `Any` can be referenced. Links are only checked
+    /// to be links, the blocks they point to are not loaded. Use [`Schema::validate_with`] to
+    /// check them against their expected type.
+    pub fn validate(&self, root: &str, ipld: &Ipld) -> Vec<ValidationError> {
+        Validator::new(self, None).run(root, ipld)
+    }
+
+    /// Validates `ipld` against the type named `root` like [`Schema::validate`], loading the
+    /// targets of typed links with `load` and validating them against the expected type.
+    ///
+    /// Errors inside a linked block are reported at the path of the link, followed by the path
+    /// inside the block. Every block is checked at most once per expected type.
+    pub fn validate_with<F>(&self, root: &str, ipld: &Ipld, mut load: F) -> Vec<ValidationError>
+    where
+        F: FnMut(&Cid) -> Result<Ipld>,
+    {
+        Validator::new(self, Some(&mut load)).run(root, ipld)
+    }
+}
+
+fn prelude(name: &str) -> Option<TypeDefn> {
+    let any = || TypeTerm::Name("Any".into());
+    Some(match name {
+        "Bool" => TypeDefn::Bool,
+        "String" => TypeDefn::String,
+        "Bytes" => TypeDefn::Bytes,
+        "Int" => TypeDefn::Int,
+        "Float" => TypeDefn::Float,
+        "Map" => TypeDefn::Map(TypeMap {
+            key_type: "String".into(),
+            value_type: any(),
+            value_nullable: true,
+            representation: MapRepr::Map,
+        }),
+        "List" => TypeDefn::List(TypeList {
+            value_type: any(),
+            value_nullable: true,
+        }),
+        "Link" => TypeDefn::Link(TypeLink::default()),
+        "Any" => TypeDefn::Any,
+        _ => return None,
+    })
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 == 1 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+struct Validator<'a> {
+    schema: &'a Schema,
+    load: Option<Loader<'a>>,
+    /// Link targets that were checked, with the type they were checked against.
+    checked: BTreeSet<(Cid, String)>,
+    segments: Vec<PathSegment>,
+    errors: Vec<ValidationError>,
+}
+
+impl<'a> Validator<'a> {
+    fn new(schema: &'a Schema, load: Option<Loader<'a>>) -> Self {
+        Self {
+            schema,
+            load,
+            checked: BTreeSet::new(),
+            segments: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn run(mut self, root: &str, ipld: &Ipld) -> Vec<ValidationError> {
+        self.named(root, ipld);
+        self.errors
+    }
+
+    fn error(&mut self, message: impl Into<String>) {
+        self.errors.push(ValidationError {
+            segments: self.segments.clone(),
+            message: message.into(),
+        });
+    }
+
+    fn mismatch(&mut self, expected: &str, ipld: &Ipld) {
+        self.error(format!(
+            "expected {} but found {}",
+            expected,
+            Kind::of(ipld)
+        ));
+    }
+
+    fn at(&mut self, segment: PathSegment, f: impl FnOnce(&mut Self)) {
+        self.segments.push(segment);
+        f(self);
+        self.segments.pop();
+    }
+
+    /// Resolves a type name, following copies.
+    fn resolve(&self, name: &str) -> Option<Cow<'a, TypeDefn>> {
+        let mut name = name;
+        for _ in 0..=self.schema.types.len() {
+            match self.schema.types.get(name) {
+                Some(TypeDefn::Copy(from)) => name = from,
+                Some(ty) => return Some(Cow::Borrowed(ty)),
+                None => return prelude(name).map(Cow::Owned),
+            }
+        }
+        None
+    }
+
+    fn named(&mut self, name: &str, ipld: &Ipld) {
+        match self.resolve(name) {
+            Some(ty) => self.defn(&ty, ipld),
+            None => self.error(format!("unknown type `{}`", name)),
+        }
+    }
+
+    fn term(&mut self, term: &TypeTerm, ipld: &Ipld) {
+        match term {
+            TypeTerm::Name(name) => self.named(name, ipld),
+            TypeTerm::Map(ty) => self.map(ty, ipld),
+            TypeTerm::List(ty) => self.list(ty, ipld),
+            TypeTerm::Link(ty) => self.link(&ty.expected_type, ipld),
+        }
+    }
+
+    fn nullable_term(&mut self, term: &TypeTerm, nullable: bool, ipld: &Ipld) {
+        if !(nullable && *ipld == Ipld::Null) {
+            self.term(term, ipld);
+        }
+    }
+
+    fn member(&mut self, member: &UnionMember, ipld: &Ipld) {
+        match member {
+            UnionMember::Name(name) => self.named(name, ipld),
+            UnionMember::Link(expected) => self.link(expected, ipld),
+        }
+    }
+
+    /// Converts a string from a string representation into the kind of its type.
+    fn scalar(&self, term: &TypeTerm, s: &str) -> Ipld {
+        let ty = match term {
+            TypeTerm::Name(name) => self.resolve(name),
+            _ => None,
+        };
+        let parsed = match ty.as_deref() {
+            Some(TypeDefn::Int) => s.parse().ok().map(Ipld::Integer),
+            Some(TypeDefn::Float) => s.parse().ok().map(Ipld::Float),
+            Some(TypeDefn::Bool) => s.parse().ok().map(Ipld::Bool),
+            _ => None,
+        };
+        parsed.unwrap_or_else(|| Ipld::String(s.into()))
+    }
+
+    fn defn(&mut self, ty: &TypeDefn, ipld: &Ipld) {
+        match (ty, ipld) {
+            (TypeDefn::Bool, Ipld::Bool(_))
+            | (TypeDefn::String, Ipld::String(_))
+            | (TypeDefn::Bytes, Ipld::Bytes(_))
+            | (TypeDefn::Int, Ipld::Integer(_))
+            | (TypeDefn::Float, Ipld::Float(_))
+            | (TypeDefn::Any, _) => {}
+            (TypeDefn::Bool, _) => self.mismatch("bool", ipld),
+            (TypeDefn::String, _) => self.mismatch("string", ipld),
+            (TypeDefn::Bytes, _) => self.mismatch("bytes", ipld),
+            (TypeDefn::Int, _) => self.mismatch("int", ipld),
+            (TypeDefn::Float, _) => self.mismatch("float", ipld),
+            (TypeDefn::Map(ty), _) => self.map(ty, ipld),
+            (TypeDefn::List(ty), _) => self.list(ty, ipld),
+            (TypeDefn::Link(ty), _) => self.link(&ty.expected_type, ipld),
+            (TypeDefn::Union(ty), _) => self.union(ty, ipld),
+            (TypeDefn::Struct(ty), _) => self.structure(ty, ipld),
+            (TypeDefn::Enum(ty), _) => self.enumeration(ty, ipld),
+            (TypeDefn::Unit(repr), _) => self.unit(*repr, ipld),
+            (TypeDefn::Copy(name), _) => self.named(name, ipld),
+        }
+    }
+
+    fn link(&mut self, expected: &str, ipld: &Ipld) {
+        let cid = match ipld {
+            Ipld::Link(cid) => cid,
+            _ => return self.mismatch("link", ipld),
+        };
+        let load = match self.load.as_mut() {
+            Some(load) if expected != "Any" => load,
+            _ => return,
+        };
+        if !self.checked.insert((*cid, expected.to_string())) {
+            return;
+        }
+        match load(cid) {
+            Ok(target) => self.named(expected, &target),
+            Err(err) => self.error(format!("failed to load link target: {}", err)),
+        }
+    }
+
+    fn list(&mut self, ty: &TypeList, ipld: &Ipld) {
+        let list = match ipld {
+            Ipld::List(list) => list,
+            _ => return self.mismatch("list", ipld),
+        };
+        for (i, item) in list.iter().enumerate() {
+            self.at(PathSegment::Index(i), |v| {
+                v.nullable_term(&ty.value_type, ty.value_nullable, item)
+            });
+        }
+    }
+
+    fn map(&mut self, ty: &TypeMap, ipld: &Ipld) {
+        let entries = match &ty.representation {
+            MapRepr::Map => match ipld {
+                Ipld::Map(map) => map
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Cow::Borrowed(value)))
+                    .collect(),
+                _ => return self.mismatch("map", ipld),
+            },
+            MapRepr::StringPairs {
+                inner_delim,
+                entry_delim,
+            } => match self.string_pairs(ipld, inner_delim, entry_delim) {
+                Some(pairs) => pairs
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let value = self.scalar(&ty.value_type, &value);
+                        (key, Cow::Owned(value))
+                    })
+                    .collect(),
+                None => return,
+            },
+            MapRepr::ListPairs => match self.list_pairs(ipld) {
+                Some(pairs) => pairs,
+                None => return,
+            },
+        };
+        let mut seen = Vec::new();
+        for (key, value) in entries {
+            self.at(PathSegment::Key(key.clone()), |v| {
+                if seen.contains(&key) {
+                    v.error("duplicate key");
+                }
+                v.named(&ty.key_type, &Ipld::String(key.clone()));
+                v.nullable_term(&ty.value_type, ty.value_nullable, &value);
+            });
+            seen.push(key);
+        }
+    }
+
+    /// Splits a stringpairs representation into keys and values.
+    fn string_pairs(
+        &mut self,
+        ipld: &Ipld,
+        inner_delim: &str,
+        entry_delim: &str,
+    ) -> Option<Vec<(String, String)>> {
+        let s = match ipld {
+            Ipld::String(s) => s,
+            _ => {
+                self.mismatch("string", ipld);
+                return None;
+            }
+        };
+        if s.is_empty() {
+            return Some(Vec::new());
+        }
+        let mut pairs = Vec::new();
+        for entry in s.split(entry_delim) {
+            match entry.split_once(inner_delim) {
+                Some((key, value)) => pairs.push((key.to_string(), value.to_string())),
+                None => {
+                    self.error(format!("entry {:?} is missing {:?}", entry, inner_delim));
+                    return None;
+                }
+            }
+        }
+        Some(pairs)
+    }
+
+    /// Reads a listpairs representation as keys and values.
+    fn list_pairs<'b>(&mut self, ipld: &'b Ipld) -> Option<Vec<(String, Cow<'b, Ipld>)>> {
+        let list = match ipld {
+            Ipld::List(list) => list,
+            _ => {
+                self.mismatch("list", ipld);
+                return None;
+            }
+        };
+        let mut pairs = Vec::new();
+        let mut valid = true;
+        for (i, pair) in list.iter().enumerate() {
+            match pair {
+                Ipld::List(pair) => match pair.as_slice() {
+                    [Ipld::String(key), value] => pairs.push((key.clone(), Cow::Borrowed(value))),
+                    _ => {
+                        self.at(PathSegment::Index(i), |v| {
+                            v.error("expected a [key, value] pair")
+                        });
+                        valid = false;
+                    }
+                },
+                _ => {
+                    self.at(PathSegment::Index(i), |v| v.mismatch("list", pair));
+                    valid = false;
+                }
+            }
+        }
+        valid.then_some(pairs)
+    }
+
+    fn structure(&mut self, ty: &TypeStruct, ipld: &Ipld) {
+        let no_details = BTreeMap::new();
+        let (entries, details) = match &ty.representation {
+            StructRepr::Map(details) => match ipld {
+                Ipld::Map(map) => (
+                    map.iter()
+                        .map(|(key, value)| (key.clone(), Cow::Borrowed(value)))
+                        .collect(),
+                    details,
+                ),
+                _ => return self.mismatch("map", ipld),
+            },
+            StructRepr::Tuple { field_order } => return self.tuple(ty, field_order, ipld),
+            StructRepr::StringPairs {
+                inner_delim,
+                entry_delim,
+            } => match self.string_pairs(ipld, inner_delim, entry_delim) {
+                Some(pairs) => {
+                    let entries = pairs
+                        .into_iter()
+                        .map(|(key, value)| {
+                            let value = match ty.fields.get(&key) {
+                                Some(field) => self.scalar(&field.ty, &value),
+                                None => Ipld::String(value),
+                            };
+                            (key, Cow::Owned(value))
+                        })
+                        .collect();
+                    (entries, &no_details)
+                }
+                None => return,
+            },
+            StructRepr::StringJoin { join, field_order } => {
+                return self.string_join(ty, join, field_order, ipld)
+            }
+            StructRepr::ListPairs => match self.list_pairs(ipld) {
+                Some(pairs) => (pairs, &no_details),
+                None => return,
+            },
+        };
+        self.fields(ty, details, entries);
+    }
+
+    /// Validates struct fields given as keyed entries.
+    fn fields(
+        &mut self,
+        ty: &TypeStruct,
+        details: &BTreeMap<String, FieldDetails>,
+        entries: Vec<(String, Cow<Ipld>)>,
+    ) {
+        let mut entries: BTreeMap<_, _> = entries.into_iter().collect();
+        for (name, field) in &ty.fields {
+            let details = details.get(name);
+            let key = details
+                .and_then(|details| details.rename.as_ref())
+                .unwrap_or(name);
+            match entries.remove(key) {
+                Some(value) => self.at(PathSegment::Key(key.clone()), |v| {
+                    v.nullable_term(&field.ty, field.nullable, &value)
+                }),
+                None => {
+                    let implicit = details.is_some_and(|details| details.implicit.is_some());
+                    if !field.optional && !implicit {
+                        self.error(format!("missing field `{}`", key));
+                    }
+                }
+            }
+        }
+        for key in entries.keys() {
+            self.at(PathSegment::Key(key.clone()), |v| v.error("unknown field"));
+        }
+    }
+
+    fn tuple(&mut self, ty: &TypeStruct, field_order: &[String], ipld: &Ipld) {
+        let list = match ipld {
+            Ipld::List(list) => list,
+            _ => return self.mismatch("list", ipld),
+        };
+        let required = field_order
+            .iter()
+            .rposition(|name| ty.fields.get(name).is_none_or(|field| !field.optional))
+            .map_or(0, |i| i + 1);
+        if list.len() < required || list.len() > field_order.len() {
+            self.error(format!(
+                "expected {} fields but found {}",
+                field_order.len(),
+                list.len()
+            ));
+        }
+        for (i, (name, item)) in field_order.iter().zip(list).enumerate() {
+            let field = match self.field(ty, name) {
+                Some(field) => field,
+                None => continue,
+            };
+            self.at(PathSegment::Index(i), |v| {
+                v.nullable_term(&field.ty, field.nullable, item)
+            });
+        }
+    }
+
+    /// Looks up a field listed in a field order.
+    fn field<'b>(&mut self, ty: &'b TypeStruct, name: &str) -> Option<&'b StructField> {
+        let field = ty.fields.get(name);
+        if field.is_none() {
+            self.error(format!("field order lists unknown field `{}`", name));
+        }
+        field
+    }
+
+    fn string_join(&mut self, ty: &TypeStruct, join: &str, field_order: &[String], ipld: &Ipld) {
+        let s = match ipld {
+            Ipld::String(s) => s,
+            _ => return self.mismatch("string", ipld),
+        };
+        let parts: Vec<&str> = s.split(join).collect();
+        if parts.len() != field_order.len() {
+            return self.error(format!(
+                "expected {} fields joined by {:?} but found {}",
+                field_order.len(),
+                join,
+                parts.len()
+            ));
+        }
+        for (i, (name, part)) in field_order.iter().zip(parts).enumerate() {
+            let field = match self.field(ty, name) {
+                Some(field) => field,
+                None => continue,
+            };
+            let value = self.scalar(&field.ty, part);
+            self.at(PathSegment::Index(i), |v| v.term(&field.ty, &value));
+        }
+    }
+
+    fn enumeration(&mut self, ty: &TypeEnum, ipld: &Ipld) {
+        let valid = match (&ty.representation, ipld) {
+            (EnumRepr::String(values), Ipld::String(s)) => ty
+                .members
+                .iter()
+                .any(|member| values.get(member).unwrap_or(member) == s),
+            (EnumRepr::Int(values), Ipld::Integer(i)) => values.values().any(|value| value == i),
+            (EnumRepr::String(_), _) => return self.mismatch("string", ipld),
+            (EnumRepr::Int(_), _) => return self.mismatch("int", ipld),
+        };
+        if !valid {
+            self.error(format!("{:?} is not a member of the enum", ipld));
+        }
+    }
+
+    fn unit(&mut self, repr: UnitRepr, ipld: &Ipld) {
+        let valid = match repr {
+            UnitRepr::Null => *ipld == Ipld::Null,
+            UnitRepr::True => *ipld == Ipld::Bool(true),
+            UnitRepr::False => *ipld == Ipld::Bool(false),
+            UnitRepr::EmptyMap => matches!(ipld, Ipld::Map(map) if map.is_empty()),
+        };
+        if !valid {
+            self.error(format!("expected {} but found {:?}", repr.as_str(), ipld));
+        }
+    }
+
+    fn union(&mut self, ty: &TypeUnion, ipld: &Ipld) {
+        match &ty.representation {
+            UnionRepr::Kinded(members) => match members.get(&Kind::of(ipld)) {
+                Some(member) => self.member(member, ipld),
+                None => self.error(format!(
+                    "no member of the union has kind {}",
+                    Kind::of(ipld)
+                )),
+            },
+            UnionRepr::Keyed(members) => {
+                let map = match ipld {
+                    Ipld::Map(map) => map,
+                    _ => return self.mismatch("map", ipld),
+                };
+                let (key, value) = match map.iter().next() {
+                    Some(entry) if map.len() == 1 => entry,
+                    _ => {
+                        return self.error(format!(
+                            "expected a single entry map but found {} entries",
+                            map.len()
+                        ))
+                    }
+                };
+                self.at(PathSegment::Key(key.clone()), |v| match members.get(key) {
+                    Some(member) => v.member(member, value),
+                    None => v.error(format!("unknown discriminant {:?}", key)),
+                });
+            }
+            UnionRepr::Envelope {
+                discriminant_key,
+                content_key,
+                discriminant_table,
+            } => {
+                let map = match ipld {
+                    Ipld::Map(map) => map,
+                    _ => return self.mismatch("map", ipld),
+                };
+                let member = match self.discriminant(map, discriminant_key, discriminant_table) {
+                    Some(member) => member,
+                    None => return,
+                };
+                for key in map.keys() {
+                    if key != discriminant_key && key != content_key {
+                        self.at(PathSegment::Key(key.clone()), |v| v.error("unknown field"));
+                    }
+                }
+                match map.get(content_key) {
+                    Some(content) => self.at(PathSegment::Key(content_key.clone()), |v| {
+                        v.member(member, content)
+                    }),
+                    None => self.error(format!("missing field `{}`", content_key)),
+                }
+            }
+            UnionRepr::Inline {
+                discriminant_key,
+                discriminant_table,
+            } => {
+                let map = match ipld {
+                    Ipld::Map(map) => map,
+                    _ => return self.mismatch("map", ipld),
+                };
+                self.inline(map, discriminant_key, discriminant_table, &[]);
+            }
+            UnionRepr::StringPrefix(prefixes) => {
+                let s = match ipld {
+                    Ipld::String(s) => s,
+                    _ => return self.mismatch("string", ipld),
+                };
+                let found = prefixes
+                    .iter()
+                    .filter(|(prefix, _)| s.starts_with(prefix.as_str()))
+                    .max_by_key(|(prefix, _)| prefix.len());
+                match found {
+                    Some((prefix, member)) => {
+                        let rest = &s[prefix.len()..];
+                        let value = match member {
+                            UnionMember::Name(name) => {
+                                self.scalar(&TypeTerm::Name(name.clone()), rest)
+                            }
+                            UnionMember::Link(_) => Ipld::String(rest.into()),
+                        };
+                        self.member(member, &value)
+                    }
+                    None => self.error(format!("{:?} has no known prefix", s)),
+                }
+            }
+            UnionRepr::BytesPrefix(prefixes) => {
+                let bytes = match ipld {
+                    Ipld::Bytes(bytes) => bytes,
+                    _ => return self.mismatch("bytes", ipld),
+                };
+                let found = prefixes
+                    .iter()
+                    .filter_map(|(prefix, member)| Some((decode_hex(prefix)?, member)))
+                    .filter(|(prefix, _)| bytes.starts_with(prefix))
+                    .max_by_key(|(prefix, _)| prefix.len());
+                match found {
+                    Some((prefix, member)) => {
+                        self.member(member, &Ipld::Bytes(bytes[prefix.len()..].to_vec()))
+                    }
+                    None => self.error("bytes have no known prefix"),
+                }
+            }
+        }
+    }
+
+    /// Validates an inline union, ignoring the discriminant keys of enclosing inline unions.
+    fn inline(
+        &mut self,
+        map: &BTreeMap<String, Ipld>,
+        key: &str,
+        table: &BTreeMap<String, UnionMember>,
+        skip: &[&str],
+    ) {
+        if let Some(member) = self.discriminant(map, key, table) {
+            let mut skip = skip.to_vec();
+            skip.push(key);
+            self.inline_member(member, map, &skip);
+        }
+    }
+
+    /// Validates the member of an inline union against the entries of `map` that aren't listed
+    /// in `skip`, without copying the map.
+    fn inline_member(&mut self, member: &UnionMember, map: &BTreeMap<String, Ipld>, skip: &[&str]) {
+        let entries = map.iter().filter(|(key, _)| !skip.contains(&key.as_str()));
+        let ty = match member {
+            UnionMember::Name(name) => self.resolve(name),
+            UnionMember::Link(_) => None,
+        };
+        match ty.as_deref() {
+            Some(TypeDefn::Struct(ty)) => {
+                if let StructRepr::Map(details) = &ty.representation {
+                    let entries = entries
+                        .map(|(key, value)| (key.clone(), Cow::Borrowed(value)))
+                        .collect();
+                    return self.fields(ty, details, entries);
+                }
+            }
+            Some(TypeDefn::Union(TypeUnion {
+                representation:
+                    UnionRepr::Inline {
+                        discriminant_key,
+                        discriminant_table,
+                    },
+                ..
+            })) => return self.inline(map, discriminant_key, discriminant_table, skip),
+            _ => {}
+        }
+        let rest = entries
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        self.member(member, &Ipld::Map(rest));
+    }
+
+    /// Looks up the member selected by the discriminant entry of a map.
+    fn discriminant<'b>(
+        &mut self,
+        map: &BTreeMap<String, Ipld>,
+        key: &str,
+        table: &'b BTreeMap<String, UnionMember>,
+    ) -> Option<&'b UnionMember> {
+        let discriminant = match map.get(key) {
+            Some(Ipld::String(discriminant)) => discriminant,
+            Some(ipld) => {
+                self.at(PathSegment::Key(key.into()), |v| v.mismatch("string", ipld));
+                return None;
+            }
+            None => {
+                self.error(format!("missing field `{}`", key));
+                return None;
+            }
+        };
+        let member = table.get(discriminant);
+        if member.is_none() {
+            self.at(PathSegment::Key(key.into()), |v| {
+                v.error(format!("unknown discriminant {:?}", discriminant))
+            });
+        }
+        member
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("00ff1a"), Some(vec![0x00, 0xff, 0x1a]));
+        assert_eq!(decode_hex("0"), None);
+        assert_eq!(decode_hex("zz"), None);
+    }
+}
@@ -0,0 +1,137 @@
+// Generated unions hold their members by value, however large they are.
+#![allow(clippy::large_enum_variant)]
+use libipld::cbor::DagCborCodec;
+use libipld::codec::assert_roundtrip;
+use libipld::{ipld, Cid, Link};
+use libipld_schema::codegen::generate;
+use libipld_schema::{Schema, TypeDefn, UnionMember, UnsupportedType};
+
+include!("codegen/generated.rs");
+
+#[test]
+fn test_generated_is_up_to_date() {
+    let schema: Schema = include_str!("codegen/schema.ipldsch").parse().unwrap();
+    assert_eq!(
+        generate(&schema).unwrap(),
+        include_str!("codegen/generated.rs")
+    );
+}
+
+#[test]
+fn test_generated_roundtrip() {
+    let post = Post {
+        author: Author::Person(Person {
+            name: "Alice".into(),
+            age: 42,
+        }),
+        body: Some(Body::PostLink(Link::new(Cid::default()))),
+        created_at: 0,
+        parent: None,
+        tags: [("a".to_string(), Some(1)), ("b".to_string(), None)].into(),
+        title: "Hello".into(),
+        r#type: Status::Draft,
+    };
+    assert_roundtrip(
+        DagCborCodec,
+        &post,
+        &ipld!({
+            "author": ["Alice", 42],
+            "body": { "quote": Cid::default() },
+            "parent": null,
+            "tags": { "a": 1, "b": null },
+            "title": "Hello",
+            "type": "draft",
+        }),
+    );
+    assert_roundtrip(DagCborCodec, &Author::Name("Bob".into()), &ipld!("Bob"));
+    assert_roundtrip(DagCborCodec, &Level::High, &ipld!(10));
+    assert_roundtrip(DagCborCodec, &Nothing, &ipld!(null));
+    assert_roundtrip(
+        DagCborCodec,
+        &Event::Nothing(Nothing),
+        &ipld!({ "tag": "nothing", "content": null }),
+    );
+    assert_roundtrip(DagCborCodec, &Version { major: 1, minor: 2 }, &ipld!("1.2"));
+    assert_roundtrip(
+        DagCborCodec,
+        &Query {
+            page: 3,
+            sort: "asc".into(),
+        },
+        &ipld!("page=3&sort=asc"),
+    );
+    assert_roundtrip(
+        DagCborCodec,
+        &Entry {
+            hits: 2,
+            key: "a".into(),
+        },
+        &ipld!([["hits", 2], ["key", "a"]]),
+    );
+    assert_roundtrip(
+        DagCborCodec,
+        &Counts([("a".to_string(), 1)].into()),
+        &ipld!([["a", 1]]),
+    );
+
+    let leaf = Node {
+        children: vec![],
+        next: None,
+        value: 2,
+    };
+    assert_roundtrip(
+        DagCborCodec,
+        &Node {
+            children: vec![leaf.clone()],
+            next: Some(Box::new(leaf)),
+            value: 1,
+        },
+        &ipld!({
+            "children": [{ "children": [], "next": null, "value": 2 }],
+            "next": { "children": [], "next": null, "value": 2 },
+            "value": 1,
+        }),
+    );
+    assert_roundtrip(
+        DagCborCodec,
+        &Expr::Neg(Box::new(Expr::Int(1))),
+        &ipld!({ "neg": { "int": 1 } }),
+    );
+}
+
+#[test]
+fn test_unsupported() {
+    let schema: Schema = r#"type Pair {String:Int} representation stringpairs {
+      innerDelim "="
+      entryDelim ","
+    }"#
+    .parse()
+    .unwrap();
+    let err = generate(&schema).unwrap_err();
+    let err = err.downcast_ref::<UnsupportedType>().unwrap();
+    assert_eq!(err.name, "Pair");
+    assert_eq!(
+        err.reason,
+        "the stringpairs representation of maps is not supported"
+    );
+
+    let schema: Schema = "type Scores {Int:String}".parse().unwrap();
+    let err = generate(&schema).unwrap_err();
+    let err = err.downcast_ref::<UnsupportedType>().unwrap();
+    assert_eq!(err.reason, "map keys of type `Int` are not supported");
+
+    let mut schema: Schema = r#"type Body union {
+      | String "text"
+    } representation keyed"#
+        .parse()
+        .unwrap();
+    if let Some(TypeDefn::Union(ty)) = schema.types.get_mut("Body") {
+        ty.members.push(UnionMember::Name("Bytes".into()));
+    }
+    let err = generate(&schema).unwrap_err();
+    let err = err.downcast_ref::<UnsupportedType>().unwrap();
+    assert_eq!(
+        err.reason,
+        "member `Bytes` is missing from the representation"
+    );
+}
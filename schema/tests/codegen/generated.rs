@@ -0,0 +1,132 @@
+// Generated from an IPLD schema, do not edit.
+
+#[derive(Clone, Debug, PartialEq, libipld::DagCbor)]
+#[ipld(repr = "kinded")]
+pub enum Author {
+    #[ipld(kind = "string")]
+    #[ipld(repr = "value")]
+    Name(Name),
+    #[ipld(kind = "list")]
+    #[ipld(repr = "value")]
+    Person(Person),
+}
+
+#[derive(Clone, Debug, PartialEq, libipld::DagCbor)]
+#[ipld(repr = "keyed")]
+pub enum Body {
+    #[ipld(rename = "text")]
+    #[ipld(repr = "value")]
+    Text(Text),
+    #[ipld(rename = "raw")]
+    #[ipld(repr = "value")]
+    Bytes(Box<[u8]>),
+    #[ipld(rename = "quote")]
+    #[ipld(repr = "value")]
+    PostLink(libipld::Link<Post>),
+}
+
+#[derive(Clone, Debug, PartialEq, libipld::DagCbor)]
+#[ipld(repr = "value")]
+pub struct Counts(
+    #[ipld(repr = "listpairs")] pub std::collections::BTreeMap<String, i64>,
+);
+
+#[derive(Clone, Debug, PartialEq, libipld::DagCbor)]
+#[ipld(repr = "listpairs")]
+pub struct Entry {
+    pub hits: i64,
+    pub key: String,
+}
+
+#[derive(Clone, Debug, PartialEq, libipld::DagCbor)]
+#[ipld(repr = "envelope", tag = "tag", content = "content")]
+pub enum Event {
+    #[ipld(rename = "post")]
+    #[ipld(repr = "value")]
+    Post(Post),
+    #[ipld(rename = "nothing")]
+    #[ipld(repr = "value")]
+    Nothing(Nothing),
+}
+
+#[derive(Clone, Debug, PartialEq, libipld::DagCbor)]
+#[ipld(repr = "keyed")]
+pub enum Expr {
+    #[ipld(rename = "int")]
+    #[ipld(repr = "value")]
+    Int(i64),
+    #[ipld(rename = "neg")]
+    #[ipld(repr = "value")]
+    Neg(Box<Neg>),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, libipld::DagCbor)]
+#[ipld(repr = "int")]
+pub enum Level {
+    Low = 1,
+    High = 10,
+}
+
+pub type Name = String;
+
+pub type Neg = Expr;
+
+#[derive(Clone, Debug, PartialEq, libipld::DagCbor)]
+#[ipld(repr = "map")]
+pub struct Node {
+    pub children: Vec<Node>,
+    pub next: Option<Box<Node>>,
+    pub value: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, libipld::DagCbor)]
+pub struct Nothing;
+
+#[derive(Clone, Debug, PartialEq, libipld::DagCbor)]
+#[ipld(repr = "tuple")]
+pub struct Person {
+    pub name: Name,
+    pub age: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, libipld::DagCbor)]
+#[ipld(repr = "map")]
+pub struct Post {
+    pub author: Author,
+    #[ipld(optional)]
+    pub body: Option<Body>,
+    #[ipld(rename = "created")]
+    #[ipld(default = 0)]
+    pub created_at: i64,
+    pub parent: Option<libipld::Link<Post>>,
+    pub tags: std::collections::BTreeMap<Name, Option<i64>>,
+    pub title: String,
+    #[ipld(rename = "type")]
+    pub r#type: Status,
+}
+
+pub type Posts = Vec<libipld::Link<Post>>;
+
+#[derive(Clone, Debug, PartialEq, libipld::DagCbor)]
+#[ipld(repr = "stringpairs", inner_delim = "=", entry_delim = "&")]
+pub struct Query {
+    pub page: i64,
+    pub sort: String,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, libipld::DagCbor)]
+#[ipld(repr = "string")]
+pub enum Status {
+    #[ipld(rename = "draft")]
+    Draft,
+    Published,
+}
+
+pub type Text = Name;
+
+#[derive(Clone, Debug, PartialEq, libipld::DagCbor)]
+#[ipld(repr = "stringjoin", join = ".")]
+pub struct Version {
+    pub major: i64,
+    pub minor: i64,
+}
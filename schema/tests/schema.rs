@@ -0,0 +1,220 @@
+use libipld_core::error::{DecodeLocation, UnknownVariant};
+use libipld_core::ipld::Ipld;
+use libipld_schema::*;
+use std::collections::BTreeMap;
+
+const SCHEMA: &str = r#"
+# A little of everything.
+type Name string
+type Count int
+type Data bytes
+type Flag bool
+type Ratio float
+type Anything any
+type AnyLink link
+type Alias = Name
+
+type Node struct {
+  name Name (rename "n")
+  count optional Count (implicit 0)
+  parent nullable &Node
+  children [&Node]
+  tags {String:nullable [Int]}
+}
+
+type Point struct {
+  y Int
+  x Int
+} representation tuple
+
+type Version struct {
+  major Int
+  minor Int
+} representation stringjoin { join "." fieldOrder ["major", "minor"] }
+
+type Params {String:String} representation stringpairs {
+  innerDelim "="
+  entryDelim "&"
+}
+
+type Color enum {
+  | Red ("r")
+  | Green
+}
+
+type Level enum {
+  | Low ("1")
+  | High ("10")
+} representation int
+
+type Shape union {
+  | Point "point"
+  | Node "node"
+} representation keyed
+
+type Value union {
+  | Name string
+  | Count int
+  | &Node link
+} representation kinded
+
+type Message union {
+  | Point "point"
+} representation envelope { discriminantKey "tag" contentKey "content" }
+
+type Event union {
+  | Node "node"
+} representation inline { discriminantKey "type" }
+
+type Nothing unit representation emptymap
+"#;
+
+#[test]
+fn test_parse() {
+    let schema: Schema = SCHEMA.parse().unwrap();
+    assert_eq!(schema.types.len(), 19);
+    assert_eq!(schema.types["Alias"], TypeDefn::Copy("Name".into()));
+    assert_eq!(schema.types["AnyLink"], TypeDefn::Link(TypeLink::default()));
+
+    let node = match &schema.types["Node"] {
+        TypeDefn::Struct(node) => node,
+        ty => panic!("expected struct, found {:?}", ty),
+    };
+    assert!(node.fields["count"].optional);
+    assert!(node.fields["parent"].nullable);
+    assert_eq!(
+        node.fields["parent"].ty,
+        TypeTerm::Link(TypeLink {
+            expected_type: "Node".into()
+        })
+    );
+    assert_eq!(
+        node.fields["tags"].ty,
+        TypeTerm::Map(Box::new(TypeMap {
+            key_type: "String".into(),
+            value_type: TypeTerm::List(Box::new(TypeList {
+                value_type: TypeTerm::Name("Int".into()),
+                value_nullable: false,
+            })),
+            value_nullable: true,
+            representation: MapRepr::Map,
+        }))
+    );
+    let mut details = BTreeMap::new();
+    details.insert(
+        "count".to_string(),
+        FieldDetails {
+            rename: None,
+            implicit: Some(Ipld::Integer(0)),
+        },
+    );
+    details.insert(
+        "name".to_string(),
+        FieldDetails {
+            rename: Some("n".into()),
+            implicit: None,
+        },
+    );
+    assert_eq!(node.representation, StructRepr::Map(details));
+
+    match &schema.types["Point"] {
+        TypeDefn::Struct(point) => assert_eq!(
+            point.representation,
+            StructRepr::Tuple {
+                field_order: vec!["y".into(), "x".into()]
+            }
+        ),
+        ty => panic!("expected struct, found {:?}", ty),
+    }
+
+    match &schema.types["Level"] {
+        TypeDefn::Enum(level) => {
+            assert_eq!(level.members, vec!["Low".to_string(), "High".to_string()]);
+            let values = [("High".to_string(), 10), ("Low".to_string(), 1)];
+            assert_eq!(level.representation, EnumRepr::Int(values.into()));
+        }
+        ty => panic!("expected enum, found {:?}", ty),
+    }
+
+    match &schema.types["Value"] {
+        TypeDefn::Union(value) => {
+            let members = [
+                (Kind::String, UnionMember::Name("Name".into())),
+                (Kind::Int, UnionMember::Name("Count".into())),
+                (Kind::Link, UnionMember::Link("Node".into())),
+            ];
+            assert_eq!(value.representation, UnionRepr::Kinded(members.into()));
+        }
+        ty => panic!("expected union, found {:?}", ty),
+    }
+    assert_eq!(schema.types["Nothing"], TypeDefn::Unit(UnitRepr::EmptyMap));
+}
+
+#[test]
+fn test_ipld_roundtrip() {
+    let schema: Schema = SCHEMA.parse().unwrap();
+    let ipld = schema.to_ipld();
+    assert_eq!(Schema::from_ipld(&ipld).unwrap(), schema);
+
+    let point = ipld.get("types").unwrap().get("Point").unwrap();
+    let expected = Ipld::Map(
+        [(
+            "struct".to_string(),
+            Ipld::Map(
+                [
+                    (
+                        "fields".to_string(),
+                        Ipld::Map(
+                            [
+                                ("x".to_string(), map("type", "Int")),
+                                ("y".to_string(), map("type", "Int")),
+                            ]
+                            .into(),
+                        ),
+                    ),
+                    (
+                        "representation".to_string(),
+                        Ipld::Map(
+                            [(
+                                "tuple".to_string(),
+                                Ipld::Map(
+                                    [(
+                                        "fieldOrder".to_string(),
+                                        Ipld::List(vec!["y".into(), "x".into()]),
+                                    )]
+                                    .into(),
+                                ),
+                            )]
+                            .into(),
+                        ),
+                    ),
+                ]
+                .into(),
+            ),
+        )]
+        .into(),
+    );
+    assert_eq!(point, &expected);
+}
+
+fn map(key: &str, value: &str) -> Ipld {
+    Ipld::Map([(key.to_string(), Ipld::String(value.into()))].into())
+}
+
+#[test]
+fn test_from_ipld_errors() {
+    let mut schema = Schema::default();
+    schema.types.insert("Foo".into(), TypeDefn::Int);
+    let mut ipld = schema.to_ipld();
+    if let Ipld::Map(map) = &mut ipld {
+        map.insert(
+            "types".into(),
+            Ipld::Map([("Foo".to_string(), self::map("integer", "x"))].into()),
+        );
+    }
+    let err = Schema::from_ipld(&ipld).unwrap_err();
+    let variant = err.downcast_ref::<UnknownVariant>().unwrap();
+    assert_eq!(variant.variant, "integer");
+    let location = err.downcast_ref::<DecodeLocation>().unwrap();
+    assert_eq!(location.path(), ".types.Foo.integer");
+}
@@ -0,0 +1,237 @@
+use libipld_core::cid::Cid;
+use libipld_core::error::BlockNotFound;
+use libipld_core::ipld::Ipld;
+use libipld_schema::{Schema, ValidationError};
+use std::collections::BTreeMap;
+
+const SCHEMA: &str = r#"
+type Post struct {
+  title String
+  author Author
+  body optional Body
+  tags nullable [String]
+  parent nullable &Post
+  version Version (implicit "1.0")
+  status Status
+  level Level
+}
+
+type Author union {
+  | String string
+  | Person list
+} representation kinded
+
+type Person struct {
+  name String
+  age Int
+} representation tuple
+
+type Body union {
+  | Text "text"
+  | Bytes "raw"
+} representation keyed
+
+type Text string
+
+type Version struct {
+  major Int
+  minor Int
+} representation stringjoin { join "." }
+
+type Status enum {
+  | Draft ("draft")
+  | Published
+}
+
+type Level enum {
+  | Low ("1")
+  | High ("2")
+} representation int
+
+type Shape union {
+  | Circle "circle"
+} representation inline { discriminantKey "type" }
+
+type Circle struct {
+  radius Int
+}
+
+type Solid union {
+  | Shape "2d"
+} representation inline { discriminantKey "dim" }
+
+type Message union {
+  | Circle "circle"
+} representation envelope { discriminantKey "tag" contentKey "content" }
+
+type Counts {String:Int} representation stringpairs { innerDelim "=" entryDelim "," }
+
+type Id union {
+  | Int "i:"
+  | String "s:"
+} representation stringprefix
+"#;
+
+fn map<const N: usize>(entries: [(&str, Ipld); N]) -> Ipld {
+    Ipld::Map(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect::<BTreeMap<_, _>>(),
+    )
+}
+
+fn messages(errors: Vec<ValidationError>) -> Vec<String> {
+    errors
+        .into_iter()
+        .map(|err| format!("{}: {}", err.path(), err.message))
+        .collect::<Vec<_>>()
+}
+
+fn errors(schema: &Schema, root: &str, ipld: &Ipld) -> Vec<String> {
+    // Every post links to a valid parent post.
+    let parent = |_: &Cid| {
+        let mut parent = valid_post();
+        if let Ipld::Map(map) = &mut parent {
+            map.insert("parent".into(), Ipld::Null);
+        }
+        Ok(parent)
+    };
+    messages(schema.validate_with(root, ipld, parent))
+}
+
+fn post() -> Ipld {
+    map([
+        ("title", "Hello".into()),
+        ("author", map([("name", "Alice".into())])),
+        ("tags", Ipld::Null),
+        ("parent", Ipld::Link(Cid::default())),
+        ("status", "draft".into()),
+        ("level", 2.into()),
+    ])
+}
+
+fn valid_post() -> Ipld {
+    let mut post = post();
+    if let Ipld::Map(map) = &mut post {
+        map.insert("author".into(), Ipld::List(vec!["Alice".into(), 42.into()]));
+        map.insert("body".into(), self::map([("text", "Hi".into())]));
+        map.insert("version".into(), "2.1".into());
+    }
+    post
+}
+
+#[test]
+fn test_valid() {
+    let schema: Schema = SCHEMA.parse().unwrap();
+    assert_eq!(errors(&schema, "Post", &valid_post()), Vec::<String>::new());
+
+    let shape = map([("type", "circle".into()), ("radius", 1.into())]);
+    assert!(schema.validate("Shape", &shape).is_empty());
+    let solid = map([
+        ("dim", "2d".into()),
+        ("type", "circle".into()),
+        ("radius", 1.into()),
+    ]);
+    assert!(schema.validate("Solid", &solid).is_empty());
+    let message = map([
+        ("tag", "circle".into()),
+        ("content", map([("radius", 1.into())])),
+    ]);
+    assert!(schema.validate("Message", &message).is_empty());
+    assert!(schema.validate("Counts", &"a=1,b=2".into()).is_empty());
+    assert!(schema.validate("Id", &"i:42".into()).is_empty());
+}
+
+#[test]
+fn test_invalid() {
+    let schema: Schema = SCHEMA.parse().unwrap();
+    let mut post = post();
+    if let Ipld::Map(map) = &mut post {
+        map.remove("title");
+        map.insert("body".into(), self::map([("html", "<p>".into())]));
+        map.insert("tags".into(), Ipld::List(vec!["a".into(), 1.into()]));
+        map.insert("parent".into(), self::map([]));
+        map.insert("version".into(), "1.x".into());
+        map.insert("status".into(), "Draft".into());
+        map.insert("level".into(), 3.into());
+        map.insert("extra".into(), true.into());
+    }
+    assert_eq!(
+        errors(&schema, "Post", &post),
+        vec![
+            ".author: no member of the union has kind map",
+            ".body.html: unknown discriminant \"html\"",
+            ".level: 3 is not a member of the enum",
+            ".parent: expected link but found map",
+            ".status: \"Draft\" is not a member of the enum",
+            ".tags[1]: expected string but found int",
+            ".: missing field `title`",
+            ".version[1]: expected int but found string",
+            ".extra: unknown field",
+        ]
+    );
+
+    let shape = map([("type", "square".into()), ("radius", 1.into())]);
+    assert_eq!(
+        errors(&schema, "Shape", &shape),
+        vec![".type: unknown discriminant \"square\""]
+    );
+    let solid = map([("dim", "2d".into()), ("type", "circle".into())]);
+    assert_eq!(
+        errors(&schema, "Solid", &solid),
+        vec![".: missing field `radius`"]
+    );
+    let message = map([("tag", "circle".into()), ("content", map([]))]);
+    assert_eq!(
+        errors(&schema, "Message", &message),
+        vec![".content: missing field `radius`"]
+    );
+    assert_eq!(
+        errors(&schema, "Counts", &"a=1,b".into()),
+        vec![".: entry \"b\" is missing \"=\""]
+    );
+    assert_eq!(
+        errors(&schema, "Id", &"i:x".into()),
+        vec![".: expected int but found string"]
+    );
+    assert_eq!(
+        errors(&schema, "Author", &Ipld::Bool(true)),
+        vec![".: no member of the union has kind bool"]
+    );
+    assert_eq!(
+        errors(&schema, "Missing", &Ipld::Null),
+        vec![".: unknown type `Missing`"]
+    );
+}
+
+#[test]
+fn test_link_target() {
+    let schema: Schema = SCHEMA.parse().unwrap();
+    assert!(schema.validate("Post", &valid_post()).is_empty());
+
+    let errors = schema.validate_with("Post", &valid_post(), |_| {
+        let mut parent = valid_post();
+        if let Ipld::Map(map) = &mut parent {
+            map.insert("title".into(), 1.into());
+        }
+        Ok(parent)
+    });
+    assert_eq!(
+        messages(errors),
+        vec![".parent.title: expected string but found int"]
+    );
+
+    let errors = schema.validate_with("Post", &valid_post(), |cid| Err(BlockNotFound(*cid).into()));
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.starts_with("failed to load link target"));
+
+    // A parent that links to itself is only checked once.
+    let mut loads = 0;
+    let errors = schema.validate_with("Post", &valid_post(), |_| {
+        loads += 1;
+        Ok(valid_post())
+    });
+    assert!(errors.is_empty());
+    assert_eq!(loads, 1);
+}
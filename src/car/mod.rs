@@ -0,0 +1,347 @@
+//! Content addressable archives (CAR).
+//!
+//! A CARv1 file consists of a varint length prefixed dag-cbor header followed by a sequence of
+//! varint length prefixed sections, each of which is a binary cid followed by the block data:
+//!
+//! ```text
+//! | varint | header | varint | cid | data | varint | cid | data | ...
+//! ```
+//!
+//! CARv2 wraps a CARv1 payload and appends an index, see [`v2`].
+use crate::block::Block;
+use crate::cbor::DagCborCodec;
+use crate::cid::Cid;
+use crate::codec::Codec;
+use crate::error::{BlockTooLarge, Result, TypeError, TypeErrorType};
+use crate::ipld::Ipld;
+use crate::store::StoreParams;
+use core::marker::PhantomData;
+use std::collections::BTreeMap;
+use std::io::{Cursor, ErrorKind, Read, Write};
+use thiserror::Error;
+
+pub mod v2;
+
+/// Maximum size of a binary cid with a 64 byte digest.
+pub(crate) const MAX_CID_SIZE: usize = 4 * 10 + 64;
+
+/// Maximum size of a CAR header.
+pub(crate) const MAX_HEADER_SIZE: usize = 32 * 1024 * 1024;
+
+/// Invalid CAR header.
+#[derive(Debug, Error)]
+#[error("Invalid CAR header: {0}.")]
+pub struct InvalidCarHeader(pub &'static str);
+
+/// The CAR version is unsupported.
+#[derive(Clone, Copy, Debug, Error)]
+#[error("Unsupported CAR version {0}.")]
+pub struct UnsupportedCarVersion(pub u64);
+
+/// Varint is malformed.
+#[derive(Clone, Copy, Debug, Error)]
+#[error("Invalid varint.")]
+pub struct InvalidVarint;
+
+/// Reads an unsigned varint. Returns `None` if the reader is at EOF.
+pub(crate) fn read_varint<R: Read>(r: &mut R) -> Result<Option<u64>> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let mut buf = [0u8; 1];
+        if let Err(err) = r.read_exact(&mut buf) {
+            if i == 0 && err.kind() == ErrorKind::UnexpectedEof {
+                return Ok(None);
+            }
+            return Err(err.into());
+        }
+        let byte = buf[0];
+        if i == 9 && byte > 1 {
+            return Err(InvalidVarint.into());
+        }
+        value |= ((byte & 0x7f) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            if i > 0 && byte == 0 {
+                return Err(InvalidVarint.into());
+            }
+            return Ok(Some(value));
+        }
+    }
+    Err(InvalidVarint.into())
+}
+
+/// Writes an unsigned varint.
+pub(crate) fn write_varint<W: Write>(w: &mut W, mut value: u64) -> Result<()> {
+    let mut buf = [0u8; 10];
+    let mut i = 0;
+    loop {
+        buf[i] = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            break;
+        }
+        buf[i] |= 0x80;
+        i += 1;
+    }
+    w.write_all(&buf[..=i])?;
+    Ok(())
+}
+
+/// CARv1 header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CarHeader {
+    /// The roots of the archive.
+    pub roots: Vec<Cid>,
+    /// The CAR version.
+    pub version: u64,
+}
+
+impl CarHeader {
+    /// Creates a new CARv1 header.
+    pub fn new(roots: Vec<Cid>) -> Self {
+        Self { roots, version: 1 }
+    }
+
+    /// Encodes the header as dag-cbor.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut map = BTreeMap::new();
+        map.insert(
+            "roots".to_string(),
+            Ipld::List(self.roots.iter().copied().map(Ipld::Link).collect()),
+        );
+        map.insert("version".to_string(), Ipld::Integer(self.version as i128));
+        DagCborCodec.encode(&Ipld::Map(map))
+    }
+
+    /// Decodes a dag-cbor header.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let ipld: Ipld = DagCborCodec.decode(bytes)?;
+        let version = match ipld.get("version")? {
+            Ipld::Integer(version) if *version >= 0 => *version as u64,
+            ipld => return Err(TypeError::new(TypeErrorType::Integer, ipld).into()),
+        };
+        let roots = match ipld.get("roots") {
+            Ok(Ipld::List(roots)) => roots
+                .iter()
+                .map(|root| match root {
+                    Ipld::Link(cid) => Ok(*cid),
+                    ipld => Err(TypeError::new(TypeErrorType::Link, ipld).into()),
+                })
+                .collect::<Result<Vec<_>>>()?,
+            Ok(ipld) => return Err(TypeError::new(TypeErrorType::List, ipld).into()),
+            // The roots are optional in the CARv2 pragma.
+            Err(_) => vec![],
+        };
+        Ok(Self { roots, version })
+    }
+}
+
+/// Reads the varint length prefixed header.
+pub(crate) fn read_header<R: Read>(r: &mut R) -> Result<CarHeader> {
+    let len = read_varint(r)?.ok_or(InvalidCarHeader("empty file"))? as usize;
+    if len == 0 || len > MAX_HEADER_SIZE {
+        return Err(InvalidCarHeader("length out of range").into());
+    }
+    let mut buf = vec![0; len];
+    r.read_exact(&mut buf)?;
+    CarHeader::decode(&buf)
+}
+
+/// Reads a section and verifies the block. Returns `None` if the reader is at EOF.
+pub(crate) fn read_section<S: StoreParams, R: Read>(r: &mut R) -> Result<Option<Block<S>>> {
+    let len = match read_varint(r)? {
+        Some(len) => len as usize,
+        None => return Ok(None),
+    };
+    if len > S::MAX_BLOCK_SIZE + MAX_CID_SIZE {
+        return Err(BlockTooLarge(len).into());
+    }
+    let mut buf = vec![0; len];
+    r.read_exact(&mut buf)?;
+    let mut cursor = Cursor::new(buf);
+    let cid = Cid::read_bytes(&mut cursor)?;
+    let offset = cursor.position() as usize;
+    let mut data = cursor.into_inner();
+    data.drain(..offset);
+    if data.len() > S::MAX_BLOCK_SIZE {
+        return Err(BlockTooLarge(data.len()).into());
+    }
+    Ok(Some(Block::new(cid, data)?))
+}
+
+/// Writes a section.
+pub(crate) fn write_section<S, W: Write>(w: &mut W, block: &Block<S>) -> Result<u64> {
+    let cid = AsRef::<Cid>::as_ref(block).to_bytes();
+    let data = AsRef::<[u8]>::as_ref(block);
+    let len = (cid.len() + data.len()) as u64;
+    let mut prefix = Vec::with_capacity(10);
+    write_varint(&mut prefix, len)?;
+    w.write_all(&prefix)?;
+    w.write_all(&cid)?;
+    w.write_all(data)?;
+    Ok(prefix.len() as u64 + len)
+}
+
+/// CARv1 writer.
+#[derive(Debug)]
+pub struct CarWriter<W> {
+    w: W,
+}
+
+impl<W: Write> CarWriter<W> {
+    /// Creates a new writer and writes the header.
+    pub fn new(roots: Vec<Cid>, mut w: W) -> Result<Self> {
+        let header = CarHeader::new(roots).encode()?;
+        write_varint(&mut w, header.len() as u64)?;
+        w.write_all(&header)?;
+        Ok(Self { w })
+    }
+
+    /// Writes a block.
+    pub fn write<S>(&mut self, block: &Block<S>) -> Result<()> {
+        write_section(&mut self.w, block)?;
+        Ok(())
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.w.flush()?;
+        Ok(())
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+/// CARv1 reader.
+///
+/// Yields the blocks of the archive in order. Every block is verified against its cid.
+#[derive(Debug)]
+pub struct CarReader<R, S> {
+    _marker: PhantomData<S>,
+    r: R,
+    header: CarHeader,
+    done: bool,
+}
+
+impl<R: Read, S: StoreParams> CarReader<R, S> {
+    /// Creates a new reader and reads the header.
+    pub fn new(mut r: R) -> Result<Self> {
+        let header = read_header(&mut r)?;
+        if header.version != 1 {
+            return Err(UnsupportedCarVersion(header.version).into());
+        }
+        Ok(Self::with_header(header, r))
+    }
+
+    /// Creates a reader from a header that was already read.
+    pub(crate) fn with_header(header: CarHeader, r: R) -> Self {
+        Self {
+            _marker: PhantomData,
+            r,
+            header,
+            done: false,
+        }
+    }
+
+    /// Returns the header.
+    pub fn header(&self) -> &CarHeader {
+        &self.header
+    }
+
+    /// Returns the roots of the archive.
+    pub fn roots(&self) -> &[Cid] {
+        &self.header.roots
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+}
+
+impl<R: Read, S: StoreParams> Iterator for CarReader<R, S> {
+    type Item = Result<Block<S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let res = read_section(&mut self.r).transpose();
+        if !matches!(res, Some(Ok(_))) {
+            self.done = true;
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipld;
+    use crate::multihash::Code;
+    use crate::store::DefaultParams;
+
+    type IpldBlock = Block<DefaultParams>;
+
+    fn block(ipld: &Ipld) -> IpldBlock {
+        IpldBlock::encode(DagCborCodec, Code::Blake3_256, ipld).unwrap()
+    }
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut buf = vec![];
+            write_varint(&mut buf, value).unwrap();
+            assert_eq!(read_varint(&mut &buf[..]).unwrap(), Some(value));
+        }
+        assert_eq!(read_varint(&mut &[][..]).unwrap(), None);
+        assert!(read_varint(&mut &[0x80, 0x00][..]).is_err());
+        assert!(read_varint(&mut &[0x80][..]).is_err());
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let a = block(&ipld!("a"));
+        let b = block(&ipld!([a.cid()]));
+        let mut writer = CarWriter::new(vec![*b.cid()], vec![]).unwrap();
+        writer.write(&b).unwrap();
+        writer.write(&a).unwrap();
+        let bytes = writer.into_inner();
+
+        let reader = CarReader::<_, DefaultParams>::new(&bytes[..]).unwrap();
+        assert_eq!(reader.roots(), &[*b.cid()]);
+        assert_eq!(reader.header().version, 1);
+        let blocks = reader.collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(blocks, vec![b, a]);
+    }
+
+    #[test]
+    fn test_invalid_block() {
+        let a = block(&ipld!("a"));
+        let mut writer = CarWriter::new(vec![*a.cid()], vec![]).unwrap();
+        writer.write(&a).unwrap();
+        let mut bytes = writer.into_inner();
+        *bytes.last_mut().unwrap() ^= 0xff;
+
+        let mut reader = CarReader::<_, DefaultParams>::new(&bytes[..]).unwrap();
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let header = CarHeader {
+            roots: vec![],
+            version: 3,
+        }
+        .encode()
+        .unwrap();
+        let mut bytes = vec![];
+        write_varint(&mut bytes, header.len() as u64).unwrap();
+        bytes.extend(header);
+        let err = CarReader::<_, DefaultParams>::new(&bytes[..]).unwrap_err();
+        assert!(err.is::<UnsupportedCarVersion>());
+    }
+}
@@ -0,0 +1,446 @@
+//! CARv2 archives.
+//!
+//! A CARv2 file starts with a fixed pragma, followed by a fixed size header, a CARv1 payload and
+//! an optional index:
+//!
+//! ```text
+//! | pragma | header | padding | CARv1 data | padding | index |
+//! ```
+//!
+//! The header stores the offset and size of the CARv1 payload and the offset of the index. The
+//! index is a `MultihashIndexSorted` index, mapping the digests of the blocks, grouped by
+//! multihash code and digest width, to the offset of their section in the payload. Entries are
+//! sorted by digest, so that a block can be found by binary search.
+use super::{read_header, read_section, write_section, write_varint, CarHeader, CarReader};
+use crate::block::Block;
+use crate::cid::Cid;
+use crate::error::{BlockNotFound, Result};
+use crate::store::StoreParams;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::io::{Read, Seek, SeekFrom, Take, Write};
+use thiserror::Error;
+
+/// The CARv2 pragma, a varint length prefixed dag-cbor encoded `{"version": 2}`.
+pub const PRAGMA: [u8; 11] = [
+    0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02,
+];
+
+/// Size of the CARv2 header.
+pub const HEADER_SIZE: usize = 40;
+
+/// Multicodec of the `MultihashIndexSorted` index.
+pub const MULTIHASH_INDEX_SORTED: u64 = 0x0401;
+
+/// The file doesn't start with the CARv2 pragma.
+#[derive(Clone, Copy, Debug, Error)]
+#[error("Invalid CARv2 pragma.")]
+pub struct InvalidPragma;
+
+/// The index is malformed or uses an unsupported format.
+#[derive(Debug, Error)]
+#[error("Invalid CARv2 index: {0}.")]
+pub struct InvalidIndex(pub &'static str);
+
+/// The archive doesn't contain an index.
+#[derive(Clone, Copy, Debug, Error)]
+#[error("CARv2 archive has no index.")]
+pub struct MissingIndex;
+
+/// CARv2 header.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CarV2Header {
+    /// Characteristics bitfield.
+    pub characteristics: u128,
+    /// Offset of the CARv1 payload from the start of the file.
+    pub data_offset: u64,
+    /// Size of the CARv1 payload.
+    pub data_size: u64,
+    /// Offset of the index from the start of the file, zero if there is no index.
+    pub index_offset: u64,
+}
+
+impl CarV2Header {
+    /// Encodes the header.
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut buf = [0; HEADER_SIZE];
+        buf[..16].copy_from_slice(&self.characteristics.to_le_bytes());
+        buf[16..24].copy_from_slice(&self.data_offset.to_le_bytes());
+        buf[24..32].copy_from_slice(&self.data_size.to_le_bytes());
+        buf[32..].copy_from_slice(&self.index_offset.to_le_bytes());
+        buf
+    }
+
+    /// Decodes the header.
+    pub fn from_bytes(buf: &[u8; HEADER_SIZE]) -> Self {
+        Self {
+            characteristics: u128::from_le_bytes(buf[..16].try_into().unwrap()),
+            data_offset: u64::from_le_bytes(buf[16..24].try_into().unwrap()),
+            data_size: u64::from_le_bytes(buf[24..32].try_into().unwrap()),
+            index_offset: u64::from_le_bytes(buf[32..].try_into().unwrap()),
+        }
+    }
+}
+
+fn read_u32<R: Read>(r: &mut R) -> Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Index entries with the same multihash code and digest width. Each entry is the digest
+/// followed by the little endian `u64` offset of the section.
+#[derive(Clone, Debug, Default)]
+struct Bucket {
+    width: usize,
+    entries: Vec<u8>,
+}
+
+impl Bucket {
+    fn find(&self, digest: &[u8]) -> Option<u64> {
+        let len = self.entries.len() / self.width;
+        let entry = |i: usize| &self.entries[i * self.width..(i + 1) * self.width];
+        let (mut lo, mut hi) = (0, len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let entry = entry(mid);
+            match entry[..digest.len()].cmp(digest) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => {
+                    return Some(u64::from_le_bytes(
+                        entry[digest.len()..].try_into().unwrap(),
+                    ))
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Index entries keyed by multihash code and entry width.
+type Buckets<T> = BTreeMap<(u64, usize), T>;
+
+/// `MultihashIndexSorted` index.
+#[derive(Clone, Debug, Default)]
+pub struct Index {
+    buckets: Buckets<Bucket>,
+}
+
+impl Index {
+    /// Creates an index from `(cid, offset)` pairs.
+    pub fn new<I: IntoIterator<Item = (Cid, u64)>>(entries: I) -> Self {
+        let mut sorted: Buckets<Vec<(Vec<u8>, u64)>> = BTreeMap::new();
+        for (cid, offset) in entries {
+            let digest = cid.hash().digest().to_vec();
+            sorted
+                .entry((cid.hash().code(), digest.len() + 8))
+                .or_default()
+                .push((digest, offset));
+        }
+        let buckets = sorted
+            .into_iter()
+            .map(|(key, mut entries)| {
+                entries.sort();
+                let mut bytes = Vec::with_capacity(entries.len() * key.1);
+                for (digest, offset) in entries {
+                    bytes.extend_from_slice(&digest);
+                    bytes.extend_from_slice(&offset.to_le_bytes());
+                }
+                let bucket = Bucket {
+                    width: key.1,
+                    entries: bytes,
+                };
+                (key, bucket)
+            })
+            .collect();
+        Self { buckets }
+    }
+
+    /// Returns the offset of the section of a block in the CARv1 payload.
+    pub fn get(&self, cid: &Cid) -> Option<u64> {
+        let digest = cid.hash().digest();
+        self.buckets
+            .get(&(cid.hash().code(), digest.len() + 8))
+            .and_then(|bucket| bucket.find(digest))
+    }
+
+    /// Writes the index.
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<()> {
+        write_varint(w, MULTIHASH_INDEX_SORTED)?;
+        let mut codes: BTreeMap<u64, Vec<&Bucket>> = BTreeMap::new();
+        for ((code, _), bucket) in &self.buckets {
+            codes.entry(*code).or_default().push(bucket);
+        }
+        w.write_all(&(codes.len() as u32).to_le_bytes())?;
+        for (code, buckets) in codes {
+            w.write_all(&code.to_le_bytes())?;
+            w.write_all(&(buckets.len() as u32).to_le_bytes())?;
+            for bucket in buckets {
+                w.write_all(&(bucket.width as u32).to_le_bytes())?;
+                w.write_all(&(bucket.entries.len() as u64).to_le_bytes())?;
+                w.write_all(&bucket.entries)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads an index.
+    pub fn read<R: Read>(r: &mut R) -> Result<Self> {
+        if super::read_varint(r)? != Some(MULTIHASH_INDEX_SORTED) {
+            return Err(InvalidIndex("unsupported index codec").into());
+        }
+        let mut buckets = BTreeMap::new();
+        for _ in 0..read_u32(r)? {
+            let code = read_u64(r)?;
+            for _ in 0..read_u32(r)? {
+                let width = read_u32(r)? as usize;
+                let len = read_u64(r)? as usize;
+                if width <= 8 || !len.is_multiple_of(width) {
+                    return Err(InvalidIndex("invalid bucket width").into());
+                }
+                let mut entries = vec![];
+                r.take(len as u64).read_to_end(&mut entries)?;
+                if entries.len() != len {
+                    return Err(InvalidIndex("truncated bucket").into());
+                }
+                buckets.insert((code, width), Bucket { width, entries });
+            }
+        }
+        Ok(Self { buckets })
+    }
+}
+
+/// CARv2 writer.
+///
+/// The header is written when the writer is finished, which requires seeking back to the start
+/// of the archive.
+#[derive(Debug)]
+pub struct CarV2Writer<W> {
+    w: W,
+    start: u64,
+    data_size: u64,
+    entries: Vec<(Cid, u64)>,
+}
+
+impl<W: Write + Seek> CarV2Writer<W> {
+    /// Creates a new writer and writes the pragma and the CARv1 header.
+    pub fn new(roots: Vec<Cid>, mut w: W) -> Result<Self> {
+        let start = w.stream_position()?;
+        w.write_all(&PRAGMA)?;
+        w.write_all(&CarV2Header::default().to_bytes())?;
+        let header = CarHeader::new(roots).encode()?;
+        let mut prefix = vec![];
+        write_varint(&mut prefix, header.len() as u64)?;
+        w.write_all(&prefix)?;
+        w.write_all(&header)?;
+        Ok(Self {
+            w,
+            start,
+            data_size: (prefix.len() + header.len()) as u64,
+            entries: vec![],
+        })
+    }
+
+    /// Writes a block.
+    pub fn write<S>(&mut self, block: &Block<S>) -> Result<()> {
+        let cid = *AsRef::<Cid>::as_ref(block);
+        self.entries.push((cid, self.data_size));
+        self.data_size += write_section(&mut self.w, block)?;
+        Ok(())
+    }
+
+    /// Writes the index and the header and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        let data_offset = (PRAGMA.len() + HEADER_SIZE) as u64;
+        let header = CarV2Header {
+            characteristics: 0,
+            data_offset,
+            data_size: self.data_size,
+            index_offset: data_offset + self.data_size,
+        };
+        Index::new(self.entries).write(&mut self.w)?;
+        let end = self.w.stream_position()?;
+        self.w
+            .seek(SeekFrom::Start(self.start + PRAGMA.len() as u64))?;
+        self.w.write_all(&header.to_bytes())?;
+        self.w.seek(SeekFrom::Start(end))?;
+        self.w.flush()?;
+        Ok(self.w)
+    }
+}
+
+/// CARv2 reader with random access to blocks.
+#[derive(Debug)]
+pub struct CarV2Reader<R> {
+    r: R,
+    start: u64,
+    header: CarV2Header,
+    car_header: CarHeader,
+    index: Option<Index>,
+}
+
+impl<R: Read + Seek> CarV2Reader<R> {
+    /// Opens an archive and reads its headers and index.
+    pub fn new(mut r: R) -> Result<Self> {
+        let start = r.stream_position()?;
+        let mut pragma = [0; PRAGMA.len()];
+        r.read_exact(&mut pragma)?;
+        if pragma != PRAGMA {
+            return Err(InvalidPragma.into());
+        }
+        let mut buf = [0; HEADER_SIZE];
+        r.read_exact(&mut buf)?;
+        let header = CarV2Header::from_bytes(&buf);
+        r.seek(SeekFrom::Start(start + header.data_offset))?;
+        let car_header = read_header(&mut (&mut r).take(header.data_size))?;
+        let index = if header.index_offset != 0 {
+            r.seek(SeekFrom::Start(start + header.index_offset))?;
+            Some(Index::read(&mut r)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            r,
+            start,
+            header,
+            car_header,
+            index,
+        })
+    }
+
+    /// Returns the CARv2 header.
+    pub fn header(&self) -> &CarV2Header {
+        &self.header
+    }
+
+    /// Returns the roots of the archive.
+    pub fn roots(&self) -> &[Cid] {
+        &self.car_header.roots
+    }
+
+    /// Returns the index.
+    pub fn index(&self) -> Option<&Index> {
+        self.index.as_ref()
+    }
+
+    /// Returns a block from the archive using the index.
+    pub fn get<S: StoreParams>(&mut self, cid: &Cid) -> Result<Block<S>> {
+        let index = self.index.as_ref().ok_or(MissingIndex)?;
+        let offset = index.get(cid).ok_or(BlockNotFound(*cid))?;
+        if offset >= self.header.data_size {
+            return Err(InvalidIndex("offset out of range").into());
+        }
+        self.r.seek(SeekFrom::Start(
+            self.start + self.header.data_offset + offset,
+        ))?;
+        let mut r = (&mut self.r).take(self.header.data_size - offset);
+        let block: Block<S> = read_section(&mut r)?.ok_or(BlockNotFound(*cid))?;
+        if block.cid().hash() != cid.hash() {
+            return Err(InvalidIndex("entry points to a different block").into());
+        }
+        Ok(Block::new_unchecked(*cid, block.into_inner().1))
+    }
+
+    /// Returns a reader over all blocks of the CARv1 payload.
+    pub fn blocks<S: StoreParams>(mut self) -> Result<CarReader<Take<R>, S>> {
+        self.r
+            .seek(SeekFrom::Start(self.start + self.header.data_offset))?;
+        let mut r = self.r.take(self.header.data_size);
+        let header = read_header(&mut r)?;
+        Ok(CarReader::with_header(header, r))
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::car::{CarWriter, UnsupportedCarVersion};
+    use crate::cbor::DagCborCodec;
+    use crate::ipld;
+    use crate::ipld::Ipld;
+    use crate::multihash::Code;
+    use crate::store::DefaultParams;
+    use std::io::Cursor;
+
+    type IpldBlock = Block<DefaultParams>;
+
+    fn block(ipld: &Ipld) -> IpldBlock {
+        IpldBlock::encode(DagCborCodec, Code::Blake3_256, ipld).unwrap()
+    }
+
+    fn archive(blocks: &[IpldBlock]) -> Vec<u8> {
+        let mut writer = CarV2Writer::new(vec![*blocks[0].cid()], Cursor::new(vec![])).unwrap();
+        for block in blocks {
+            writer.write(block).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_header() {
+        let header = CarV2Header {
+            characteristics: 1 << 127,
+            data_offset: 51,
+            data_size: 448,
+            index_offset: 499,
+        };
+        assert_eq!(CarV2Header::from_bytes(&header.to_bytes()), header);
+    }
+
+    #[test]
+    fn test_random_access() {
+        let blocks: Vec<_> = (0..100).map(|i| block(&ipld!(i))).collect();
+        let sha = IpldBlock::encode(DagCborCodec, Code::Sha2_512, &ipld!("sha")).unwrap();
+        let mut all = blocks.clone();
+        all.push(sha.clone());
+        let bytes = archive(&all);
+
+        let mut reader = CarV2Reader::new(Cursor::new(&bytes)).unwrap();
+        assert_eq!(reader.roots(), &[*blocks[0].cid()]);
+        assert_eq!(reader.header().data_offset, 51);
+        for block in all.iter().rev() {
+            assert_eq!(&reader.get::<DefaultParams>(block.cid()).unwrap(), block);
+        }
+        let missing = block(&ipld!("missing"));
+        let err = reader.get::<DefaultParams>(missing.cid()).unwrap_err();
+        assert!(err.is::<BlockNotFound>());
+
+        let read = reader
+            .blocks::<DefaultParams>()
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(read, all);
+    }
+
+    #[test]
+    fn test_invalid_pragma() {
+        let mut writer = CarWriter::new(vec![], vec![]).unwrap();
+        writer.write(&block(&ipld!("a"))).unwrap();
+        let bytes = writer.into_inner();
+        let err = CarV2Reader::new(Cursor::new(&bytes)).unwrap_err();
+        assert!(err.is::<InvalidPragma>());
+    }
+
+    #[test]
+    fn test_v1_reader_rejects_v2() {
+        let bytes = archive(&[block(&ipld!("a"))]);
+        let err = CarReader::<_, DefaultParams>::new(&bytes[..]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<UnsupportedCarVersion>(),
+            Some(super::super::UnsupportedCarVersion(2))
+        ));
+    }
+}
@@ -0,0 +1,459 @@
+//! Filesystem block store.
+//!
+//! Every block is stored in its own file, named by the base32 encoded cid and sharded into
+//! directories by the next-to-last two characters of the name:
+//!
+//! ```text
+//! <root>/blocks/<shard>/<cid>
+//! <root>/aliases/<alias>
+//! <root>/tmp/
+//! ```
+//!
+//! Files are written to `tmp` first and then renamed into place, so a crash never leaves a
+//! partially written block or alias behind. Leftovers of interrupted writes are removed from
+//! `tmp` once they are older than an hour, so several stores can share a root. Aliases survive
+//! restarts, temporary pins don't.
+//! Garbage collection only runs when [`Store::gc`] is called.
+use crate::block::Block;
+use crate::cid::Cid;
+use crate::codec::References;
+use crate::error::{BlockNotFound, Result};
+use crate::ipld::Ipld;
+use crate::multibase::{self, Base};
+use crate::store::{Store, StoreParams};
+use core::convert::TryFrom;
+use core::marker::PhantomData;
+use fnv::{FnvHashMap, FnvHashSet};
+use std::collections::hash_map::RandomState;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, SystemTime};
+
+/// Age after which a file in `tmp` is considered a leftover of an interrupted write.
+const STALE_TMP: Duration = Duration::from_secs(60 * 60);
+
+type TempPins = Mutex<FnvHashMap<u64, FnvHashSet<Cid>>>;
+
+/// Filesystem block store.
+#[derive(Debug)]
+pub struct FsStore<S> {
+    _marker: PhantomData<S>,
+    root: PathBuf,
+    verify: bool,
+    next_tmp: Arc<AtomicU64>,
+    temp_pins: Arc<TempPins>,
+}
+
+impl<S> Clone for FsStore<S> {
+    fn clone(&self) -> Self {
+        Self {
+            _marker: PhantomData,
+            root: self.root.clone(),
+            verify: self.verify,
+            next_tmp: self.next_tmp.clone(),
+            temp_pins: self.temp_pins.clone(),
+        }
+    }
+}
+
+impl<S: StoreParams> FsStore<S> {
+    /// Opens or creates a store in the directory `root`.
+    ///
+    /// When `verify` is set, the hash of every block read from disk is checked against its cid.
+    pub fn open<P: AsRef<Path>>(root: P, verify: bool) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(root.join("blocks"))?;
+        fs::create_dir_all(root.join("aliases"))?;
+        fs::create_dir_all(root.join("tmp"))?;
+        remove_stale(&root.join("tmp"))?;
+        Ok(Self {
+            _marker: PhantomData,
+            root,
+            verify,
+            next_tmp: Default::default(),
+            temp_pins: Default::default(),
+        })
+    }
+
+    /// Returns the root directory of the store.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn block_path(&self, cid: &Cid) -> PathBuf {
+        let name = multibase::encode(Base::Base32Lower, cid.to_bytes());
+        let shard = &name[name.len() - 3..name.len() - 1];
+        self.root.join("blocks").join(shard).join(name)
+    }
+
+    fn alias_path(&self, alias: &[u8]) -> PathBuf {
+        self.root
+            .join("aliases")
+            .join(multibase::encode(Base::Base32Lower, alias))
+    }
+
+    /// Creates a new file with a unique name in `tmp`.
+    fn create_tmp(&self) -> Result<(PathBuf, File)> {
+        loop {
+            let tmp = self.root.join("tmp").join(random_name());
+            match OpenOptions::new().write(true).create_new(true).open(&tmp) {
+                Ok(file) => return Ok((tmp, file)),
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Atomically writes `data` to `path`.
+    fn write_atomic(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let (tmp, mut file) = self.create_tmp()?;
+        let res = file.write_all(data).and_then(|_| file.sync_all());
+        drop(file);
+        if let Err(err) = res {
+            let _ = fs::remove_file(&tmp);
+            return Err(err.into());
+        }
+        let parent = path.parent().unwrap_or(&self.root);
+        fs::create_dir_all(parent)?;
+        if let Err(err) = fs::rename(&tmp, path) {
+            let _ = fs::remove_file(&tmp);
+            return Err(err.into());
+        }
+        sync_dir(parent)?;
+        Ok(())
+    }
+
+    fn read_block(&self, cid: &Cid) -> Result<Option<Block<S>>> {
+        let data = match fs::read(self.block_path(cid)) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        if self.verify {
+            Ok(Some(Block::new(*cid, data)?))
+        } else {
+            Ok(Some(Block::new_unchecked(*cid, data)))
+        }
+    }
+
+    fn aliases(&self) -> Result<Vec<Cid>> {
+        let mut roots = vec![];
+        for entry in fs::read_dir(self.root.join("aliases"))? {
+            let data = fs::read(entry?.path())?;
+            roots.push(Cid::try_from(data)?);
+        }
+        Ok(roots)
+    }
+
+    fn blocks(&self) -> Result<Vec<(Cid, PathBuf)>> {
+        let mut blocks = vec![];
+        for shard in fs::read_dir(self.root.join("blocks"))? {
+            for entry in fs::read_dir(shard?.path())? {
+                let path = entry?.path();
+                let name = path.file_name().and_then(|name| name.to_str());
+                if let Some(Ok((_, bytes))) = name.map(multibase::decode) {
+                    blocks.push((Cid::try_from(bytes)?, path));
+                }
+            }
+        }
+        Ok(blocks)
+    }
+}
+
+impl<S: StoreParams> FsStore<S>
+where
+    Ipld: References<S::Codecs>,
+{
+    /// Returns the set of blocks that are reachable from an alias or a temporary pin.
+    fn live(&self) -> Result<FnvHashSet<Cid>> {
+        let mut stack = self.aliases()?;
+        stack.extend(self.temp_pins.lock().unwrap().values().flatten());
+        let mut live = FnvHashSet::default();
+        while let Some(cid) = stack.pop() {
+            if !live.insert(cid) {
+                continue;
+            }
+            // An aliased dag doesn't need to be complete.
+            if let Some(block) = self.read_block(&cid)? {
+                let mut refs = FnvHashSet::default();
+                block.references(&mut refs)?;
+                stack.extend(refs.into_iter().filter(|cid| !live.contains(cid)));
+            }
+        }
+        Ok(live)
+    }
+}
+
+/// Temporary pin of a [`FsStore`].
+#[derive(Debug)]
+pub struct FsTempPin {
+    id: u64,
+    temp_pins: Weak<TempPins>,
+}
+
+impl Drop for FsTempPin {
+    fn drop(&mut self) {
+        if let Some(temp_pins) = self.temp_pins.upgrade() {
+            if let Ok(mut temp_pins) = temp_pins.lock() {
+                temp_pins.remove(&self.id);
+            }
+        }
+    }
+}
+
+impl<S: StoreParams> Store for FsStore<S>
+where
+    Ipld: References<S::Codecs>,
+{
+    type Params = S;
+    type TempPin = FsTempPin;
+
+    fn get(&self, cid: &Cid) -> Result<Block<S>> {
+        self.read_block(cid)?
+            .ok_or_else(|| BlockNotFound(*cid).into())
+    }
+
+    fn has(&self, cid: &Cid) -> Result<bool> {
+        match fs::metadata(self.block_path(cid)) {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn insert(&self, block: Block<S>) -> Result<()> {
+        if self.has(block.cid())? {
+            return Ok(());
+        }
+        self.write_atomic(&self.block_path(block.cid()), block.data())
+    }
+
+    fn remove(&self, cid: &Cid) -> Result<()> {
+        if self.live()?.contains(cid) {
+            return Ok(());
+        }
+        match fs::remove_file(self.block_path(cid)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn alias<T: AsRef<[u8]>>(&self, alias: T, cid: Option<&Cid>) -> Result<()> {
+        let path = self.alias_path(alias.as_ref());
+        if let Some(cid) = cid {
+            self.write_atomic(&path, &cid.to_bytes())
+        } else {
+            match fs::remove_file(path) {
+                Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    fn resolve<T: AsRef<[u8]>>(&self, alias: T) -> Result<Option<Cid>> {
+        match fs::read(self.alias_path(alias.as_ref())) {
+            Ok(data) => Ok(Some(Cid::try_from(data)?)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn create_temp_pin(&self) -> Result<Self::TempPin> {
+        let id = self.next_tmp.fetch_add(1, Ordering::Relaxed);
+        self.temp_pins
+            .lock()
+            .unwrap()
+            .insert(id, Default::default());
+        Ok(FsTempPin {
+            id,
+            temp_pins: Arc::downgrade(&self.temp_pins),
+        })
+    }
+
+    fn temp_pin(&self, tmp: &mut Self::TempPin, cid: &Cid) -> Result<()> {
+        self.temp_pins
+            .lock()
+            .unwrap()
+            .entry(tmp.id)
+            .or_default()
+            .insert(*cid);
+        Ok(())
+    }
+
+    fn gc(&self) -> Result<()> {
+        let live = self.live()?;
+        for (cid, path) in self.blocks()? {
+            if !live.contains(&cid) {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns a random file name.
+fn random_name() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    if let Ok(now) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(now.as_nanos());
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/// Removes the files in `dir` that haven't been modified for [`STALE_TMP`].
+fn remove_stale(dir: &Path) -> Result<()> {
+    let now = SystemTime::now();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let modified = match entry.metadata().and_then(|meta| meta.modified()) {
+            Ok(modified) => modified,
+            Err(_) => continue,
+        };
+        if now.duration_since(modified).unwrap_or_default() > STALE_TMP {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Flushes the directory entries of `dir` to disk.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> Result<()> {
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+/// Directories can't be opened on this platform, renames are durable once they return.
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbor::DagCborCodec;
+    use crate::ipld;
+    use crate::multihash::Code;
+    use crate::store::DefaultParams;
+
+    type IpldBlock = Block<DefaultParams>;
+
+    fn block(ipld: &Ipld) -> IpldBlock {
+        IpldBlock::encode(DagCborCodec, Code::Blake3_256, ipld).unwrap()
+    }
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("libipld-fs-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_get_insert_remove() {
+        let dir = TempDir::new("get-insert-remove");
+        let store = FsStore::<DefaultParams>::open(&dir.0, true).unwrap();
+        let a = block(&ipld!("a"));
+        assert!(!store.has(a.cid()).unwrap());
+        assert!(store.get(a.cid()).unwrap_err().is::<BlockNotFound>());
+        store.insert(a.clone()).unwrap();
+        assert!(store.has(a.cid()).unwrap());
+        assert_eq!(store.get(a.cid()).unwrap().data(), a.data());
+        store.remove(a.cid()).unwrap();
+        assert!(!store.has(a.cid()).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_has_error() {
+        let dir = TempDir::new("has-error");
+        let store = FsStore::<DefaultParams>::open(&dir.0, true).unwrap();
+        let a = block(&ipld!("a"));
+        let path = store.block_path(a.cid());
+        let shard = path.parent().unwrap();
+        let _ = fs::remove_dir_all(shard);
+        fs::write(shard, b"").unwrap();
+        assert!(store.has(a.cid()).is_err());
+        assert!(store.insert(a).is_err());
+    }
+
+    #[test]
+    fn test_persistence() {
+        let dir = TempDir::new("persistence");
+        let a = block(&ipld!("a"));
+        let b = block(&ipld!([a.cid()]));
+        {
+            let store = FsStore::<DefaultParams>::open(&dir.0, true).unwrap();
+            store.insert(a.clone()).unwrap();
+            store.insert(b.clone()).unwrap();
+            store.alias(b"root", Some(b.cid())).unwrap();
+        }
+        let store = FsStore::<DefaultParams>::open(&dir.0, true).unwrap();
+        assert_eq!(store.resolve(b"root").unwrap(), Some(*b.cid()));
+        store.gc().unwrap();
+        assert!(store.has(a.cid()).unwrap());
+        assert!(store.has(b.cid()).unwrap());
+        store.alias(b"root", None).unwrap();
+        store.gc().unwrap();
+        assert!(!store.has(a.cid()).unwrap());
+        assert!(!store.has(b.cid()).unwrap());
+    }
+
+    #[test]
+    fn test_temp_pin() {
+        let dir = TempDir::new("temp-pin");
+        let store = FsStore::<DefaultParams>::open(&dir.0, true).unwrap();
+        let a = block(&ipld!("a"));
+        let mut tmp = store.create_temp_pin().unwrap();
+        store.temp_pin(&mut tmp, a.cid()).unwrap();
+        store.insert(a.clone()).unwrap();
+        store.gc().unwrap();
+        assert!(store.has(a.cid()).unwrap());
+        drop(tmp);
+        store.gc().unwrap();
+        assert!(!store.has(a.cid()).unwrap());
+    }
+
+    #[test]
+    fn test_verify() {
+        let dir = TempDir::new("verify");
+        let a = block(&ipld!("a"));
+        let store = FsStore::<DefaultParams>::open(&dir.0, true).unwrap();
+        store.insert(a.clone()).unwrap();
+        fs::write(store.block_path(a.cid()), b"corrupt").unwrap();
+        assert!(store.get(a.cid()).is_err());
+        let store = FsStore::<DefaultParams>::open(&dir.0, false).unwrap();
+        assert_eq!(store.get(a.cid()).unwrap().data(), b"corrupt");
+    }
+
+    #[test]
+    fn test_shared_root() {
+        let dir = TempDir::new("shared-root");
+        let a = block(&ipld!("a"));
+        let b = block(&ipld!("b"));
+        let store1 = FsStore::<DefaultParams>::open(&dir.0, true).unwrap();
+        let (pending, _file) = store1.create_tmp().unwrap();
+        let store2 = FsStore::<DefaultParams>::open(&dir.0, true).unwrap();
+        assert!(pending.exists());
+        store1.insert(a.clone()).unwrap();
+        store2.insert(b.clone()).unwrap();
+        assert_eq!(store2.get(a.cid()).unwrap().data(), a.data());
+        assert_eq!(store1.get(b.cid()).unwrap().data(), b.data());
+    }
+}
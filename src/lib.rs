@@ -3,28 +3,43 @@
 #![deny(warnings)]
 
 pub mod block;
+#[cfg(feature = "dag-cbor")]
+pub mod car;
 pub mod codec_impl;
+pub mod fs;
+pub mod mem;
 pub mod path;
 pub mod prelude;
+pub mod selector;
 pub mod store;
+pub mod traversal;
 
 #[cfg(feature = "dag-cbor")]
 pub use libipld_cbor as cbor;
 #[cfg(all(feature = "dag-cbor", feature = "derive"))]
 pub use libipld_cbor_derive::DagCbor;
+#[cfg(all(feature = "dag-json", feature = "derive"))]
+pub use libipld_cbor_derive::DagJson;
+#[cfg(feature = "derive")]
+pub use libipld_cbor_derive::{IntoIpld, TryFromIpld};
 pub use libipld_core::*;
 #[cfg(feature = "dag-json")]
 pub use libipld_json as json;
 pub use libipld_macro::*;
 #[cfg(feature = "dag-pb")]
 pub use libipld_pb as pb;
+#[cfg(feature = "schema")]
+pub use libipld_schema as schema;
 
 pub use block::Block;
 pub use cid::Cid;
 pub use codec_impl::IpldCodec;
 pub use error::Result;
 pub use ipld::Ipld;
+pub use ipld_ref::IpldRef;
 pub use link::Link;
 pub use multihash::Multihash;
 pub use path::{DagPath, Path};
-pub use store::DefaultParams;
+#[cfg(feature = "async")]
+pub use store::AsyncStore;
+pub use store::{DefaultParams, Store};
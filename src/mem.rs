@@ -0,0 +1,394 @@
+//! In-memory block store.
+//!
+//! Implements the aliasing, temporary pinning and garbage collection semantics described in
+//! [`store`](crate::store). Blocks are collected in insertion order once the configured size is
+//! exceeded.
+use crate::block::Block;
+use crate::cid::Cid;
+use crate::codec::References;
+use crate::error::{BlockNotFound, Result};
+use crate::ipld::Ipld;
+use crate::store::{Store, StoreParams};
+use fnv::{FnvHashMap, FnvHashSet};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+
+#[derive(Debug)]
+struct MemStoreInner<S> {
+    blocks: FnvHashMap<Cid, Block<S>>,
+    order: VecDeque<Cid>,
+    aliases: FnvHashMap<Vec<u8>, Cid>,
+    temp_pins: FnvHashMap<u64, FnvHashSet<Cid>>,
+    next_temp_pin: u64,
+    /// Cached result of [`MemStoreInner::live`], cleared when a root is removed.
+    live: Option<FnvHashSet<Cid>>,
+    size: usize,
+    max_size: usize,
+}
+
+impl<S: StoreParams> MemStoreInner<S>
+where
+    Ipld: References<S::Codecs>,
+{
+    /// Returns the set of blocks that are reachable from an alias or a temporary pin.
+    fn live(&mut self) -> Result<&FnvHashSet<Cid>> {
+        if self.live.is_none() {
+            let mut live = FnvHashSet::default();
+            let roots = self
+                .aliases
+                .values()
+                .chain(self.temp_pins.values().flatten())
+                .copied()
+                .collect();
+            self.mark_live(&mut live, roots)?;
+            self.live = Some(live);
+        }
+        Ok(self.live.as_ref().unwrap())
+    }
+
+    /// Adds the blocks reachable from `stack` to `live`.
+    fn mark_live(&self, live: &mut FnvHashSet<Cid>, mut stack: Vec<Cid>) -> Result<()> {
+        while let Some(cid) = stack.pop() {
+            if !live.insert(cid) {
+                continue;
+            }
+            // An aliased dag doesn't need to be complete.
+            if let Some(block) = self.blocks.get(&cid) {
+                let mut refs = FnvHashSet::default();
+                block.references(&mut refs)?;
+                stack.extend(refs.into_iter().filter(|cid| !live.contains(cid)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Updates the cached live set after `cid` became reachable, either because it was added as
+    /// a root or because it was inserted while already being referenced by a live block.
+    fn extend_live(&mut self, cid: Cid) -> Result<()> {
+        if let Some(mut live) = self.live.take() {
+            live.remove(&cid);
+            self.mark_live(&mut live, vec![cid])?;
+            self.live = Some(live);
+        }
+        Ok(())
+    }
+
+    fn remove_unchecked(&mut self, cid: &Cid) {
+        if let Some(block) = self.blocks.remove(cid) {
+            self.size -= block.data().len();
+            self.order.retain(|other| other != cid);
+        }
+    }
+
+    /// Removes unprotected blocks in insertion order until the size target is met. When `full`
+    /// is set all unprotected blocks are removed. The `keep` block is never removed.
+    fn gc(&mut self, full: bool, keep: Option<&Cid>) -> Result<()> {
+        self.live()?;
+        let live = self.live.take().unwrap_or_default();
+        let mut order = std::mem::take(&mut self.order);
+        order.retain(|cid| {
+            if live.contains(cid) || Some(cid) == keep || (!full && self.size <= self.max_size) {
+                return true;
+            }
+            if let Some(block) = self.blocks.remove(cid) {
+                self.size -= block.data().len();
+            }
+            false
+        });
+        self.order = order;
+        self.live = Some(live);
+        Ok(())
+    }
+}
+
+/// In-memory block store.
+#[derive(Debug)]
+pub struct MemStore<S> {
+    inner: Arc<Mutex<MemStoreInner<S>>>,
+}
+
+impl<S> Clone for MemStore<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S> Default for MemStore<S> {
+    fn default() -> Self {
+        Self::new(usize::MAX)
+    }
+}
+
+impl<S> MemStore<S> {
+    /// Creates a new store that starts collecting unaliased blocks when the total size of the
+    /// stored blocks exceeds `max_size` bytes.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(MemStoreInner {
+                blocks: Default::default(),
+                order: Default::default(),
+                aliases: Default::default(),
+                temp_pins: Default::default(),
+                next_temp_pin: 0,
+                live: None,
+                size: 0,
+                max_size,
+            })),
+        }
+    }
+
+    /// Returns the total size of the stored blocks.
+    pub fn size(&self) -> usize {
+        self.lock().size
+    }
+
+    /// Returns the number of stored blocks.
+    pub fn len(&self) -> usize {
+        self.lock().blocks.len()
+    }
+
+    /// Returns true if the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.lock().blocks.is_empty()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemStoreInner<S>> {
+        self.inner.lock().unwrap()
+    }
+}
+
+/// Temporary pin of a [`MemStore`].
+#[derive(Debug)]
+pub struct MemTempPin<S> {
+    id: u64,
+    inner: Weak<Mutex<MemStoreInner<S>>>,
+}
+
+impl<S> Drop for MemTempPin<S> {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.upgrade() {
+            if let Ok(mut inner) = inner.lock() {
+                inner.temp_pins.remove(&self.id);
+                inner.live = None;
+            }
+        }
+    }
+}
+
+impl<S: StoreParams> Store for MemStore<S>
+where
+    Ipld: References<S::Codecs>,
+{
+    type Params = S;
+    type TempPin = MemTempPin<S>;
+
+    fn get(&self, cid: &Cid) -> Result<Block<S>> {
+        self.lock()
+            .blocks
+            .get(cid)
+            .cloned()
+            .ok_or_else(|| BlockNotFound(*cid).into())
+    }
+
+    fn has(&self, cid: &Cid) -> Result<bool> {
+        Ok(self.lock().blocks.contains_key(cid))
+    }
+
+    fn insert(&self, block: Block<S>) -> Result<()> {
+        let mut inner = self.lock();
+        if inner.blocks.contains_key(block.cid()) {
+            return Ok(());
+        }
+        let cid = *block.cid();
+        inner.size += block.data().len();
+        inner.order.push_back(cid);
+        inner.blocks.insert(cid, block);
+        if inner.live.as_ref().map(|live| live.contains(&cid)) == Some(true) {
+            inner.extend_live(cid)?;
+        }
+        if inner.size > inner.max_size {
+            inner.gc(false, Some(&cid))?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, cid: &Cid) -> Result<()> {
+        let mut inner = self.lock();
+        if !inner.live()?.contains(cid) {
+            inner.remove_unchecked(cid);
+        }
+        Ok(())
+    }
+
+    fn alias<T: AsRef<[u8]>>(&self, alias: T, cid: Option<&Cid>) -> Result<()> {
+        let mut inner = self.lock();
+        let old = if let Some(cid) = cid {
+            inner.extend_live(*cid)?;
+            inner.aliases.insert(alias.as_ref().to_vec(), *cid)
+        } else {
+            inner.aliases.remove(alias.as_ref())
+        };
+        if old.is_some() {
+            inner.live = None;
+        }
+        Ok(())
+    }
+
+    fn resolve<T: AsRef<[u8]>>(&self, alias: T) -> Result<Option<Cid>> {
+        Ok(self.lock().aliases.get(alias.as_ref()).copied())
+    }
+
+    fn create_temp_pin(&self) -> Result<Self::TempPin> {
+        let mut inner = self.lock();
+        let id = inner.next_temp_pin;
+        inner.next_temp_pin += 1;
+        inner.temp_pins.insert(id, Default::default());
+        Ok(MemTempPin {
+            id,
+            inner: Arc::downgrade(&self.inner),
+        })
+    }
+
+    fn temp_pin(&self, tmp: &mut Self::TempPin, cid: &Cid) -> Result<()> {
+        let mut inner = self.lock();
+        inner.extend_live(*cid)?;
+        inner.temp_pins.entry(tmp.id).or_default().insert(*cid);
+        Ok(())
+    }
+
+    fn gc(&self) -> Result<()> {
+        self.lock().gc(true, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbor::DagCborCodec;
+    use crate::ipld;
+    use crate::multihash::Code;
+    use crate::store::DefaultParams;
+
+    type IpldBlock = Block<DefaultParams>;
+
+    fn block(ipld: &Ipld) -> IpldBlock {
+        IpldBlock::encode(DagCborCodec, Code::Blake3_256, ipld).unwrap()
+    }
+
+    #[test]
+    fn test_get_insert_remove() {
+        let store = MemStore::<DefaultParams>::default();
+        let a = block(&ipld!("a"));
+        assert!(!store.has(a.cid()).unwrap());
+        assert!(store.get(a.cid()).unwrap_err().is::<BlockNotFound>());
+        store.insert(a.clone()).unwrap();
+        assert!(store.has(a.cid()).unwrap());
+        assert_eq!(store.get(a.cid()).unwrap(), a);
+        store.remove(a.cid()).unwrap();
+        assert!(!store.has(a.cid()).unwrap());
+        assert!(store.is_empty());
+        assert_eq!(store.size(), 0);
+    }
+
+    #[test]
+    fn test_alias_protects_dag() {
+        let store = MemStore::<DefaultParams>::default();
+        let a = block(&ipld!("a"));
+        let b = block(&ipld!({ "a": a.cid() }));
+        let c = block(&ipld!([b.cid()]));
+        let d = block(&ipld!("d"));
+        for block in [&a, &b, &c, &d] {
+            store.insert(block.clone()).unwrap();
+        }
+        store.alias(b"root", Some(c.cid())).unwrap();
+        assert_eq!(store.resolve(b"root").unwrap(), Some(*c.cid()));
+        store.remove(a.cid()).unwrap();
+        store.gc().unwrap();
+        assert!(store.has(a.cid()).unwrap());
+        assert!(store.has(b.cid()).unwrap());
+        assert!(store.has(c.cid()).unwrap());
+        assert!(!store.has(d.cid()).unwrap());
+
+        store.alias(b"root", None).unwrap();
+        assert_eq!(store.resolve(b"root").unwrap(), None);
+        store.gc().unwrap();
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_incomplete_dag() {
+        let store = MemStore::<DefaultParams>::default();
+        let a = block(&ipld!("a"));
+        let b = block(&ipld!([a.cid()]));
+        store.insert(b.clone()).unwrap();
+        store.alias(b"root", Some(b.cid())).unwrap();
+        store.gc().unwrap();
+        assert!(store.has(b.cid()).unwrap());
+    }
+
+    #[test]
+    fn test_temp_pin() {
+        let store = MemStore::<DefaultParams>::default();
+        let a = block(&ipld!("a"));
+        let b = block(&ipld!([a.cid()]));
+        let mut tmp = store.create_temp_pin().unwrap();
+        store.temp_pin(&mut tmp, b.cid()).unwrap();
+        store.insert(a.clone()).unwrap();
+        store.insert(b.clone()).unwrap();
+        store.gc().unwrap();
+        assert!(store.has(a.cid()).unwrap());
+        assert!(store.has(b.cid()).unwrap());
+        drop(tmp);
+        store.gc().unwrap();
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_incremental_gc() {
+        let a = block(&ipld!("a"));
+        let b = block(&ipld!("b"));
+        let c = block(&ipld!("c"));
+        let store = MemStore::<DefaultParams>::new(a.data().len() * 2);
+        store.insert(a.clone()).unwrap();
+        store.alias(b"a", Some(a.cid())).unwrap();
+        store.insert(b.clone()).unwrap();
+        store.insert(c.clone()).unwrap();
+        assert!(store.has(a.cid()).unwrap());
+        assert!(!store.has(b.cid()).unwrap());
+        assert!(store.has(c.cid()).unwrap());
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn test_insert_into_full_store() {
+        let a = block(&ipld!("a"));
+        let b = block(&ipld!("b"));
+        let store = MemStore::<DefaultParams>::new(a.data().len());
+        store.insert(a.clone()).unwrap();
+        store.alias(b"a", Some(a.cid())).unwrap();
+        store.insert(b.clone()).unwrap();
+        assert_eq!(store.get(b.cid()).unwrap(), b);
+        assert_eq!(store.get(a.cid()).unwrap(), a);
+    }
+
+    #[test]
+    fn test_live_cache() {
+        let a = block(&ipld!("a"));
+        let b = block(&ipld!([a.cid()]));
+        let c = block(&ipld!("c"));
+        let store = MemStore::<DefaultParams>::new(0);
+        store.alias(b"root", Some(b.cid())).unwrap();
+        store.insert(b.clone()).unwrap();
+        store.insert(a.clone()).unwrap();
+        store.insert(c.clone()).unwrap();
+        assert!(store.has(a.cid()).unwrap());
+        assert!(store.has(b.cid()).unwrap());
+        store.alias(b"root", Some(c.cid())).unwrap();
+        store.gc().unwrap();
+        assert!(!store.has(a.cid()).unwrap());
+        assert!(!store.has(b.cid()).unwrap());
+        assert!(store.has(c.cid()).unwrap());
+    }
+}
@@ -1,5 +1,10 @@
 //! Path
+use crate::block::Block;
 use crate::cid::Cid;
+use crate::codec::Decode;
+use crate::error::{Result, TooManyLinks};
+use crate::ipld::Ipld;
+use crate::store::StoreParams;
 
 /// Represents a path in an ipld dag.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
@@ -57,6 +62,9 @@ impl ToString for Path {
     }
 }
 
+/// Maximum number of links [`DagPath::resolve`] follows in a row before applying a segment.
+pub const MAX_LINK_HOPS: usize = 64;
+
 /// Path in a dag.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct DagPath<'a>(&'a Cid, Path);
@@ -76,6 +84,38 @@ impl<'a> DagPath<'a> {
     pub fn path(&self) -> &Path {
         &self.1
     }
+
+    /// Resolves the path across blocks.
+    ///
+    /// Starting at the root block, every segment indexes into the current value. When a
+    /// segment has to index into a link, the linked block is loaded with `load` and the
+    /// segment is applied to its value instead. Returns the final value and the cid of the
+    /// block that contains it. A link at the end of the path is returned as is.
+    ///
+    /// Blocks that consist of a single link are followed, up to [`MAX_LINK_HOPS`] links in a
+    /// row, after which a [`TooManyLinks`] error is returned.
+    pub fn resolve<S, F>(&self, mut load: F) -> Result<(Cid, Ipld)>
+    where
+        S: StoreParams,
+        F: FnMut(&Cid) -> Result<Block<S>>,
+        Ipld: Decode<S::Codecs>,
+    {
+        let mut cid = *self.0;
+        let mut ipld = load(&cid)?.ipld()?;
+        for segment in self.1.iter() {
+            let mut hops = 0;
+            while let Ipld::Link(link) = ipld {
+                if hops == MAX_LINK_HOPS {
+                    return Err(TooManyLinks(link).into());
+                }
+                hops += 1;
+                cid = link;
+                ipld = load(&cid)?.ipld()?;
+            }
+            ipld = ipld.take(segment)?;
+        }
+        Ok((cid, ipld))
+    }
 }
 
 impl<'a> From<&'a Cid> for DagPath<'a> {
@@ -87,6 +127,12 @@ impl<'a> From<&'a Cid> for DagPath<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cbor::DagCborCodec;
+    use crate::error::{BlockNotFound, TooManyLinks, TypeError};
+    use crate::ipld;
+    use crate::mem::MemStore;
+    use crate::multihash::Code;
+    use crate::store::{DefaultParams, Store};
 
     #[test]
     fn test_parsing_one_segment() {
@@ -117,4 +163,80 @@ mod tests {
     fn test_to_string() {
         assert_eq!(Path::from(vec!["0", "foo", "2"]).to_string(), "0/foo/2");
     }
+
+    fn insert(store: &MemStore<DefaultParams>, ipld: &Ipld) -> Cid {
+        let block = Block::encode(DagCborCodec, Code::Blake3_256, ipld).unwrap();
+        let cid = *block.cid();
+        store.insert(block).unwrap();
+        cid
+    }
+
+    #[test]
+    fn test_resolve() {
+        let store = MemStore::<DefaultParams>::default();
+        let leaf = insert(&store, &ipld!({ "name": "leaf", "values": [1, 2, 3] }));
+        let mid = insert(&store, &ipld!([{ "leaf": leaf }]));
+        let root = insert(&store, &ipld!({ "mid": mid, "value": 42 }));
+        let load = |cid: &Cid| store.get(cid);
+
+        let path = DagPath::new(&root, "");
+        assert_eq!(path.resolve(load).unwrap().0, root);
+
+        let path = DagPath::new(&root, "value");
+        assert_eq!(path.resolve(load).unwrap(), (root, ipld!(42)));
+
+        let path = DagPath::new(&root, "mid/0/leaf");
+        assert_eq!(path.resolve(load).unwrap(), (mid, Ipld::Link(leaf)));
+
+        let path = DagPath::new(&root, "mid/0/leaf/values/2");
+        assert_eq!(path.resolve(load).unwrap(), (leaf, ipld!(3)));
+
+        let path = DagPath::new(&root, "mid/0/leaf/missing");
+        let err = path.resolve(load).unwrap_err();
+        assert!(err.is::<TypeError>());
+    }
+
+    #[test]
+    fn test_resolve_missing_block() {
+        let store = MemStore::<DefaultParams>::default();
+        let leaf =
+            Block::<DefaultParams>::encode(DagCborCodec, Code::Blake3_256, &ipld!("leaf")).unwrap();
+        let root = insert(&store, &ipld!({ "leaf": leaf.cid() }));
+        let path = DagPath::new(&root, "leaf/value");
+        let err = path.resolve(|cid| store.get(cid)).unwrap_err();
+        assert!(err.is::<BlockNotFound>());
+    }
+
+    #[test]
+    fn test_resolve_link_chain() {
+        let store = MemStore::<DefaultParams>::default();
+        let leaf = insert(&store, &ipld!({ "value": 42 }));
+        let hop = insert(&store, &ipld!(leaf));
+        let root = insert(&store, &ipld!({ "hop": hop }));
+        let path = DagPath::new(&root, "hop/value");
+        assert_eq!(
+            path.resolve(|cid| store.get(cid)).unwrap(),
+            (leaf, ipld!(42))
+        );
+    }
+
+    #[test]
+    fn test_resolve_link_cycle() {
+        let store = MemStore::<DefaultParams>::default();
+        let target = insert(&store, &ipld!("target"));
+        let root = insert(&store, &ipld!({ "next": target }));
+        // A loader that answers every request for `target` with a link back to `target`.
+        let cycle =
+            Block::<DefaultParams>::encode(DagCborCodec, Code::Blake3_256, &ipld!(target)).unwrap();
+        let load = |cid: &Cid| {
+            if cid == &target {
+                Ok(cycle.clone())
+            } else {
+                store.get(cid)
+            }
+        };
+        let path = DagPath::new(&root, "next/value");
+        let err = path.resolve(load).unwrap_err();
+        assert!(err.is::<TooManyLinks>());
+    }
 }
@@ -1,3 +1,3 @@
 //! Prelude
 pub use crate::codec::{Codec, Decode, Encode, References};
-pub use crate::store::StoreParams;
+pub use crate::store::{Store, StoreParams};
@@ -0,0 +1,913 @@
+//! IPLD selectors.
+//!
+//! Selectors describe a subset of a dag. They are parsed from their data model form, as defined
+//! by the [selector spec], which can be encoded with any codec:
+//!
+//! ```text
+//! {"R": {"l": {"depth": 3}, ":>": {"a": {">": {"@": {}}}}}}
+//! ```
+//!
+//! Conditions are left open by the spec. They are represented as a keyed union with the
+//! following members:
+//!
+//! | key           | value                              |
+//! |---------------|------------------------------------|
+//! | `hasField`    | field name                         |
+//! | `=`           | value                              |
+//! | `%`           | kind (`null`, `bool`, `int`, ...)  |
+//! | `/`           | `{}`, matches links                |
+//! | `greaterThan` | number                             |
+//! | `lessThan`    | number                             |
+//! | `and`         | list of conditions                 |
+//! | `or`          | list of conditions                 |
+//!
+//! [selector spec]: https://ipld.io/specs/selectors/
+use crate::block::Block;
+use crate::cid::Cid;
+use crate::codec::Decode;
+use crate::error::Result;
+use crate::ipld::Ipld;
+use crate::path::Path;
+use crate::store::StoreParams;
+use core::convert::TryFrom;
+use fnv::FnvHashSet;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// The selector is malformed.
+#[derive(Clone, Debug, Error)]
+#[error("Invalid selector: {0}.")]
+pub struct InvalidSelector(pub String);
+
+impl InvalidSelector {
+    fn new<T: Into<String>>(msg: T) -> Self {
+        Self(msg.into())
+    }
+}
+
+/// Selector.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Selector {
+    /// Matches the current node.
+    Matcher {
+        /// Only match if the condition holds.
+        only_if: Option<Condition>,
+        /// Label of the match.
+        label: Option<String>,
+    },
+    /// Applies the selector to all elements of a list or all values of a map.
+    ExploreAll {
+        /// Selector applied to the children.
+        next: Box<Selector>,
+    },
+    /// Applies selectors to fields of a map.
+    ExploreFields {
+        /// Selectors keyed by field name.
+        fields: BTreeMap<String, Selector>,
+    },
+    /// Applies the selector to an element of a list.
+    ExploreIndex {
+        /// Index into the list.
+        index: usize,
+        /// Selector applied to the element.
+        next: Box<Selector>,
+    },
+    /// Applies the selector to a range of elements of a list.
+    ExploreRange {
+        /// Start of the range, inclusive.
+        start: usize,
+        /// End of the range, exclusive.
+        end: usize,
+        /// Selector applied to the elements.
+        next: Box<Selector>,
+    },
+    /// Applies the sequence recursively at every [`Selector::ExploreRecursiveEdge`].
+    ExploreRecursive {
+        /// Selector that is applied recursively.
+        sequence: Box<Selector>,
+        /// Limits the recursion.
+        limit: RecursionLimit,
+        /// Stops the recursion at nodes matching the condition.
+        stop_at: Option<Condition>,
+    },
+    /// Applies all selectors.
+    ExploreUnion(Vec<Selector>),
+    /// Applies the selector if the condition holds.
+    ExploreConditional {
+        /// The condition.
+        condition: Condition,
+        /// Selector applied if the condition holds.
+        next: Box<Selector>,
+    },
+    /// Marks where the sequence of the enclosing [`Selector::ExploreRecursive`] is applied again.
+    ExploreRecursiveEdge,
+}
+
+/// Limits the depth of an [`Selector::ExploreRecursive`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecursionLimit {
+    /// Unlimited recursion.
+    None,
+    /// Maximum number of recursive edges that are followed.
+    Depth(u64),
+}
+
+/// Condition.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    /// The node is a map with the field.
+    HasField(String),
+    /// The node is equal to the value.
+    HasValue(Ipld),
+    /// The node is of the kind.
+    HasKind(Kind),
+    /// The node is a link.
+    IsLink,
+    /// The node is a number greater than the value.
+    GreaterThan(Ipld),
+    /// The node is a number less than the value.
+    LessThan(Ipld),
+    /// All conditions hold.
+    And(Vec<Condition>),
+    /// Any condition holds.
+    Or(Vec<Condition>),
+}
+
+/// Data model kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// Null kind.
+    Null,
+    /// Boolean kind.
+    Bool,
+    /// Integer kind.
+    Integer,
+    /// Float kind.
+    Float,
+    /// String kind.
+    String,
+    /// Bytes kind.
+    Bytes,
+    /// List kind.
+    List,
+    /// Map kind.
+    Map,
+    /// Link kind.
+    Link,
+}
+
+impl Kind {
+    /// Returns the kind of an ipld value.
+    pub fn of(ipld: &Ipld) -> Self {
+        match ipld {
+            Ipld::Null => Self::Null,
+            Ipld::Bool(_) => Self::Bool,
+            Ipld::Integer(_) => Self::Integer,
+            Ipld::Float(_) => Self::Float,
+            Ipld::String(_) => Self::String,
+            Ipld::Bytes(_) => Self::Bytes,
+            Ipld::List(_) => Self::List,
+            Ipld::Map(_) => Self::Map,
+            Ipld::Link(_) => Self::Link,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Bool => "bool",
+            Self::Integer => "int",
+            Self::Float => "float",
+            Self::String => "string",
+            Self::Bytes => "bytes",
+            Self::List => "list",
+            Self::Map => "map",
+            Self::Link => "link",
+        }
+    }
+
+    fn from_name(kind: &str) -> Option<Self> {
+        Some(match kind {
+            "null" => Self::Null,
+            "bool" => Self::Bool,
+            "int" => Self::Integer,
+            "float" => Self::Float,
+            "string" => Self::String,
+            "bytes" => Self::Bytes,
+            "list" => Self::List,
+            "map" => Self::Map,
+            "link" => Self::Link,
+            _ => return None,
+        })
+    }
+}
+
+fn as_number(ipld: &Ipld) -> Option<f64> {
+    match ipld {
+        Ipld::Integer(i) => Some(*i as f64),
+        Ipld::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+fn cmp_numbers(a: &Ipld, b: &Ipld) -> Option<core::cmp::Ordering> {
+    match (a, b) {
+        (Ipld::Integer(a), Ipld::Integer(b)) => Some(a.cmp(b)),
+        _ => as_number(a)?.partial_cmp(&as_number(b)?),
+    }
+}
+
+impl Condition {
+    /// Returns true if the condition holds for the node.
+    pub fn matches(&self, ipld: &Ipld) -> bool {
+        match self {
+            Self::HasField(field) => match ipld {
+                Ipld::Map(map) => map.contains_key(field),
+                _ => false,
+            },
+            Self::HasValue(value) => ipld == value,
+            Self::HasKind(kind) => Kind::of(ipld) == *kind,
+            Self::IsLink => matches!(ipld, Ipld::Link(_)),
+            Self::GreaterThan(value) => {
+                cmp_numbers(ipld, value) == Some(core::cmp::Ordering::Greater)
+            }
+            Self::LessThan(value) => cmp_numbers(ipld, value) == Some(core::cmp::Ordering::Less),
+            Self::And(conditions) => conditions.iter().all(|c| c.matches(ipld)),
+            Self::Or(conditions) => conditions.iter().any(|c| c.matches(ipld)),
+        }
+    }
+}
+
+/// Returns the single entry of a keyed union.
+fn keyed(ipld: &Ipld) -> Result<(&str, &Ipld)> {
+    match ipld {
+        Ipld::Map(map) if map.len() == 1 => {
+            let (key, value) = map.iter().next().unwrap();
+            Ok((key.as_str(), value))
+        }
+        _ => Err(InvalidSelector::new("expected a map with a single entry").into()),
+    }
+}
+
+fn field<'a>(ipld: &'a Ipld, key: &str) -> Result<&'a Ipld> {
+    optional_field(ipld, key)?
+        .ok_or_else(|| InvalidSelector::new(format!("missing field `{}`", key)).into())
+}
+
+fn optional_field<'a>(ipld: &'a Ipld, key: &str) -> Result<Option<&'a Ipld>> {
+    match ipld {
+        Ipld::Map(map) => Ok(map.get(key)),
+        _ => Err(InvalidSelector::new("expected a map").into()),
+    }
+}
+
+fn index(ipld: &Ipld) -> Result<usize> {
+    match ipld {
+        Ipld::Integer(i) => {
+            usize::try_from(*i).map_err(|_| InvalidSelector::new("index out of range").into())
+        }
+        _ => Err(InvalidSelector::new("expected an integer").into()),
+    }
+}
+
+fn number(ipld: &Ipld) -> Result<Ipld> {
+    match ipld {
+        Ipld::Integer(_) | Ipld::Float(_) => Ok(ipld.clone()),
+        _ => Err(InvalidSelector::new("expected a number").into()),
+    }
+}
+
+fn string(ipld: &Ipld) -> Result<String> {
+    match ipld {
+        Ipld::String(s) => Ok(s.clone()),
+        _ => Err(InvalidSelector::new("expected a string").into()),
+    }
+}
+
+fn empty_map(ipld: &Ipld) -> Result<()> {
+    match ipld {
+        Ipld::Map(map) if map.is_empty() => Ok(()),
+        _ => Err(InvalidSelector::new("expected an empty map").into()),
+    }
+}
+
+fn list(ipld: &Ipld) -> Result<&[Ipld]> {
+    match ipld {
+        Ipld::List(list) => Ok(list),
+        _ => Err(InvalidSelector::new("expected a list").into()),
+    }
+}
+
+impl TryFrom<&Ipld> for Condition {
+    type Error = crate::error::Error;
+
+    fn try_from(ipld: &Ipld) -> Result<Self> {
+        let (key, value) = keyed(ipld)?;
+        Ok(match key {
+            "hasField" => Self::HasField(string(value)?),
+            "=" => Self::HasValue(value.clone()),
+            "%" => Self::HasKind(
+                Kind::from_name(&string(value)?)
+                    .ok_or_else(|| InvalidSelector::new("unknown kind"))?,
+            ),
+            "/" => {
+                empty_map(value)?;
+                Self::IsLink
+            }
+            "greaterThan" => Self::GreaterThan(number(value)?),
+            "lessThan" => Self::LessThan(number(value)?),
+            "and" => Self::And(
+                list(value)?
+                    .iter()
+                    .map(Self::try_from)
+                    .collect::<Result<_>>()?,
+            ),
+            "or" => Self::Or(
+                list(value)?
+                    .iter()
+                    .map(Self::try_from)
+                    .collect::<Result<_>>()?,
+            ),
+            key => return Err(InvalidSelector::new(format!("unknown condition `{}`", key)).into()),
+        })
+    }
+}
+
+impl From<&Condition> for Ipld {
+    fn from(condition: &Condition) -> Self {
+        let (key, value) = match condition {
+            Condition::HasField(field) => ("hasField", Ipld::String(field.clone())),
+            Condition::HasValue(value) => ("=", value.clone()),
+            Condition::HasKind(kind) => ("%", Ipld::String(kind.as_str().into())),
+            Condition::IsLink => ("/", Ipld::Map(Default::default())),
+            Condition::GreaterThan(value) => ("greaterThan", value.clone()),
+            Condition::LessThan(value) => ("lessThan", value.clone()),
+            Condition::And(conditions) => (
+                "and",
+                Ipld::List(conditions.iter().map(Ipld::from).collect()),
+            ),
+            Condition::Or(conditions) => (
+                "or",
+                Ipld::List(conditions.iter().map(Ipld::from).collect()),
+            ),
+        };
+        map(vec![(key, value)])
+    }
+}
+
+fn map(entries: Vec<(&str, Ipld)>) -> Ipld {
+    Ipld::Map(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+    )
+}
+
+impl Selector {
+    /// Parses a selector without validating it.
+    fn parse(ipld: &Ipld) -> Result<Self> {
+        let (key, value) = keyed(ipld)?;
+        let next = |key| -> Result<Box<Self>> { Ok(Box::new(Self::parse(field(value, key)?)?)) };
+        Ok(match key {
+            "." => Self::Matcher {
+                only_if: optional_field(value, "&")?
+                    .map(Condition::try_from)
+                    .transpose()?,
+                label: optional_field(value, "@")?.map(string).transpose()?,
+            },
+            "a" => Self::ExploreAll { next: next(">")? },
+            "f" => match field(value, "f>")? {
+                Ipld::Map(fields) => Self::ExploreFields {
+                    fields: fields
+                        .iter()
+                        .map(|(key, value)| Ok((key.clone(), Self::parse(value)?)))
+                        .collect::<Result<_>>()?,
+                },
+                _ => return Err(InvalidSelector::new("expected a map").into()),
+            },
+            "i" => Self::ExploreIndex {
+                index: index(field(value, "i")?)?,
+                next: next(">")?,
+            },
+            "r" => Self::ExploreRange {
+                start: index(field(value, "^")?)?,
+                end: index(field(value, "$")?)?,
+                next: next(">")?,
+            },
+            "R" => Self::ExploreRecursive {
+                sequence: next(":>")?,
+                limit: match keyed(field(value, "l")?)? {
+                    ("none", value) => {
+                        empty_map(value)?;
+                        RecursionLimit::None
+                    }
+                    ("depth", value) => RecursionLimit::Depth(index(value)? as u64),
+                    _ => return Err(InvalidSelector::new("unknown recursion limit").into()),
+                },
+                stop_at: optional_field(value, "!")?
+                    .map(Condition::try_from)
+                    .transpose()?,
+            },
+            "|" => Self::ExploreUnion(
+                list(value)?
+                    .iter()
+                    .map(Self::parse)
+                    .collect::<Result<_>>()?,
+            ),
+            "&" => Self::ExploreConditional {
+                condition: Condition::try_from(field(value, "&")?)?,
+                next: next(">")?,
+            },
+            "@" => {
+                empty_map(value)?;
+                Self::ExploreRecursiveEdge
+            }
+            key => return Err(InvalidSelector::new(format!("unknown selector `{}`", key)).into()),
+        })
+    }
+
+    /// Validates the selector.
+    ///
+    /// Recursive edges need to be inside a recursive selector, ranges need to be well formed
+    /// and unions must not be empty.
+    pub fn validate(&self) -> Result<()> {
+        self.validate_inner(false)
+    }
+
+    fn validate_inner(&self, recursive: bool) -> Result<()> {
+        match self {
+            Self::Matcher { .. } => Ok(()),
+            Self::ExploreAll { next } | Self::ExploreIndex { next, .. } => {
+                next.validate_inner(recursive)
+            }
+            Self::ExploreConditional { next, .. } => next.validate_inner(recursive),
+            Self::ExploreFields { fields } => fields
+                .values()
+                .try_for_each(|next| next.validate_inner(recursive)),
+            Self::ExploreRange { start, end, next } => {
+                if start > end {
+                    return Err(InvalidSelector::new("range start is after its end").into());
+                }
+                next.validate_inner(recursive)
+            }
+            Self::ExploreRecursive { sequence, .. } => sequence.validate_inner(true),
+            Self::ExploreUnion(selectors) => {
+                if selectors.is_empty() {
+                    return Err(InvalidSelector::new("empty union").into());
+                }
+                selectors
+                    .iter()
+                    .try_for_each(|next| next.validate_inner(recursive))
+            }
+            Self::ExploreRecursiveEdge => {
+                if !recursive {
+                    return Err(InvalidSelector::new("recursive edge outside of recursion").into());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Selects the matching nodes of a single value. Links are not followed.
+    pub fn select(&self, ipld: &Ipld) -> Result<Vec<Match>> {
+        let mut walker = Walker::<fn(&Cid) -> Result<Ipld>> {
+            load: None,
+            matches: vec![],
+            blocks: vec![],
+            seen: Default::default(),
+        };
+        walker.walk(self, ipld.clone(), &mut vec![], None)?;
+        Ok(walker.matches)
+    }
+
+    /// Selects the matching nodes of a dag. Links are followed transparently by loading the
+    /// linked blocks with `load`.
+    pub fn select_dag<S, F>(&self, root: &Cid, mut load: F) -> Result<Selection>
+    where
+        S: StoreParams,
+        F: FnMut(&Cid) -> Result<Block<S>>,
+        Ipld: Decode<S::Codecs>,
+    {
+        let mut walker = Walker {
+            load: Some(|cid: &Cid| load(cid)?.ipld()),
+            matches: vec![],
+            blocks: vec![],
+            seen: Default::default(),
+        };
+        walker.walk(self, Ipld::Link(*root), &mut vec![], None)?;
+        Ok(Selection {
+            matches: walker.matches,
+            blocks: walker.blocks,
+        })
+    }
+}
+
+impl TryFrom<&Ipld> for Selector {
+    type Error = crate::error::Error;
+
+    fn try_from(ipld: &Ipld) -> Result<Self> {
+        let selector = Self::parse(ipld)?;
+        selector.validate()?;
+        Ok(selector)
+    }
+}
+
+impl TryFrom<Ipld> for Selector {
+    type Error = crate::error::Error;
+
+    fn try_from(ipld: Ipld) -> Result<Self> {
+        Self::try_from(&ipld)
+    }
+}
+
+impl From<&Selector> for Ipld {
+    fn from(selector: &Selector) -> Self {
+        let next = |next: &Selector| Ipld::from(next);
+        match selector {
+            Selector::Matcher { only_if, label } => {
+                let mut entries = vec![];
+                if let Some(only_if) = only_if {
+                    entries.push(("&", Ipld::from(only_if)));
+                }
+                if let Some(label) = label {
+                    entries.push(("@", Ipld::String(label.clone())));
+                }
+                map(vec![(".", map(entries))])
+            }
+            Selector::ExploreAll { next: n } => map(vec![("a", map(vec![(">", next(n))]))]),
+            Selector::ExploreFields { fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|(key, value)| (key.clone(), next(value)))
+                    .collect();
+                map(vec![("f", map(vec![("f>", Ipld::Map(fields))]))])
+            }
+            Selector::ExploreIndex { index, next: n } => map(vec![(
+                "i",
+                map(vec![("i", Ipld::Integer(*index as _)), (">", next(n))]),
+            )]),
+            Selector::ExploreRange {
+                start,
+                end,
+                next: n,
+            } => map(vec![(
+                "r",
+                map(vec![
+                    ("^", Ipld::Integer(*start as _)),
+                    ("$", Ipld::Integer(*end as _)),
+                    (">", next(n)),
+                ]),
+            )]),
+            Selector::ExploreRecursive {
+                sequence,
+                limit,
+                stop_at,
+            } => {
+                let limit = match limit {
+                    RecursionLimit::None => map(vec![("none", map(vec![]))]),
+                    RecursionLimit::Depth(depth) => {
+                        map(vec![("depth", Ipld::Integer(*depth as _))])
+                    }
+                };
+                let mut entries = vec![(":>", next(sequence)), ("l", limit)];
+                if let Some(stop_at) = stop_at {
+                    entries.push(("!", Ipld::from(stop_at)));
+                }
+                map(vec![("R", map(entries))])
+            }
+            Selector::ExploreUnion(selectors) => map(vec![(
+                "|",
+                Ipld::List(selectors.iter().map(next).collect()),
+            )]),
+            Selector::ExploreConditional { condition, next: n } => map(vec![(
+                "&",
+                map(vec![("&", Ipld::from(condition)), (">", next(n))]),
+            )]),
+            Selector::ExploreRecursiveEdge => map(vec![("@", map(vec![]))]),
+        }
+    }
+}
+
+impl From<Selector> for Ipld {
+    fn from(selector: Selector) -> Self {
+        Self::from(&selector)
+    }
+}
+
+/// A node matched by a [`Selector::Matcher`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    /// Path to the node from the root.
+    pub path: Path,
+    /// The node.
+    pub value: Ipld,
+    /// Label of the matcher.
+    pub label: Option<String>,
+}
+
+/// Result of selecting a dag.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Selection {
+    /// The matched nodes.
+    pub matches: Vec<Match>,
+    /// The blocks that were traversed, in the order they were first loaded.
+    pub blocks: Vec<Cid>,
+}
+
+/// Innermost recursive selector.
+struct Recursion<'a> {
+    sequence: &'a Selector,
+    remaining: Option<u64>,
+    stop_at: Option<&'a Condition>,
+}
+
+struct Walker<F> {
+    load: Option<F>,
+    matches: Vec<Match>,
+    blocks: Vec<Cid>,
+    seen: FnvHashSet<Cid>,
+}
+
+impl<F: FnMut(&Cid) -> Result<Ipld>> Walker<F> {
+    fn walk(
+        &mut self,
+        selector: &Selector,
+        mut ipld: Ipld,
+        path: &mut Vec<String>,
+        recursion: Option<&Recursion>,
+    ) -> Result<()> {
+        if let (Ipld::Link(cid), Some(load)) = (&ipld, self.load.as_mut()) {
+            let cid = *cid;
+            ipld = load(&cid)?;
+            if self.seen.insert(cid) {
+                self.blocks.push(cid);
+            }
+        }
+        match selector {
+            Selector::Matcher { only_if, label } => {
+                if only_if.as_ref().map(|c| c.matches(&ipld)).unwrap_or(true) {
+                    self.matches.push(Match {
+                        path: Path::from(path.clone()),
+                        value: ipld,
+                        label: label.clone(),
+                    });
+                }
+            }
+            Selector::ExploreAll { next } => match ipld {
+                Ipld::List(list) => {
+                    for (i, ipld) in list.into_iter().enumerate() {
+                        self.walk_child(next, ipld, path, i.to_string(), recursion)?;
+                    }
+                }
+                Ipld::Map(map) => {
+                    for (key, ipld) in map {
+                        self.walk_child(next, ipld, path, key, recursion)?;
+                    }
+                }
+                _ => {}
+            },
+            Selector::ExploreFields { fields } => {
+                if let Ipld::Map(mut map) = ipld {
+                    for (key, next) in fields {
+                        if let Some(ipld) = map.remove(key) {
+                            self.walk_child(next, ipld, path, key.clone(), recursion)?;
+                        }
+                    }
+                }
+            }
+            Selector::ExploreIndex { index, next } => {
+                if let Ipld::List(mut list) = ipld {
+                    if *index < list.len() {
+                        let ipld = list.swap_remove(*index);
+                        self.walk_child(next, ipld, path, index.to_string(), recursion)?;
+                    }
+                }
+            }
+            Selector::ExploreRange { start, end, next } => {
+                if let Ipld::List(list) = ipld {
+                    let range = list
+                        .into_iter()
+                        .enumerate()
+                        .skip(*start)
+                        .take(end.saturating_sub(*start));
+                    for (i, ipld) in range {
+                        self.walk_child(next, ipld, path, i.to_string(), recursion)?;
+                    }
+                }
+            }
+            Selector::ExploreRecursive {
+                sequence,
+                limit,
+                stop_at,
+            } => {
+                let recursion = Recursion {
+                    sequence,
+                    remaining: match limit {
+                        RecursionLimit::None => None,
+                        RecursionLimit::Depth(depth) => Some(*depth),
+                    },
+                    stop_at: stop_at.as_ref(),
+                };
+                self.walk(sequence, ipld, path, Some(&recursion))?;
+            }
+            Selector::ExploreUnion(selectors) => {
+                for selector in selectors {
+                    self.walk(selector, ipld.clone(), path, recursion)?;
+                }
+            }
+            Selector::ExploreConditional { condition, next } => {
+                if condition.matches(&ipld) {
+                    self.walk(next, ipld, path, recursion)?;
+                }
+            }
+            Selector::ExploreRecursiveEdge => {
+                let recursion = recursion
+                    .ok_or_else(|| InvalidSelector::new("recursive edge outside of recursion"))?;
+                if recursion.remaining == Some(0) {
+                    return Ok(());
+                }
+                if let Some(stop_at) = recursion.stop_at {
+                    if stop_at.matches(&ipld) {
+                        return Ok(());
+                    }
+                }
+                let next = Recursion {
+                    sequence: recursion.sequence,
+                    remaining: recursion.remaining.map(|remaining| remaining - 1),
+                    stop_at: recursion.stop_at,
+                };
+                self.walk(recursion.sequence, ipld, path, Some(&next))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn walk_child(
+        &mut self,
+        selector: &Selector,
+        ipld: Ipld,
+        path: &mut Vec<String>,
+        segment: String,
+        recursion: Option<&Recursion>,
+    ) -> Result<()> {
+        path.push(segment);
+        let res = self.walk(selector, ipld, path, recursion);
+        path.pop();
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbor::DagCborCodec;
+    use crate::error::BlockNotFound;
+    use crate::ipld;
+    use crate::json::DagJsonCodec;
+    use crate::mem::MemStore;
+    use crate::multihash::Code;
+    use crate::prelude::*;
+    use crate::store::DefaultParams;
+
+    fn selector(json: &str) -> Selector {
+        let ipld: Ipld = DagJsonCodec.decode(json.as_bytes()).unwrap();
+        Selector::try_from(&ipld).unwrap()
+    }
+
+    fn paths(matches: &[Match]) -> Vec<String> {
+        matches.iter().map(|m| m.path.to_string()).collect()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let json = [
+            r#"{".": {}}"#,
+            r#"{".": {"&": {"%": "int"}, "@": "label"}}"#,
+            r#"{"a": {">": {".": {}}}}"#,
+            r#"{"f": {"f>": {"a": {".": {}}, "b": {"i": {"i": 1, ">": {".": {}}}}}}}"#,
+            r#"{"r": {"^": 1, "$": 3, ">": {".": {}}}}"#,
+            r#"{"R": {"l": {"depth": 3}, ":>": {"a": {">": {"@": {}}}}}}"#,
+            r#"{"R": {"l": {"none": {}}, ":>": {"|": [{".": {}}, {"a": {">": {"@": {}}}}]}, "!": {"hasField": "stop"}}}"#,
+            r#"{"&": {"&": {"and": [{"greaterThan": 1}, {"lessThan": 2.5}]}, ">": {".": {}}}}"#,
+            r#"{"&": {"&": {"or": [{"=": "x"}, {"/": {}}]}, ">": {".": {}}}}"#,
+        ];
+        for json in json {
+            let ipld: Ipld = DagJsonCodec.decode(json.as_bytes()).unwrap();
+            let selector = Selector::try_from(&ipld).unwrap();
+            assert_eq!(Ipld::from(&selector), ipld);
+            let bytes = DagCborCodec.encode(&Ipld::from(&selector)).unwrap();
+            let ipld2: Ipld = DagCborCodec.decode(&bytes).unwrap();
+            assert_eq!(Selector::try_from(ipld2).unwrap(), selector);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        let json = [
+            r#"{}"#,
+            r#"{"x": {}}"#,
+            r#"{"a": {}}"#,
+            r#"{"@": {}}"#,
+            r#"{"a": {">": {"@": {}}}}"#,
+            r#"{"r": {"^": 3, "$": 1, ">": {".": {}}}}"#,
+            r#"{"i": {"i": -1, ">": {".": {}}}}"#,
+            r#"{"|": []}"#,
+            r#"{"R": {"l": {"width": 1}, ":>": {"@": {}}}}"#,
+            r#"{".": {"&": {"%": "float32"}}}"#,
+        ];
+        for json in json {
+            let ipld: Ipld = DagJsonCodec.decode(json.as_bytes()).unwrap();
+            let err = Selector::try_from(&ipld).unwrap_err();
+            assert!(err.is::<InvalidSelector>(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let ipld = ipld!({
+            "a": [0, 1, 2, 3],
+            "b": { "c": true, "d": "hello" },
+        });
+        let matches = selector(r#"{".": {}}"#).select(&ipld).unwrap();
+        assert_eq!(
+            matches,
+            vec![Match {
+                path: Path::default(),
+                value: ipld.clone(),
+                label: None,
+            }]
+        );
+
+        let s = selector(r#"{"f": {"f>": {"a": {"r": {"^": 1, "$": 3, ">": {".": {}}}}}}}"#);
+        assert_eq!(paths(&s.select(&ipld).unwrap()), vec!["a/1", "a/2"]);
+
+        let s =
+            selector(r#"{"f": {"f>": {"b": {"a": {">": {".": {"@": "x"}}}}, "e": {".": {}}}}}"#);
+        let matches = s.select(&ipld).unwrap();
+        assert_eq!(paths(&matches), vec!["b/c", "b/d"]);
+        assert_eq!(matches[1].value, ipld!("hello"));
+        assert_eq!(matches[1].label.as_deref(), Some("x"));
+
+        let s = selector(r#"{"f": {"f>": {"a": {"i": {"i": 3, ">": {".": {}}}}}}}"#);
+        assert_eq!(s.select(&ipld).unwrap()[0].value, ipld!(3));
+
+        let s = selector(r#"{"f": {"f>": {"a": {"a": {">": {".": {"&": {"greaterThan": 1}}}}}}}}"#);
+        assert_eq!(paths(&s.select(&ipld).unwrap()), vec!["a/2", "a/3"]);
+
+        let s = Selector::ExploreRange {
+            start: 3,
+            end: 1,
+            next: Box::new(Selector::Matcher {
+                only_if: None,
+                label: None,
+            }),
+        };
+        assert!(s.select(&ipld!([0, 1, 2, 3])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_select_recursive() {
+        let ipld = ipld!({ "n": { "n": { "n": { "n": null, "stop": true } } } });
+        let s = selector(
+            r#"{"R": {"l": {"depth": 2}, ":>": {"|": [{".": {}}, {"f": {"f>": {"n": {"@": {}}}}}]}}}"#,
+        );
+        assert_eq!(paths(&s.select(&ipld).unwrap()), vec!["", "n", "n/n"]);
+
+        let s = selector(
+            r#"{"R": {"l": {"none": {}}, ":>": {"|": [{".": {}}, {"f": {"f>": {"n": {"@": {}}}}}]}, "!": {"hasField": "stop"}}}"#,
+        );
+        assert_eq!(paths(&s.select(&ipld).unwrap()), vec!["", "n", "n/n"]);
+    }
+
+    #[test]
+    fn test_select_dag() {
+        let store = MemStore::<DefaultParams>::default();
+        let insert = |ipld: Ipld| {
+            let block = Block::encode(DagCborCodec, Code::Blake3_256, &ipld).unwrap();
+            let cid = *block.cid();
+            store.insert(block).unwrap();
+            cid
+        };
+        let leaf1 = insert(ipld!({ "value": 1 }));
+        let leaf2 = insert(ipld!({ "value": 2 }));
+        let root = insert(ipld!({ "children": [leaf1, leaf2, leaf1] }));
+
+        let s = selector(
+            r#"{"R": {"l": {"none": {}}, ":>": {"a": {">": {"|": [{".": {"&": {"%": "int"}}}, {"@": {}}]}}}}}"#,
+        );
+        let selection = s.select_dag(&root, |cid| store.get(cid)).unwrap();
+        assert_eq!(
+            paths(&selection.matches),
+            vec!["children/0/value", "children/1/value", "children/2/value"]
+        );
+        assert_eq!(selection.blocks, vec![root, leaf1, leaf2]);
+
+        let missing =
+            Block::<DefaultParams>::encode(DagCborCodec, Code::Blake3_256, &ipld!(0)).unwrap();
+        let root = insert(ipld!([missing.cid()]));
+        let s = selector(r#"{"a": {">": {".": {}}}}"#);
+        let err = s.select_dag(&root, |cid| store.get(cid)).unwrap_err();
+        assert!(err.is::<BlockNotFound>());
+    }
+}
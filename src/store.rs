@@ -16,8 +16,18 @@
 //! However it is intended to run only when the configured size is exceeded at when it will start
 //! incrementally deleting unaliased blocks until the size target is no longer exceeded. It is
 //! implementation defined in which order unaliased blocks get removed.
+//!
+//! ## Async
+//! With the `async` feature enabled, [`AsyncStore`] provides the store operations to async
+//! runtimes. A synchronous [`Store`] can be used as an [`AsyncStore`] by wrapping it in
+//! [`Blocking`], which runs the operations on a thread pool.
+use crate::block::Block;
+use crate::cid::Cid;
 use crate::codec::Codec;
+use crate::error::Result;
 use crate::multihash::MultihashDigest;
+#[cfg(feature = "async")]
+use async_trait::async_trait;
 
 /// The store parameters.
 pub trait StoreParams: std::fmt::Debug + Clone + Send + Sync + Unpin + 'static {
@@ -38,3 +48,153 @@ impl StoreParams for DefaultParams {
     type Codecs = crate::IpldCodec;
     type Hashes = crate::multihash::Code;
 }
+
+/// Block store.
+///
+/// Operations that look up a block which is not in the store return a
+/// [`BlockNotFound`](crate::error::BlockNotFound) error.
+pub trait Store: Send + Sync {
+    /// The store parameters.
+    type Params: StoreParams;
+    /// A temporary pin. Blocks pinned by it are unpinned when it is dropped.
+    type TempPin: Send + Sync;
+
+    /// Returns a block.
+    fn get(&self, cid: &Cid) -> Result<Block<Self::Params>>;
+
+    /// Returns true if the block is in the store.
+    fn has(&self, cid: &Cid) -> Result<bool>;
+
+    /// Inserts a block into the store.
+    fn insert(&self, block: Block<Self::Params>) -> Result<()>;
+
+    /// Removes a block from the store. Blocks that are protected by an alias or a temporary pin
+    /// are not removed.
+    fn remove(&self, cid: &Cid) -> Result<()>;
+
+    /// Creates an alias for a root. Passing `None` removes the alias.
+    fn alias<T: AsRef<[u8]>>(&self, alias: T, cid: Option<&Cid>) -> Result<()>;
+
+    /// Resolves an alias to its root.
+    fn resolve<T: AsRef<[u8]>>(&self, alias: T) -> Result<Option<Cid>>;
+
+    /// Creates a new temporary pin.
+    fn create_temp_pin(&self) -> Result<Self::TempPin>;
+
+    /// Adds a root to a temporary pin.
+    fn temp_pin(&self, tmp: &mut Self::TempPin, cid: &Cid) -> Result<()>;
+
+    /// Removes all blocks that are neither reachable from an alias nor temporarily pinned.
+    fn gc(&self) -> Result<()>;
+
+    /// Flushes pending writes to the underlying storage.
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Async block store.
+#[cfg(feature = "async")]
+#[async_trait]
+pub trait AsyncStore: Send + Sync {
+    /// The store parameters.
+    type Params: StoreParams;
+
+    /// Returns a block.
+    async fn get(&self, cid: &Cid) -> Result<Block<Self::Params>>;
+
+    /// Inserts a block into the store.
+    async fn insert(&self, block: Block<Self::Params>) -> Result<()>;
+
+    /// Creates an alias for a root. Passing `None` removes the alias.
+    async fn alias<T: AsRef<[u8]> + Send + Sync>(&self, alias: T, cid: Option<&Cid>) -> Result<()>;
+
+    /// Resolves an alias to its root.
+    async fn resolve<T: AsRef<[u8]> + Send + Sync>(&self, alias: T) -> Result<Option<Cid>>;
+
+    /// Flushes pending writes to the underlying storage.
+    async fn sync(&self) -> Result<()>;
+}
+
+/// Adapter that turns a [`Store`] into an [`AsyncStore`].
+///
+/// The operations of the inner store are run on a thread pool, so that they don't block the
+/// executor.
+#[cfg(feature = "async")]
+#[derive(Clone, Debug, Default)]
+pub struct Blocking<T>(T);
+
+#[cfg(feature = "async")]
+impl<T> Blocking<T> {
+    /// Wraps a store.
+    pub fn new(store: T) -> Self {
+        Self(store)
+    }
+
+    /// Returns a reference to the inner store.
+    pub fn get_ref(&self) -> &T {
+        &self.0
+    }
+
+    /// Returns the inner store.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl<T: Store + Clone + 'static> AsyncStore for Blocking<T> {
+    type Params = T::Params;
+
+    async fn get(&self, cid: &Cid) -> Result<Block<Self::Params>> {
+        let store = self.0.clone();
+        let cid = *cid;
+        blocking::unblock(move || store.get(&cid)).await
+    }
+
+    async fn insert(&self, block: Block<Self::Params>) -> Result<()> {
+        let store = self.0.clone();
+        blocking::unblock(move || store.insert(block)).await
+    }
+
+    async fn alias<A: AsRef<[u8]> + Send + Sync>(&self, alias: A, cid: Option<&Cid>) -> Result<()> {
+        let store = self.0.clone();
+        let alias = alias.as_ref().to_vec();
+        let cid = cid.copied();
+        blocking::unblock(move || store.alias(alias, cid.as_ref())).await
+    }
+
+    async fn resolve<A: AsRef<[u8]> + Send + Sync>(&self, alias: A) -> Result<Option<Cid>> {
+        let store = self.0.clone();
+        let alias = alias.as_ref().to_vec();
+        blocking::unblock(move || store.resolve(alias)).await
+    }
+
+    async fn sync(&self) -> Result<()> {
+        let store = self.0.clone();
+        blocking::unblock(move || store.sync()).await
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use super::*;
+    use crate::cbor::DagCborCodec;
+    use crate::ipld;
+    use crate::mem::MemStore;
+    use crate::multihash::Code;
+
+    #[async_std::test]
+    async fn test_blocking() {
+        let store = Blocking::new(MemStore::<DefaultParams>::default());
+        let block =
+            Block::<DefaultParams>::encode(DagCborCodec, Code::Blake3_256, &ipld!("a")).unwrap();
+        store.insert(block.clone()).await.unwrap();
+        assert_eq!(store.get(block.cid()).await.unwrap(), block);
+        store.alias(b"root", Some(block.cid())).await.unwrap();
+        assert_eq!(store.resolve(b"root").await.unwrap(), Some(*block.cid()));
+        store.sync().await.unwrap();
+        assert!(store.get_ref().has(block.cid()).unwrap());
+    }
+}
@@ -0,0 +1,277 @@
+//! Dag traversal.
+//!
+//! Walks a dag from a root in depth-first or breadth-first order. Children are discovered with
+//! [`Block::references`] and fetched with a user supplied loader. Every block is visited at
+//! most once. With a maximum depth, a block that is reached again over a shorter path has its
+//! children expanded again, so that everything within the limit is visited.
+use crate::block::Block;
+use crate::cid::Cid;
+use crate::codec::References;
+use crate::error::{BlockNotFound, Result};
+use crate::ipld::Ipld;
+use crate::store::StoreParams;
+use fnv::FnvHashMap;
+use std::collections::VecDeque;
+
+/// Traversal order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Order {
+    /// Depth-first pre-order.
+    #[default]
+    DepthFirst,
+    /// Breadth-first order.
+    BreadthFirst,
+}
+
+/// Returned by the visitor to control the traversal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    /// Continue with the children of the block.
+    Continue,
+    /// Don't visit the children of the block.
+    Skip,
+    /// Stop the traversal.
+    Stop,
+}
+
+/// Dag traversal.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Traversal {
+    order: Order,
+    max_depth: Option<usize>,
+    ignore_missing: bool,
+}
+
+impl Traversal {
+    /// Creates a new traversal.
+    pub fn new(order: Order) -> Self {
+        Self {
+            order,
+            ..Default::default()
+        }
+    }
+
+    /// Doesn't visit blocks that are more than `depth` links away from the root.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Skips blocks that the loader can't find instead of returning a [`BlockNotFound`] error.
+    pub fn ignore_missing(mut self, ignore: bool) -> Self {
+        self.ignore_missing = ignore;
+        self
+    }
+
+    /// Walks the dag starting at `root`.
+    ///
+    /// The visitor is called with every block and its depth, the root being at depth zero.
+    pub fn walk<S, L, V>(&self, root: &Cid, mut load: L, mut visit: V) -> Result<()>
+    where
+        S: StoreParams,
+        L: FnMut(&Cid) -> Result<Block<S>>,
+        V: FnMut(&Block<S>, usize) -> Result<Control>,
+        Ipld: References<S::Codecs>,
+    {
+        // Smallest depth at which each block was expanded. Skipped blocks are recorded at depth
+        // zero, so that they are never expanded.
+        let mut depths = FnvHashMap::default();
+        let expand =
+            |depths: &FnvHashMap<Cid, usize>, cid: &Cid, depth: usize| match depths.get(cid) {
+                None => true,
+                Some(seen) => self.max_depth.is_some() && depth < *seen,
+            };
+        let mut queue = VecDeque::new();
+        queue.push_back((*root, 0));
+        let mut refs = vec![];
+        while let Some((cid, depth)) = match self.order {
+            Order::DepthFirst => queue.pop_back(),
+            Order::BreadthFirst => queue.pop_front(),
+        } {
+            if !expand(&depths, &cid, depth) {
+                continue;
+            }
+            let first = depths.insert(cid, depth).is_none();
+            let block = match load(&cid) {
+                Ok(block) => block,
+                Err(err) if self.ignore_missing && err.is::<BlockNotFound>() => continue,
+                Err(err) => return Err(err),
+            };
+            if first {
+                match visit(&block, depth)? {
+                    Control::Continue => {}
+                    Control::Skip => {
+                        depths.insert(cid, 0);
+                        continue;
+                    }
+                    Control::Stop => return Ok(()),
+                }
+            }
+            if self.max_depth.map(|max| depth >= max).unwrap_or(false) {
+                continue;
+            }
+            refs.clear();
+            block.references(&mut refs)?;
+            let children = refs
+                .iter()
+                .filter(|cid| expand(&depths, cid, depth + 1))
+                .map(|cid| (*cid, depth + 1));
+            match self.order {
+                // Pushed in reverse, so that the children are visited in order.
+                Order::DepthFirst => queue.extend(children.rev()),
+                Order::BreadthFirst => queue.extend(children),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbor::DagCborCodec;
+    use crate::ipld;
+    use crate::mem::MemStore;
+    use crate::multihash::Code;
+    use crate::store::{DefaultParams, Store};
+
+    struct Dag {
+        store: MemStore<DefaultParams>,
+        root: Cid,
+        names: Vec<(Cid, &'static str)>,
+    }
+
+    impl Dag {
+        //       root
+        //      /    \
+        //     a      b
+        //    / \    /
+        //   c   d  c
+        fn new() -> Self {
+            let store = MemStore::default();
+            let mut names = vec![];
+            let mut insert = |name, ipld: Ipld| {
+                let block = Block::encode(DagCborCodec, Code::Blake3_256, &ipld).unwrap();
+                let cid = *block.cid();
+                store.insert(block).unwrap();
+                names.push((cid, name));
+                cid
+            };
+            let c = insert("c", ipld!("c"));
+            let d = insert("d", ipld!("d"));
+            let a = insert("a", ipld!([c, d]));
+            let b = insert("b", ipld!([c]));
+            let root = insert("root", ipld!([a, b]));
+            Self { store, root, names }
+        }
+
+        fn name(&self, cid: &Cid) -> &'static str {
+            self.names.iter().find(|(c, _)| c == cid).unwrap().1
+        }
+
+        fn walk(&self, traversal: Traversal, control: Control) -> Vec<(&'static str, usize)> {
+            let mut visited = vec![];
+            traversal
+                .walk(
+                    &self.root,
+                    |cid| self.store.get(cid),
+                    |block, depth| {
+                        visited.push((self.name(block.cid()), depth));
+                        Ok(if self.name(block.cid()) == "a" {
+                            control
+                        } else {
+                            Control::Continue
+                        })
+                    },
+                )
+                .unwrap();
+            visited
+        }
+    }
+
+    #[test]
+    fn test_order() {
+        let dag = Dag::new();
+        let dfs = dag.walk(Traversal::new(Order::DepthFirst), Control::Continue);
+        assert_eq!(
+            dfs,
+            vec![("root", 0), ("a", 1), ("c", 2), ("d", 2), ("b", 1)]
+        );
+        let bfs = dag.walk(Traversal::new(Order::BreadthFirst), Control::Continue);
+        assert_eq!(
+            bfs,
+            vec![("root", 0), ("a", 1), ("b", 1), ("c", 2), ("d", 2)]
+        );
+    }
+
+    #[test]
+    fn test_control() {
+        let dag = Dag::new();
+        let skip = dag.walk(Traversal::default(), Control::Skip);
+        assert_eq!(skip, vec![("root", 0), ("a", 1), ("b", 1), ("c", 2)]);
+        let stop = dag.walk(Traversal::default(), Control::Stop);
+        assert_eq!(stop, vec![("root", 0), ("a", 1)]);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let dag = Dag::new();
+        let walk = dag.walk(
+            Traversal::new(Order::BreadthFirst).max_depth(1),
+            Control::Continue,
+        );
+        assert_eq!(walk, vec![("root", 0), ("a", 1), ("b", 1)]);
+    }
+
+    #[test]
+    fn test_max_depth_diamond() {
+        // root -> a -> b -> c and root -> b
+        let store = MemStore::<DefaultParams>::default();
+        let insert = |ipld: Ipld| {
+            let block = Block::encode(DagCborCodec, Code::Blake3_256, &ipld).unwrap();
+            let cid = *block.cid();
+            store.insert(block).unwrap();
+            cid
+        };
+        let c = insert(ipld!("c"));
+        let b = insert(ipld!([c]));
+        let a = insert(ipld!([b]));
+        let root = insert(ipld!([a, b]));
+        for order in [Order::DepthFirst, Order::BreadthFirst] {
+            let mut visited = vec![];
+            Traversal::new(order)
+                .max_depth(2)
+                .walk(
+                    &root,
+                    |cid| store.get(cid),
+                    |block, _| {
+                        visited.push(*block.cid());
+                        Ok(Control::Continue)
+                    },
+                )
+                .unwrap();
+            visited.sort();
+            let mut expected = vec![root, a, b, c];
+            expected.sort();
+            assert_eq!(visited, expected);
+        }
+    }
+
+    #[test]
+    fn test_missing() {
+        let dag = Dag::new();
+        let (d, _) = dag.names[1];
+        dag.store.remove(&d).unwrap();
+        let err = Traversal::default()
+            .walk(
+                &dag.root,
+                |cid| dag.store.get(cid),
+                |_, _| Ok(Control::Continue),
+            )
+            .unwrap_err();
+        assert!(err.is::<BlockNotFound>());
+
+        let walk = dag.walk(Traversal::default().ignore_missing(true), Control::Continue);
+        assert_eq!(walk, vec![("root", 0), ("a", 1), ("c", 2), ("b", 1)]);
+    }
+}